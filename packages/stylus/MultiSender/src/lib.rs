@@ -10,6 +10,7 @@
 //! - Gas optimization through single transaction
 //! - Safety checks and event emission
 //! - Owner-only emergency functions
+//! - Owner recovery of ERC20, ERC721 and ERC1155 assets sent by mistake
 //!
 //! Note: this code is a template-only and has not been audited.
 //!
@@ -45,6 +46,7 @@ pub enum Error {
     TransferFailed,
     InvalidRecipient,
     InvalidAmount,
+    ArithmeticOverflow,
 }
 
 impl From<ownable::Error> for Error {
@@ -62,6 +64,9 @@ sol! {
     event BatchTokenTransfer(address indexed sender, address indexed token, uint256 totalAmount, uint256 recipientCount);
     event TransferSuccess(address indexed recipient, uint256 amount);
     event TransferFailed(address indexed recipient, uint256 amount, string reason);
+    event ERC20Recovered(address indexed token, address indexed to, uint256 amount);
+    event ERC721Recovered(address indexed token, address indexed to, uint256 tokenId);
+    event ERC1155Recovered(address indexed token, address indexed to, uint256 id, uint256 amount);
 }
 
 // ERC20 interface for token transfers
@@ -72,6 +77,14 @@ sol! {
         function balanceOf(address account) external view returns (uint256);
         function allowance(address owner, address spender) external view returns (uint256);
     }
+
+    interface IERC721 {
+        function safeTransferFrom(address from, address to, uint256 tokenId) external;
+    }
+
+    interface IERC1155 {
+        function safeTransferFrom(address from, address to, uint256 id, uint256 value, bytes data) external;
+    }
 }
 
 // Define persistent storage using the Solidity ABI.
@@ -139,7 +152,9 @@ impl MultiSender {
             if *amount == U256::ZERO {
                 return Err(Error::InvalidAmount);
             }
-            total_amount += *amount;
+            total_amount = total_amount
+                .checked_add(*amount)
+                .ok_or(Error::ArithmeticOverflow)?;
         }
 
         // Check if sent value covers total amount
@@ -179,14 +194,7 @@ impl MultiSender {
         }
 
         // Update statistics
-        let current_total_tx = self.total_transactions.get();
-        self.total_transactions.set(current_total_tx + U256::from(1));
-        
-        let current_total_recipients = self.total_recipients.get();
-        self.total_recipients.set(current_total_recipients + U256::from(successful_transfers));
-        
-        let current_user_count = self.user_transaction_count.get(sender);
-        self.user_transaction_count.insert(sender, current_user_count + U256::from(1));
+        self.record_batch(sender, U256::from(successful_transfers))?;
 
         // Emit batch transfer event
         log(self.vm(), BatchEthTransfer {
@@ -232,7 +240,9 @@ impl MultiSender {
             if *amount == U256::ZERO {
                 return Err(Error::InvalidAmount);
             }
-            total_amount += *amount;
+            total_amount = total_amount
+                .checked_add(*amount)
+                .ok_or(Error::ArithmeticOverflow)?;
         }
 
         // Perform transfers using transferFrom
@@ -290,14 +300,7 @@ impl MultiSender {
         }
 
         // Update statistics
-        let current_total_tx = self.total_transactions.get();
-        self.total_transactions.set(current_total_tx + U256::from(1));
-        
-        let current_total_recipients = self.total_recipients.get();
-        self.total_recipients.set(current_total_recipients + U256::from(successful_transfers));
-        
-        let current_user_count = self.user_transaction_count.get(sender);
-        self.user_transaction_count.insert(sender, current_user_count + U256::from(1));
+        self.record_batch(sender, U256::from(successful_transfers))?;
 
         // Emit batch transfer event
         log(self.vm(), BatchTokenTransfer {
@@ -323,6 +326,101 @@ impl MultiSender {
         Ok(())
     }
 
+    /// Recover ERC20 tokens sent to the contract by mistake (owner only).
+    /// This variant never holds tokens for users, so the whole balance is recoverable.
+    pub fn recover_erc20(
+        &mut self,
+        token: Address,
+        to: Address,
+        amount: U256,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient);
+        }
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount);
+        }
+
+        let transfer_call = IERC20::transferCall { to, amount };
+        match self.vm().call_contract(token, &transfer_call.abi_encode()) {
+            // Tokens that return no data are treated as successful
+            Ok(result) if result.is_empty() => {}
+            Ok(result) => {
+                if !bool::abi_decode(&result, true).unwrap_or(false) {
+                    return Err(Error::TransferFailed);
+                }
+            }
+            Err(_) => return Err(Error::TransferFailed),
+        }
+
+        log(self.vm(), ERC20Recovered { token, to, amount });
+        Ok(())
+    }
+
+    /// Recover an ERC721 token sent to the contract by mistake (owner only)
+    pub fn recover_erc721(
+        &mut self,
+        token: Address,
+        to: Address,
+        token_id: U256,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient);
+        }
+
+        let transfer_call = IERC721::safeTransferFromCall {
+            from: self.vm().contract_address(),
+            to,
+            tokenId: token_id,
+        };
+        if self.vm().call_contract(token, &transfer_call.abi_encode()).is_err() {
+            return Err(Error::TransferFailed);
+        }
+
+        log(self.vm(), ERC721Recovered {
+            token,
+            to,
+            tokenId: token_id,
+        });
+        Ok(())
+    }
+
+    /// Recover ERC1155 tokens sent to the contract by mistake (owner only)
+    pub fn recover_erc1155(
+        &mut self,
+        token: Address,
+        to: Address,
+        id: U256,
+        amount: U256,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient);
+        }
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount);
+        }
+
+        let transfer_call = IERC1155::safeTransferFromCall {
+            from: self.vm().contract_address(),
+            to,
+            id,
+            value: amount,
+            data: Default::default(),
+        };
+        if self.vm().call_contract(token, &transfer_call.abi_encode()).is_err() {
+            return Err(Error::TransferFailed);
+        }
+
+        log(self.vm(), ERC1155Recovered { token, to, id, amount });
+        Ok(())
+    }
+
     /// Get estimated gas for batch ETH transfer
     pub fn estimate_batch_eth_gas(&self, recipient_count: U256) -> U256 {
         // Base gas cost + per-recipient cost
@@ -349,6 +447,30 @@ impl MultiSender {
     }
 }
 
+impl MultiSender {
+    /// Add a finished batch to the contract statistics
+    fn record_batch(&mut self, sender: Address, successful_transfers: U256) -> Result<(), Error> {
+        let current_total_tx = self.total_transactions.get();
+        let total_tx = current_total_tx
+            .checked_add(U256::from(1))
+            .ok_or(Error::ArithmeticOverflow)?;
+        self.total_transactions.set(total_tx);
+
+        let current_total_recipients = self.total_recipients.get();
+        let total_recipients = current_total_recipients
+            .checked_add(successful_transfers)
+            .ok_or(Error::ArithmeticOverflow)?;
+        self.total_recipients.set(total_recipients);
+
+        let current_user_count = self.user_transaction_count.get(sender);
+        let user_count = current_user_count
+            .checked_add(U256::from(1))
+            .ok_or(Error::ArithmeticOverflow)?;
+        self.user_transaction_count.insert(sender, user_count);
+        Ok(())
+    }
+}
+
 /// Implementation of the IOwnable interface
 #[public]
 impl IOwnable for MultiSender {
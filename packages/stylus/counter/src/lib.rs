@@ -18,6 +18,10 @@
 //! The program is ABI-equivalent with Solidity, which means you can call it from both Solidity and Rust.
 //! To do this, run `cargo stylus export-abi`.
 //!
+//! The counter can also run behind the `erc1967-proxy` crate, in which case `initialize` replaces
+//! the constructor and the account that initialized it may upgrade it in place (UUPS). The
+//! constructor locks `initialize` on the implementation itself.
+//!
 //! Note: this code is a template-only and has not been audited.
 //!
 // Allow `cargo stylus export-abi` to generate a main function.
@@ -30,30 +34,86 @@ extern crate alloc;
 use alloc::vec::Vec;
 
 /// Import items from the SDK. The prelude contains common traits and macros.
-use stylus_sdk::{alloy_primitives::U256, prelude::*};
+use stylus_sdk::{
+    abi::Bytes,
+    alloy_primitives::{Address, B256, U256},
+    alloy_sol_types::sol,
+    prelude::*,
+};
+
+#[path = "../../uups-common/uups.rs"]
+mod uups;
+
+use uups::{ERC1967InvalidImplementation, UUPSUnauthorizedCallContext, UpgradeCallFailed};
+
+// Define the upgrade authorization error
+sol! {
+    error UnauthorizedAccount(address account);
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    UnauthorizedAccount(UnauthorizedAccount),
+    InvalidImplementation(ERC1967InvalidImplementation),
+    UpgradeCallFailed(UpgradeCallFailed),
+    UnauthorizedCallContext(UUPSUnauthorizedCallContext),
+}
+
+impl From<uups::UupsError> for Error {
+    fn from(value: uups::UupsError) -> Self {
+        match value {
+            uups::UupsError::InvalidImplementation(e) => Error::InvalidImplementation(e),
+            uups::UupsError::UpgradeCallFailed(e) => Error::UpgradeCallFailed(e),
+            uups::UupsError::UnauthorizedCallContext(e) => Error::UnauthorizedCallContext(e),
+        }
+    }
+}
 
 // Define some persistent storage using the Solidity ABI.
 // `Counter` will be the entrypoint.
+//
+// The counter can run behind an ERC-1967 proxy, so the layout is append-only:
+// new fields go at the end and existing fields are never removed or reordered.
 sol_storage! {
     #[entrypoint]
     pub struct Counter {
         uint256 number;
         bool is_initialized;
+        address owner;
     }
 }
 
 /// Declare that `Counter` is a contract with the following external methods.
 #[public]
 impl Counter {
+    /// Sets the number, makes the deployer the owner and locks `initialize`, including on
+    /// implementations deployed for a proxy. On Orbit chains without constructor support
+    /// `initialize` replaces it and should be called right after deploying.
+    #[constructor]
+    pub fn constructor(&mut self, initial_number: U256) {
+        self.number.set(initial_number);
+        self.is_initialized.set(true);
+        let deployer = self.vm().tx_origin();
+        self.owner.set(deployer);
+    }
+
     pub fn initialize(&mut self, initial_number: U256) {
         if !self.is_initialized.get() {
             self.number.set(initial_number);
             self.is_initialized.set(true);
+            let sender = self.vm().msg_sender();
+            self.owner.set(sender);
         } else {
             panic!("Counter already initialized");
         }
     }
 
+    /// Gets the account allowed to upgrade the counter.
+    pub fn owner(&self) -> Address {
+        self.owner.get()
+    }
+
     /// Gets the number from storage.
     pub fn number(&self) -> U256 {
         self.number.get()
@@ -86,6 +146,31 @@ impl Counter {
         let number = self.number.get();
         self.set_number(number + self.vm().msg_value());
     }
+
+    /// Gets the UUPS identifier, which is the ERC-1967 implementation slot.
+    /// Fails through a proxy so the proxy is never taken for an implementation.
+    #[selector(name = "proxiableUUID")]
+    pub fn proxiable_uuid(&self) -> Result<B256, Error> {
+        uups::not_delegated(self)?;
+        Ok(uups::IMPLEMENTATION_SLOT)
+    }
+
+    /// Upgrades the proxy to a new implementation and optionally calls it (owner only).
+    /// Only works through a proxy.
+    #[payable]
+    pub fn upgrade_to_and_call(
+        &mut self,
+        new_implementation: Address,
+        data: Bytes,
+    ) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        uups::upgrade_to_and_call(self, new_implementation, &data)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -118,4 +203,85 @@ mod test {
         contract.add_from_msg_value();
         assert_eq!(U256::from(102), contract.number());
     }
+
+    sol_storage! {
+        /// `Counter` with a field appended, as the next implementation would declare it.
+        pub struct CounterV2 {
+            uint256 number;
+            bool is_initialized;
+            address owner;
+            uint256 decrements;
+        }
+    }
+
+    #[test]
+    fn test_upgrade() {
+        use stylus_sdk::alloy_sol_types::SolCall;
+        use stylus_sdk::testing::TestVM;
+        use uups::{IERC1822Proxiable, IMPLEMENTATION_SLOT};
+        let vm = TestVM::default();
+        let new_implementation = Address::from([6u8; 20]);
+
+        // The constructor locks `initialize` on a directly deployed counter or implementation
+        let mut implementation = Counter::from(&vm);
+        implementation.constructor(U256::from(1));
+        assert_eq!(implementation.owner(), vm.tx_origin());
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            implementation.initialize(U256::from(7))
+        }))
+        .is_err());
+        assert_eq!(implementation.number(), U256::from(1));
+
+        // Upgrades only run through a proxy, and proxies are not implementations
+        vm.set_sender(vm.tx_origin());
+        let uuid_call = IERC1822Proxiable::proxiableUUIDCall {}.abi_encode();
+        vm.mock_static_call(new_implementation, uuid_call, Ok(IMPLEMENTATION_SLOT.to_vec()));
+        assert!(matches!(
+            implementation.upgrade_to_and_call(new_implementation, Bytes::from(vec![])),
+            Err(Error::UnauthorizedCallContext(_))
+        ));
+        assert_eq!(implementation.proxiable_uuid().ok(), Some(IMPLEMENTATION_SLOT));
+
+        // Fresh storage behind a proxy, which holds the current implementation
+        let vm = TestVM::default();
+        let mut contract = Counter::from(&vm);
+        let current_implementation = Address::from([5u8; 20]).into_word();
+        unsafe { vm.storage_cache_bytes32(uups::implementation_slot(), current_implementation) };
+        assert!(contract.proxiable_uuid().is_err());
+
+        contract.initialize(U256::from(41));
+        contract.increment();
+        assert_eq!(contract.owner(), vm.msg_sender());
+
+        // Upgrades require a UUPS-compatible implementation
+        assert!(matches!(
+            contract.upgrade_to_and_call(new_implementation, Bytes::from(vec![])),
+            Err(Error::InvalidImplementation(_))
+        ));
+        let uuid_call = IERC1822Proxiable::proxiableUUIDCall {}.abi_encode();
+        vm.mock_static_call(new_implementation, uuid_call, Ok(IMPLEMENTATION_SLOT.to_vec()));
+
+        // Only the account that initialized the counter may upgrade it
+        let owner = vm.msg_sender();
+        vm.set_sender(Address::from([2u8; 20]));
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_err());
+
+        vm.set_sender(owner);
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_ok());
+        assert_eq!(
+            Address::from_word(vm.storage_load_bytes32(uups::implementation_slot())),
+            new_implementation
+        );
+
+        // The appended layout reads the existing state and starts the new field empty
+        let upgraded = CounterV2::from(&vm);
+        assert_eq!(upgraded.number.get(), U256::from(42));
+        assert!(upgraded.is_initialized.get());
+        assert_eq!(upgraded.owner.get(), owner);
+        assert_eq!(upgraded.decrements.get(), U256::ZERO);
+    }
 }
@@ -0,0 +1,49 @@
+//! Integration tests against a local nitro-devnode (see `tests-common/devnode.rs`)
+
+#[path = "../../tests-common/devnode.rs"]
+mod devnode;
+
+use ethers::prelude::*;
+use eyre::Result;
+
+abigen!(
+    Counter,
+    r#"[
+        function initialize(uint256 initialNumber) external
+        function owner() external view returns (address)
+        function number() external view returns (uint256)
+        function setNumber(uint256 newNumber) external
+        function mulNumber(uint256 newNumber) external
+        function addNumber(uint256 newNumber) external
+        function increment() external
+        function addFromMsgValue() external payable
+    ]"#
+);
+
+#[tokio::test]
+async fn test_counter_on_devnode() -> Result<()> {
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let counter = Counter::new(devnode::deploy(&["5".to_string()])?, client.clone());
+
+    // The constructor sets the number and the owner, and locks `initialize`
+    assert_eq!(counter.number().call().await?, U256::from(5));
+    assert_eq!(counter.owner().call().await?, client.address());
+    assert!(counter.initialize(U256::from(7)).send().await.is_err());
+
+    // Arithmetic
+    counter.increment().send().await?.await?;
+    counter.mul_number(U256::from(2)).send().await?.await?;
+    counter.add_number(U256::from(3)).send().await?.await?;
+    assert_eq!(counter.number().call().await?, U256::from(15));
+
+    // Payable calls add the sent value
+    counter.add_from_msg_value().value(10).send().await?.await?;
+    assert_eq!(counter.number().call().await?, U256::from(25));
+
+    counter.set_number(U256::zero()).send().await?.await?;
+    assert_eq!(counter.number().call().await?, U256::zero());
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//!
+//! ERC-1967 Proxy in Stylus Rust
+//!
+//! A UUPS-style proxy that forwards every call to an implementation contract with `delegatecall`,
+//! so `multi-sender`, `your-contract` and `counter` keep their storage across upgrades.
+//!
+//! The implementation address lives in the ERC-1967 slot, far away from the sequential slots
+//! used by `sol_storage!`, and the proxy declares no storage fields of its own. Upgrades are
+//! authorized and performed by the implementation's `upgrade_to_and_call`.
+//!
+//! Deploy an implementation first, then this proxy with
+//! `--constructor-args <implementation> <initialize calldata>`.
+//!
+//! Note: this code is a template-only and has not been audited.
+//!
+
+// Allow `cargo stylus export-abi` to generate a main function.
+#![cfg_attr(not(any(test, feature = "export-abi")), no_main)]
+#![cfg_attr(not(any(test, feature = "export-abi")), no_std)]
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+/// Import items from the SDK. The prelude contains common traits and macros.
+use stylus_sdk::{
+    abi::Bytes,
+    alloy_primitives::Address,
+    alloy_sol_types::sol,
+    prelude::calls::context::Call,
+    prelude::*,
+    stylus_core::log,
+    ArbResult,
+};
+
+#[path = "../../uups-common/uups.rs"]
+mod uups;
+
+pub use uups::{implementation_slot, IMPLEMENTATION_SLOT};
+use uups::{ERC1967InvalidImplementation, Upgraded};
+
+// Define proxy errors
+sol! {
+    error ProxyInitializationFailed(bytes returnData);
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    InvalidImplementation(ERC1967InvalidImplementation),
+    InitializationFailed(ProxyInitializationFailed),
+}
+
+// The proxy has no sequential storage so it never collides with the implementation's layout.
+// `Erc1967Proxy` will be the entrypoint.
+sol_storage! {
+    #[entrypoint]
+    pub struct Erc1967Proxy {}
+}
+
+/// Declare that `Erc1967Proxy` is a contract with the following external methods.
+#[public]
+impl Erc1967Proxy {
+    /// Set the implementation and run `data` (usually an `initialize` call) against it
+    #[constructor]
+    #[payable]
+    pub fn constructor(&mut self, implementation: Address, data: Bytes) -> Result<(), Error> {
+        if implementation == Address::ZERO || self.vm().code_size(implementation) == 0 {
+            return Err(Error::InvalidImplementation(ERC1967InvalidImplementation {
+                implementation,
+            }));
+        }
+
+        unsafe {
+            self.vm()
+                .storage_cache_bytes32(implementation_slot(), implementation.into_word());
+        }
+        self.vm().flush_cache(false);
+
+        log(self.vm(), Upgraded { implementation });
+
+        if !data.is_empty() {
+            let config = Call::new();
+            if let Err(err) = unsafe { self.vm().delegate_call(&config, implementation, &data) } {
+                return Err(Error::InitializationFailed(ProxyInitializationFailed {
+                    returnData: Vec::<u8>::from(err).into(),
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forward every other call to the implementation
+    #[fallback]
+    #[payable]
+    pub fn fallback(&mut self, calldata: &[u8]) -> ArbResult {
+        let implementation =
+            Address::from_word(self.vm().storage_load_bytes32(implementation_slot()));
+        let config = Call::new();
+        unsafe { self.vm().delegate_call(&config, implementation, calldata) }
+            .map_err(|err| err.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stylus_sdk::testing::*;
+
+    #[test]
+    fn test_proxy() {
+        let vm = TestVM::default();
+        let mut contract = Erc1967Proxy::from(&vm);
+
+        // An implementation without code is rejected
+        let implementation = Address::from([7u8; 20]);
+        assert!(contract
+            .constructor(implementation, Bytes::from(vec![]))
+            .is_err());
+
+        vm.set_code(implementation, vec![0xef, 0xf0, 0x00, 0x00]);
+        assert!(contract
+            .constructor(implementation, Bytes::from(vec![]))
+            .is_ok());
+        assert_eq!(
+            Address::from_word(vm.storage_load_bytes32(implementation_slot())),
+            implementation
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//!
+//! Mock ERC20 Contract in Stylus Rust
+//!
+//! A mintable ERC20 whose transfers can be switched into the misbehaviours found in real tokens,
+//! used to exercise the token paths of `multi-sender` on a local nitro-devnode.
+//!
+//! Features:
+//! - Open `mint` for test balances
+//! - Transfer modes: standard, return `false`, revert, or return no data
+//! - Fee-on-transfer in basis points, burned from the transferred amount
+//! - Re-entry hook that calls a configured target before moving balances
+//!
+//! `transfer` and `transferFrom` are dispatched from the fallback so they can return raw data,
+//! which means they do not appear in the exported ABI. Use the standard IERC20 ABI to call them.
+//!
+//! Note: this code is for local testing only and has not been audited.
+//!
+
+// Allow `cargo stylus export-abi` to generate a main function.
+#![cfg_attr(not(any(test, feature = "export-abi")), no_main)]
+#![cfg_attr(not(any(test, feature = "export-abi")), no_std)]
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Import items from the SDK. The prelude contains common traits and macros.
+use stylus_sdk::{
+    abi::Bytes,
+    alloy_primitives::{Address, U16, U256, U8},
+    alloy_sol_types::{sol, SolCall, SolValue},
+    prelude::calls::context::Call,
+    prelude::*,
+    stylus_core::log,
+    ArbResult,
+};
+
+/// Transfers move balances and return `true`
+pub const MODE_STANDARD: u8 = 0;
+/// Transfers return `false` without moving balances
+pub const MODE_RETURN_FALSE: u8 = 1;
+/// Transfers revert with `TransferReverted`
+pub const MODE_REVERT: u8 = 2;
+/// Transfers move balances and return no data, like USDT
+pub const MODE_NO_RETURN: u8 = 3;
+
+/// Fees are expressed in basis points of the transferred amount
+const MAX_FEE_BPS: u16 = 10_000;
+
+// Define ERC20 events
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event Approval(address indexed owner, address indexed spender, uint256 value);
+}
+
+// Define errors
+sol! {
+    error InsufficientBalance(address account, uint256 balance, uint256 needed);
+    error InsufficientAllowance(address spender, uint256 allowance, uint256 needed);
+    error TransferReverted();
+    error InvalidMode(uint8 mode);
+    error InvalidFee(uint16 feeBps);
+}
+
+// Transfer functions dispatched from the fallback
+sol! {
+    interface IERC20Transfers {
+        function transfer(address to, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+    }
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    InsufficientBalance(InsufficientBalance),
+    InsufficientAllowance(InsufficientAllowance),
+    TransferReverted(TransferReverted),
+    InvalidMode(InvalidMode),
+    InvalidFee(InvalidFee),
+}
+
+// Define persistent storage using the Solidity ABI.
+// `MockErc20` will be the entrypoint.
+sol_storage! {
+    #[entrypoint]
+    pub struct MockErc20 {
+        mapping(address => uint256) balances;
+        mapping(address => mapping(address => uint256)) allowances;
+        uint256 total_supply;
+        uint8 mode;
+        uint16 fee_bps;
+        address reentry_target;
+        bytes reentry_calldata;
+    }
+}
+
+/// Declare that `MockErc20` is a contract with the following external methods.
+#[public]
+impl MockErc20 {
+    /// Get the token name
+    pub fn name(&self) -> String {
+        "Mock Token".to_string()
+    }
+
+    /// Get the token symbol
+    pub fn symbol(&self) -> String {
+        "MOCK".to_string()
+    }
+
+    /// Get the token decimals
+    pub fn decimals(&self) -> u8 {
+        18
+    }
+
+    /// Get the total supply
+    pub fn total_supply(&self) -> U256 {
+        self.total_supply.get()
+    }
+
+    /// Get the balance of an account
+    pub fn balance_of(&self, account: Address) -> U256 {
+        self.balances.get(account)
+    }
+
+    /// Get the amount `spender` may transfer on behalf of `owner`
+    pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        self.allowances.getter(owner).get(spender)
+    }
+
+    /// Get the current transfer mode
+    pub fn mode(&self) -> u8 {
+        self.mode.get().to::<u8>()
+    }
+
+    /// Get the transfer fee in basis points
+    pub fn fee_bps(&self) -> u16 {
+        self.fee_bps.get().to::<u16>()
+    }
+
+    /// Get the re-entry target and calldata
+    pub fn reentry(&self) -> (Address, Bytes) {
+        (self.reentry_target.get(), Bytes::from(self.reentry_calldata.get_bytes()))
+    }
+
+    /// Mint `amount` to `to`. Anyone can mint test balances.
+    pub fn mint(&mut self, to: Address, amount: U256) {
+        let balance = self.balances.get(to);
+        self.balances.insert(to, balance + amount);
+        let total_supply = self.total_supply.get();
+        self.total_supply.set(total_supply + amount);
+
+        log(self.vm(), Transfer {
+            from: Address::ZERO,
+            to,
+            value: amount,
+        });
+    }
+
+    /// Allow `spender` to transfer up to `amount` of the caller's balance
+    pub fn approve(&mut self, spender: Address, amount: U256) -> bool {
+        let owner = self.vm().msg_sender();
+        self.allowances.setter(owner).insert(spender, amount);
+
+        log(self.vm(), Approval {
+            owner,
+            spender,
+            value: amount,
+        });
+
+        true
+    }
+
+    /// Switch the transfer mode
+    pub fn set_mode(&mut self, mode: u8) -> Result<(), Error> {
+        if mode > MODE_NO_RETURN {
+            return Err(Error::InvalidMode(InvalidMode { mode }));
+        }
+        self.mode.set(U8::from(mode));
+        Ok(())
+    }
+
+    /// Burn `fee_bps` of every transfer
+    pub fn set_fee_bps(&mut self, fee_bps: u16) -> Result<(), Error> {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(Error::InvalidFee(InvalidFee { feeBps: fee_bps }));
+        }
+        self.fee_bps.set(U16::from(fee_bps));
+        Ok(())
+    }
+
+    /// Call `target` with `data` before moving balances on each transfer, or disable with zero
+    pub fn set_reentry(&mut self, target: Address, data: Bytes) {
+        self.reentry_target.set(target);
+        self.reentry_calldata.set_bytes(data.as_slice());
+    }
+
+    /// Dispatch `transfer` and `transferFrom` so the mode can shape the returned data
+    #[fallback]
+    pub fn fallback(&mut self, calldata: &[u8]) -> ArbResult {
+        let caller = self.vm().msg_sender();
+        let (from, to, amount) =
+            if let Ok(call) = IERC20Transfers::transferCall::abi_decode(calldata, true) {
+                (caller, call.to, call.amount)
+            } else if let Ok(call) = IERC20Transfers::transferFromCall::abi_decode(calldata, true) {
+                (call.from, call.to, call.amount)
+            } else {
+                return Err(Vec::new());
+            };
+
+        match self.mode() {
+            MODE_RETURN_FALSE => return Ok(false.abi_encode()),
+            MODE_REVERT => return Err(Error::TransferReverted(TransferReverted {}).into()),
+            _ => {}
+        }
+
+        // Hand control to the re-entry target first, like a malicious token hook would
+        let target = self.reentry_target.get();
+        if target != Address::ZERO {
+            let data = self.reentry_calldata.get_bytes();
+            let config = Call::new();
+            self.vm().call(&config, target, &data)?;
+        }
+
+        if from != caller {
+            self.spend_allowance(from, caller, amount)?;
+        }
+        self.move_balance(from, to, amount)?;
+
+        if self.mode() == MODE_NO_RETURN {
+            return Ok(Vec::new());
+        }
+        Ok(true.abi_encode())
+    }
+}
+
+impl MockErc20 {
+    /// Decrease the allowance `owner` granted to `spender`
+    fn spend_allowance(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Result<(), Error> {
+        let mut allowances = self.allowances.setter(owner);
+        let allowance = allowances.get(spender);
+        if allowance < amount {
+            return Err(Error::InsufficientAllowance(InsufficientAllowance {
+                spender,
+                allowance,
+                needed: amount,
+            }));
+        }
+        if allowance != U256::MAX {
+            allowances.insert(spender, allowance - amount);
+        }
+        Ok(())
+    }
+
+    /// Move `amount` minus the fee between two balances, burning the fee
+    fn move_balance(&mut self, from: Address, to: Address, amount: U256) -> Result<(), Error> {
+        let balance = self.balances.get(from);
+        if balance < amount {
+            return Err(Error::InsufficientBalance(InsufficientBalance {
+                account: from,
+                balance,
+                needed: amount,
+            }));
+        }
+
+        let fee = amount * U256::from(self.fee_bps()) / U256::from(MAX_FEE_BPS);
+        let received = amount - fee;
+        self.balances.insert(from, balance - amount);
+        let to_balance = self.balances.get(to);
+        self.balances.insert(to, to_balance + received);
+
+        if fee > U256::ZERO {
+            let total_supply = self.total_supply.get();
+            self.total_supply.set(total_supply - fee);
+            log(self.vm(), Transfer {
+                from,
+                to: Address::ZERO,
+                value: fee,
+            });
+        }
+
+        log(self.vm(), Transfer {
+            from,
+            to,
+            value: received,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stylus_sdk::testing::*;
+
+    fn transfer(to: Address, amount: U256) -> Vec<u8> {
+        IERC20Transfers::transferCall { to, amount }.abi_encode()
+    }
+
+    #[test]
+    fn test_mock_erc20() {
+        let vm = TestVM::default();
+        let mut contract = MockErc20::from(&vm);
+        let sender = vm.msg_sender();
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+
+        contract.mint(sender, U256::from(1000));
+        assert_eq!(contract.total_supply(), U256::from(1000));
+
+        // Standard transfers return true
+        let result = contract.fallback(&transfer(alice, U256::from(100)));
+        assert!(matches!(result, Ok(data) if data == true.abi_encode()));
+        assert_eq!(contract.balance_of(alice), U256::from(100));
+        assert!(contract.fallback(&transfer(alice, U256::from(1000))).is_err());
+
+        // transferFrom spends the allowance
+        assert!(contract.approve(bob, U256::from(50)));
+        vm.set_sender(bob);
+        let call = IERC20Transfers::transferFromCall {
+            from: sender,
+            to: bob,
+            amount: U256::from(60),
+        };
+        assert!(contract.fallback(&call.abi_encode()).is_err());
+        let call = IERC20Transfers::transferFromCall {
+            from: sender,
+            to: bob,
+            amount: U256::from(50),
+        };
+        assert!(contract.fallback(&call.abi_encode()).is_ok());
+        assert_eq!(contract.allowance(sender, bob), U256::ZERO);
+        vm.set_sender(sender);
+
+        // Returning false leaves balances untouched
+        assert!(contract.set_mode(MODE_RETURN_FALSE).is_ok());
+        let result = contract.fallback(&transfer(alice, U256::from(10)));
+        assert!(matches!(result, Ok(data) if data == false.abi_encode()));
+        assert_eq!(contract.balance_of(alice), U256::from(100));
+
+        // Reverting transfers fail
+        assert!(contract.set_mode(MODE_REVERT).is_ok());
+        assert!(contract.fallback(&transfer(alice, U256::from(10))).is_err());
+
+        // Tokens without return data still move balances
+        assert!(contract.set_mode(MODE_NO_RETURN).is_ok());
+        let result = contract.fallback(&transfer(alice, U256::from(10)));
+        assert!(matches!(result, Ok(data) if data.is_empty()));
+        assert_eq!(contract.balance_of(alice), U256::from(110));
+        assert!(contract.set_mode(4).is_err());
+
+        // Fees are burned from the transferred amount
+        assert!(contract.set_mode(MODE_STANDARD).is_ok());
+        assert!(contract.set_fee_bps(1_000).is_ok());
+        assert!(contract.set_fee_bps(10_001).is_err());
+        assert!(contract.fallback(&transfer(alice, U256::from(100))).is_ok());
+        assert_eq!(contract.balance_of(alice), U256::from(200));
+        assert_eq!(contract.total_supply(), U256::from(990));
+
+        // Unknown calls revert
+        assert!(contract.fallback(&[0x12, 0x34, 0x56, 0x78]).is_err());
+    }
+
+    #[test]
+    fn test_reentry_hook() {
+        let vm = TestVM::default();
+        let mut contract = MockErc20::from(&vm);
+        let sender = vm.msg_sender();
+        let target = Address::from([9u8; 20]);
+        let data = vec![0xab, 0xcd, 0xef, 0x01];
+        contract.mint(sender, U256::from(100));
+
+        // A failing re-entry makes the whole transfer fail
+        contract.set_reentry(target, Bytes::from(data.clone()));
+        assert_eq!(contract.reentry().0, target);
+        vm.mock_call(target, data, Err(vec![]));
+        assert!(contract.fallback(&transfer(target, U256::from(10))).is_err());
+        assert_eq!(contract.balance_of(sender), U256::from(100));
+
+        contract.set_reentry(Address::ZERO, Bytes::from(vec![]));
+        assert!(contract.fallback(&transfer(target, U256::from(10))).is_ok());
+    }
+}
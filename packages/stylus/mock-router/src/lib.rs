@@ -0,0 +1,285 @@
+//!
+//! Mock Uniswap V2 Router in Stylus Rust
+//!
+//! A router that swaps at a fixed, configurable rate against its own token balances, used to
+//! exercise the swap-and-distribute path of `multi-sender` on a local nitro-devnode without a
+//! live DEX.
+//!
+//! Features:
+//! - `swapExactETHForTokens` and `swapExactTokensForTokens` over two-token paths
+//! - `getAmountsOut` quotes at `rate_numerator / rate_denominator` of the input
+//! - `amountOutMin` and `deadline` checks with Uniswap-like errors
+//!
+//! Swap output is paid from the router's own balance, so mint the output token (for example a
+//! `mock-erc20`) to the router first. ETH and input tokens stay in the router.
+//!
+//! Note: this code is for local testing only and has not been audited.
+//!
+
+// Allow `cargo stylus export-abi` to generate a main function.
+#![cfg_attr(not(any(test, feature = "export-abi")), no_main)]
+#![cfg_attr(not(any(test, feature = "export-abi")), no_std)]
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+/// Import items from the SDK. The prelude contains common traits and macros.
+use stylus_sdk::{
+    alloy_primitives::{Address, U256},
+    alloy_sol_types::{sol, SolCall, SolValue},
+    prelude::calls::context::Call,
+    prelude::*,
+    stylus_core::log,
+};
+
+// Define router events
+sol! {
+    event Swap(address indexed sender, address indexed tokenIn, address indexed tokenOut, uint256 amountIn, uint256 amountOut, address to);
+}
+
+// Define errors
+sol! {
+    error Expired(uint256 deadline);
+    error InvalidPath();
+    error InvalidRate();
+    error InsufficientOutputAmount(uint256 amountOut, uint256 amountOutMin);
+    error TransferFailed(address token);
+}
+
+// ERC20 interface for paying out and pulling input
+sol! {
+    interface IERC20 {
+        function transfer(address to, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+    }
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    Expired(Expired),
+    InvalidPath(InvalidPath),
+    InvalidRate(InvalidRate),
+    InsufficientOutputAmount(InsufficientOutputAmount),
+    TransferFailed(TransferFailed),
+}
+
+// Define persistent storage using the Solidity ABI.
+// `MockRouter` will be the entrypoint.
+sol_storage! {
+    #[entrypoint]
+    pub struct MockRouter {
+        address weth;
+        uint256 rate_numerator;
+        uint256 rate_denominator;
+    }
+}
+
+/// Declare that `MockRouter` is a contract with the following external methods.
+#[public]
+impl MockRouter {
+    /// Set the WETH address used in ETH paths and start at a 1:1 rate
+    #[constructor]
+    pub fn constructor(&mut self, weth: Address) {
+        self.weth.set(weth);
+        self.rate_numerator.set(U256::from(1));
+        self.rate_denominator.set(U256::from(1));
+    }
+
+    /// Get the WETH address that ETH paths must start with
+    #[selector(name = "WETH")]
+    pub fn weth(&self) -> Address {
+        self.weth.get()
+    }
+
+    /// Get the `(numerator, denominator)` applied to every swap
+    pub fn rate(&self) -> (U256, U256) {
+        (self.rate_numerator.get(), self.rate_denominator.get())
+    }
+
+    /// Pay `numerator / denominator` output tokens per input token. Anyone can set the rate.
+    pub fn set_rate(&mut self, numerator: U256, denominator: U256) -> Result<(), Error> {
+        if denominator == U256::ZERO {
+            return Err(Error::InvalidRate(InvalidRate {}));
+        }
+        self.rate_numerator.set(numerator);
+        self.rate_denominator.set(denominator);
+        Ok(())
+    }
+
+    /// Quote a swap of `amount_in` along a two-token `path`
+    pub fn get_amounts_out(&self, amount_in: U256, path: Vec<Address>) -> Result<Vec<U256>, Error> {
+        if path.len() != 2 {
+            return Err(Error::InvalidPath(InvalidPath {}));
+        }
+        Ok(vec![amount_in, self.quote(amount_in)])
+    }
+
+    /// Swap the sent ETH for `path[1]`, paid to `to`
+    #[payable]
+    #[selector(name = "swapExactETHForTokens")]
+    pub fn swap_exact_eth_for_tokens(
+        &mut self,
+        amount_out_min: U256,
+        path: Vec<Address>,
+        to: Address,
+        deadline: U256,
+    ) -> Result<Vec<U256>, Error> {
+        if path.len() != 2 || path[0] != self.weth.get() {
+            return Err(Error::InvalidPath(InvalidPath {}));
+        }
+
+        let amount_in = self.vm().msg_value();
+        self.swap(amount_in, amount_out_min, &path, to, deadline)
+    }
+
+    /// Pull `amount_in` of `path[0]` from the caller and swap it for `path[1]`, paid to `to`
+    pub fn swap_exact_tokens_for_tokens(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: Vec<Address>,
+        to: Address,
+        deadline: U256,
+    ) -> Result<Vec<U256>, Error> {
+        if path.len() != 2 {
+            return Err(Error::InvalidPath(InvalidPath {}));
+        }
+
+        let this = self.vm().contract_address();
+        let call = IERC20::transferFromCall {
+            from: self.vm().msg_sender(),
+            to: this,
+            amount: amount_in,
+        };
+        self.call_token(path[0], &call.abi_encode())?;
+
+        self.swap(amount_in, amount_out_min, &path, to, deadline)
+    }
+}
+
+impl MockRouter {
+    /// Output for `amount_in` at the configured rate
+    fn quote(&self, amount_in: U256) -> U256 {
+        let denominator = self.rate_denominator.get().max(U256::from(1));
+        amount_in.saturating_mul(self.rate_numerator.get()) / denominator
+    }
+
+    /// Check the deadline and minimum output, then pay `path[1]` to `to`
+    fn swap(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: &[Address],
+        to: Address,
+        deadline: U256,
+    ) -> Result<Vec<U256>, Error> {
+        if U256::from(self.vm().block_timestamp()) > deadline {
+            return Err(Error::Expired(Expired { deadline }));
+        }
+
+        let amount_out = self.quote(amount_in);
+        if amount_out < amount_out_min {
+            return Err(Error::InsufficientOutputAmount(InsufficientOutputAmount {
+                amountOut: amount_out,
+                amountOutMin: amount_out_min,
+            }));
+        }
+
+        let call = IERC20::transferCall { to, amount: amount_out };
+        self.call_token(path[1], &call.abi_encode())?;
+
+        log(self.vm(), Swap {
+            sender: self.vm().msg_sender(),
+            tokenIn: path[0],
+            tokenOut: path[1],
+            amountIn: amount_in,
+            amountOut: amount_out,
+            to,
+        });
+
+        Ok(vec![amount_in, amount_out])
+    }
+
+    /// Call `token`, accepting `true` or no return data
+    fn call_token(&mut self, token: Address, calldata: &[u8]) -> Result<(), Error> {
+        let config = Call::new();
+        match self.vm().call(&config, token, calldata) {
+            Ok(result) if result.is_empty() => Ok(()),
+            Ok(result) if bool::abi_decode(&result, true).unwrap_or(false) => Ok(()),
+            _ => Err(Error::TransferFailed(TransferFailed { token })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::Error;
+    use stylus_sdk::testing::*;
+
+    #[test]
+    fn test_mock_router() {
+        let vm = TestVM::default();
+        let mut contract = MockRouter::from(&vm);
+        let weth = Address::from([7u8; 20]);
+        let token_a = Address::from([4u8; 20]);
+        let token_b = Address::from([5u8; 20]);
+        let alice = Address::from([2u8; 20]);
+        contract.constructor(weth);
+        assert_eq!(contract.weth(), weth);
+
+        // Quotes follow the rate
+        assert!(contract.set_rate(U256::from(3), U256::ZERO).is_err());
+        assert!(contract.set_rate(U256::from(3), U256::from(2)).is_ok());
+        let quote = contract.get_amounts_out(U256::from(100), vec![token_a, token_b]);
+        assert_eq!(quote.ok(), Some(vec![U256::from(100), U256::from(150)]));
+        assert!(contract.get_amounts_out(U256::from(100), vec![token_a]).is_err());
+
+        // ETH swaps pay out from the router balance
+        let payout = IERC20::transferCall {
+            to: alice,
+            amount: U256::from(150),
+        };
+        vm.mock_call(token_b, payout.abi_encode(), Ok(true.abi_encode()));
+        vm.set_value(U256::from(100));
+        let deadline = U256::from(vm.block_timestamp());
+        let result = contract.swap_exact_eth_for_tokens(
+            U256::from(150),
+            vec![weth, token_b],
+            alice,
+            deadline,
+        );
+        assert_eq!(result.ok(), Some(vec![U256::from(100), U256::from(150)]));
+
+        // Paths, minimum output and deadlines are checked
+        let result =
+            contract.swap_exact_eth_for_tokens(U256::ZERO, vec![token_a, token_b], alice, deadline);
+        assert!(matches!(result, Err(Error::InvalidPath(_))));
+        let path = vec![weth, token_b];
+        let result = contract.swap_exact_eth_for_tokens(U256::from(151), path, alice, deadline);
+        assert!(matches!(result, Err(Error::InsufficientOutputAmount(_))));
+        vm.set_block_timestamp(vm.block_timestamp() + 1);
+        let result =
+            contract.swap_exact_eth_for_tokens(U256::ZERO, vec![weth, token_b], alice, deadline);
+        assert!(matches!(result, Err(Error::Expired(_))));
+
+        // Token swaps fail when the input cannot be pulled
+        let pull = IERC20::transferFromCall {
+            from: vm.msg_sender(),
+            to: vm.contract_address(),
+            amount: U256::from(100),
+        };
+        vm.mock_call(token_a, pull.abi_encode(), Ok(false.abi_encode()));
+        let result = contract.swap_exact_tokens_for_tokens(
+            U256::from(100),
+            U256::ZERO,
+            vec![token_a, token_b],
+            alice,
+            U256::MAX,
+        );
+        assert!(matches!(result, Err(Error::TransferFailed(_))));
+    }
+}
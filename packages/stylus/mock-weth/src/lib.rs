@@ -0,0 +1,247 @@
+//!
+//! Mock WETH Contract in Stylus Rust
+//!
+//! A minimal WETH9-style wrapped ether used to exercise the WETH fallback of `multi-sender`
+//! on a local nitro-devnode.
+//!
+//! Features:
+//! - Wrap ETH with `deposit` and unwrap with `withdraw`
+//! - ERC20 `transfer`, `transferFrom` and `approve`
+//!
+//! Note: this code is for local testing only and has not been audited.
+//!
+
+// Allow `cargo stylus export-abi` to generate a main function.
+#![cfg_attr(not(any(test, feature = "export-abi")), no_main)]
+#![cfg_attr(not(any(test, feature = "export-abi")), no_std)]
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Import items from the SDK. The prelude contains common traits and macros.
+use stylus_sdk::{
+    alloy_primitives::{Address, U256},
+    alloy_sol_types::sol,
+    prelude::*,
+    stylus_core::log,
+};
+
+// Define WETH9 events
+sol! {
+    event Deposit(address indexed dst, uint256 wad);
+    event Withdrawal(address indexed src, uint256 wad);
+    event Transfer(address indexed src, address indexed dst, uint256 wad);
+    event Approval(address indexed src, address indexed guy, uint256 wad);
+}
+
+// Define errors
+sol! {
+    error InsufficientBalance(address account, uint256 balance, uint256 needed);
+    error InsufficientAllowance(address spender, uint256 allowance, uint256 needed);
+    error EthTransferFailed(address recipient);
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    InsufficientBalance(InsufficientBalance),
+    InsufficientAllowance(InsufficientAllowance),
+    EthTransferFailed(EthTransferFailed),
+}
+
+// Define persistent storage using the Solidity ABI.
+// `MockWeth` will be the entrypoint.
+sol_storage! {
+    #[entrypoint]
+    pub struct MockWeth {
+        mapping(address => uint256) balances;
+        mapping(address => mapping(address => uint256)) allowances;
+        uint256 total_supply;
+    }
+}
+
+/// Declare that `MockWeth` is a contract with the following external methods.
+#[public]
+impl MockWeth {
+    /// Get the token name
+    pub fn name(&self) -> String {
+        "Wrapped Ether".to_string()
+    }
+
+    /// Get the token symbol
+    pub fn symbol(&self) -> String {
+        "WETH".to_string()
+    }
+
+    /// Get the token decimals
+    pub fn decimals(&self) -> u8 {
+        18
+    }
+
+    /// Get the total amount of wrapped ETH
+    pub fn total_supply(&self) -> U256 {
+        self.total_supply.get()
+    }
+
+    /// Get the balance of an account
+    pub fn balance_of(&self, account: Address) -> U256 {
+        self.balances.get(account)
+    }
+
+    /// Get the amount `spender` may transfer on behalf of `owner`
+    pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        self.allowances.getter(owner).get(spender)
+    }
+
+    /// Wrap the sent ETH
+    #[payable]
+    pub fn deposit(&mut self) {
+        let sender = self.vm().msg_sender();
+        let amount = self.vm().msg_value();
+
+        let balance = self.balances.get(sender);
+        self.balances.insert(sender, balance + amount);
+        let total_supply = self.total_supply.get();
+        self.total_supply.set(total_supply + amount);
+
+        log(self.vm(), Deposit {
+            dst: sender,
+            wad: amount,
+        });
+    }
+
+    /// Unwrap `amount` and send the ETH back to the caller
+    pub fn withdraw(&mut self, amount: U256) -> Result<(), Error> {
+        let sender = self.vm().msg_sender();
+        self.debit(sender, amount)?;
+        let total_supply = self.total_supply.get();
+        self.total_supply.set(total_supply - amount);
+
+        if self.vm().transfer_eth(sender, amount).is_err() {
+            return Err(Error::EthTransferFailed(EthTransferFailed { recipient: sender }));
+        }
+
+        log(self.vm(), Withdrawal {
+            src: sender,
+            wad: amount,
+        });
+
+        Ok(())
+    }
+
+    /// Allow `spender` to transfer up to `amount` of the caller's balance
+    pub fn approve(&mut self, spender: Address, amount: U256) -> bool {
+        let owner = self.vm().msg_sender();
+        self.allowances.setter(owner).insert(spender, amount);
+
+        log(self.vm(), Approval {
+            src: owner,
+            guy: spender,
+            wad: amount,
+        });
+
+        true
+    }
+
+    /// Transfer `amount` from the caller to `to`
+    pub fn transfer(&mut self, to: Address, amount: U256) -> Result<bool, Error> {
+        let from = self.vm().msg_sender();
+        self.move_balance(from, to, amount)?;
+        Ok(true)
+    }
+
+    /// Transfer `amount` from `from` to `to` using the caller's allowance
+    pub fn transfer_from(&mut self, from: Address, to: Address, amount: U256) -> Result<bool, Error> {
+        let spender = self.vm().msg_sender();
+        if spender != from {
+            let mut allowances = self.allowances.setter(from);
+            let allowance = allowances.get(spender);
+            if allowance < amount {
+                return Err(Error::InsufficientAllowance(InsufficientAllowance {
+                    spender,
+                    allowance,
+                    needed: amount,
+                }));
+            }
+            if allowance != U256::MAX {
+                allowances.insert(spender, allowance - amount);
+            }
+        }
+
+        self.move_balance(from, to, amount)?;
+        Ok(true)
+    }
+}
+
+impl MockWeth {
+    /// Decrease the balance of `account`, failing if it does not cover `amount`
+    fn debit(&mut self, account: Address, amount: U256) -> Result<(), Error> {
+        let balance = self.balances.get(account);
+        if balance < amount {
+            return Err(Error::InsufficientBalance(InsufficientBalance {
+                account,
+                balance,
+                needed: amount,
+            }));
+        }
+        self.balances.insert(account, balance - amount);
+        Ok(())
+    }
+
+    /// Move `amount` between two balances and emit `Transfer`
+    fn move_balance(&mut self, from: Address, to: Address, amount: U256) -> Result<(), Error> {
+        self.debit(from, amount)?;
+        let balance = self.balances.get(to);
+        self.balances.insert(to, balance + amount);
+
+        log(self.vm(), Transfer {
+            src: from,
+            dst: to,
+            wad: amount,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stylus_sdk::testing::*;
+
+    #[test]
+    fn test_mock_weth() {
+        let vm = TestVM::default();
+        let mut contract = MockWeth::from(&vm);
+        let sender = vm.msg_sender();
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+
+        // Wrap ETH
+        vm.set_value(U256::from(100));
+        contract.deposit();
+        vm.set_value(U256::ZERO);
+        assert_eq!(contract.balance_of(sender), U256::from(100));
+        assert_eq!(contract.total_supply(), U256::from(100));
+
+        // Transfer wrapped ETH
+        assert!(contract.transfer(alice, U256::from(40)).is_ok());
+        assert!(contract.transfer(alice, U256::from(61)).is_err());
+        assert_eq!(contract.balance_of(alice), U256::from(40));
+
+        // Spend through an allowance
+        assert!(contract.approve(bob, U256::from(25)));
+        vm.set_sender(bob);
+        assert!(contract.transfer_from(sender, bob, U256::from(30)).is_err());
+        assert!(contract.transfer_from(sender, bob, U256::from(25)).is_ok());
+        assert_eq!(contract.allowance(sender, bob), U256::ZERO);
+        assert_eq!(contract.balance_of(sender), U256::from(35));
+        assert_eq!(contract.balance_of(bob), U256::from(25));
+
+        // Unwrapping more than the balance fails
+        assert!(contract.withdraw(U256::from(26)).is_err());
+    }
+}
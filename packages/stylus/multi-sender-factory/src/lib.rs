@@ -0,0 +1,290 @@
+//!
+//! MultiSender Factory in Stylus Rust
+//!
+//! Deploys a separate `multi-sender` instance per team so each team has its own owner,
+//! statistics and reserved funds.
+//!
+//! Instances are ERC-1167 minimal proxies (plain EVM bytecode) that delegate to one shared,
+//! already activated `multi-sender` implementation, so no per-instance Stylus activation is
+//! needed. They are created with CREATE2 and initialized in the same transaction.
+//!
+//! Features:
+//! - Deterministic instance addresses from `(creator, salt)`
+//! - Per-instance `initial_owner`
+//! - Registry of instances by owner
+//!
+//! Note: clones follow the shared implementation and cannot be upgraded individually. They hold
+//! no ERC-1967 implementation slot, so `upgrade_to_and_call` reverts on them with
+//! `UUPSUnauthorizedCallContext` instead of emitting `Upgraded` without effect.
+//! The registry records the initial owner and is not updated on ownership transfers.
+//!
+//! Note: this code is a template-only and has not been audited.
+//!
+
+// Allow `cargo stylus export-abi` to generate a main function.
+#![cfg_attr(not(any(test, feature = "export-abi")), no_main)]
+#![cfg_attr(not(any(test, feature = "export-abi")), no_std)]
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+/// Import items from the SDK. The prelude contains common traits and macros.
+use stylus_sdk::{
+    alloy_primitives::{keccak256, Address, B256, U256},
+    alloy_sol_types::{sol, SolCall, SolValue},
+    prelude::calls::context::Call,
+    prelude::*,
+    stylus_core::log,
+};
+
+// Define factory events
+sol! {
+    event InstanceCreated(address indexed instance, address indexed owner, address indexed creator, bytes32 salt);
+}
+
+// Define errors
+sol! {
+    error InvalidImplementation(address implementation);
+    error InvalidOwner(address owner);
+    error DeploymentFailed(bytes32 salt);
+    error InitializationFailed(address instance);
+}
+
+// Define the MultiSender initializer
+sol! {
+    interface IMultiSender {
+        function initialize(address initialOwner) external;
+    }
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    InvalidImplementation(InvalidImplementation),
+    InvalidOwner(InvalidOwner),
+    DeploymentFailed(DeploymentFailed),
+    InitializationFailed(InitializationFailed),
+}
+
+/// ERC-1167 creation code placed before the implementation address
+const CLONE_PREFIX: [u8; 20] = [
+    0x3d, 0x60, 0x2d, 0x80, 0x60, 0x0a, 0x3d, 0x39, 0x81, 0xf3, 0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d,
+    0x3d, 0x36, 0x3d, 0x73,
+];
+
+/// ERC-1167 creation code placed after the implementation address
+const CLONE_SUFFIX: [u8; 15] = [
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+];
+
+// Define persistent storage using the Solidity ABI.
+// `MultiSenderFactory` will be the entrypoint.
+sol_storage! {
+    #[entrypoint]
+    pub struct MultiSenderFactory {
+        address implementation;
+        address[] instances;
+        mapping(address => address[]) instances_by_owner;
+        mapping(address => bool) is_instance;
+    }
+}
+
+/// Declare that `MultiSenderFactory` is a contract with the following external methods.
+#[public]
+impl MultiSenderFactory {
+    /// Set the shared `multi-sender` implementation
+    #[constructor]
+    pub fn constructor(&mut self, implementation: Address) -> Result<(), Error> {
+        if implementation == Address::ZERO || self.vm().code_size(implementation) == 0 {
+            return Err(Error::InvalidImplementation(InvalidImplementation { implementation }));
+        }
+
+        self.implementation.set(implementation);
+        Ok(())
+    }
+
+    /// Get the shared `multi-sender` implementation
+    pub fn implementation(&self) -> Address {
+        self.implementation.get()
+    }
+
+    /// Deploy and initialize a new instance owned by `initial_owner`
+    pub fn create_instance(&mut self, initial_owner: Address, salt: B256) -> Result<Address, Error> {
+        if initial_owner == Address::ZERO {
+            return Err(Error::InvalidOwner(InvalidOwner { owner: initial_owner }));
+        }
+
+        // Scope the salt to the caller so nobody can take another team's address
+        let creator = self.vm().msg_sender();
+        let code = self.clone_code();
+        let scoped_salt = Self::scoped_salt(creator, salt);
+        let instance = unsafe { self.vm().deploy(&code, U256::ZERO, Some(scoped_salt)) }
+            .map_err(|_| Error::DeploymentFailed(DeploymentFailed { salt }))?;
+
+        // Initialize in the same transaction so the owner cannot be front-run
+        let call = IMultiSender::initializeCall { initialOwner: initial_owner };
+        let config = Call::new();
+        if self.vm().call(&config, instance, &call.abi_encode()).is_err() {
+            return Err(Error::InitializationFailed(InitializationFailed { instance }));
+        }
+
+        // Register the instance
+        self.instances.push(instance);
+        self.instances_by_owner.setter(initial_owner).push(instance);
+        self.is_instance.insert(instance, true);
+
+        log(self.vm(), InstanceCreated {
+            instance,
+            owner: initial_owner,
+            creator,
+            salt,
+        });
+
+        Ok(instance)
+    }
+
+    /// Predict the address `create_instance` deploys to for `creator` and `salt`
+    pub fn predict_address(&self, creator: Address, salt: B256) -> Address {
+        self.vm()
+            .contract_address()
+            .create2_from_code(Self::scoped_salt(creator, salt), self.clone_code())
+    }
+
+    /// Get the total number of instances
+    pub fn instance_count(&self) -> U256 {
+        U256::from(self.instances.len())
+    }
+
+    /// Get the instance at `index`
+    pub fn instance_at(&self, index: U256) -> Address {
+        self.instances.get(index).unwrap_or(Address::ZERO)
+    }
+
+    /// Get all instances created for `owner`
+    pub fn instances_of(&self, owner: Address) -> Vec<Address> {
+        let instances = self.instances_by_owner.getter(owner);
+        (0..instances.len())
+            .filter_map(|i| instances.get(i))
+            .collect()
+    }
+
+    /// Check whether `account` was deployed by this factory
+    pub fn is_instance(&self, account: Address) -> bool {
+        self.is_instance.get(account)
+    }
+}
+
+impl MultiSenderFactory {
+    /// ERC-1167 creation code pointing at the shared implementation
+    fn clone_code(&self) -> Vec<u8> {
+        let mut code = Vec::with_capacity(CLONE_PREFIX.len() + 20 + CLONE_SUFFIX.len());
+        code.extend_from_slice(&CLONE_PREFIX);
+        code.extend_from_slice(self.implementation.get().as_slice());
+        code.extend_from_slice(&CLONE_SUFFIX);
+        code
+    }
+
+    /// CREATE2 salt bound to the caller
+    fn scoped_salt(creator: Address, salt: B256) -> B256 {
+        keccak256((creator, salt).abi_encode())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::Error;
+    use stylus_sdk::testing::*;
+
+    #[test]
+    fn test_factory() {
+        let vm = TestVM::default();
+        let mut contract = MultiSenderFactory::from(&vm);
+        let implementation = Address::from([7u8; 20]);
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+        let salt = B256::from([1u8; 32]);
+
+        // An implementation without code is rejected
+        assert!(contract.constructor(implementation).is_err());
+        vm.set_code(implementation, vec![0xef, 0xf0, 0x00, 0x00]);
+        assert!(contract.constructor(implementation).is_ok());
+        assert_eq!(contract.implementation(), implementation);
+
+        // Clone code embeds the implementation
+        let code = contract.clone_code();
+        assert_eq!(code.len(), 55);
+        assert_eq!(&code[20..40], implementation.as_slice());
+
+        // Addresses are deterministic and scoped to the creator
+        assert_eq!(contract.predict_address(alice, salt), contract.predict_address(alice, salt));
+        assert_ne!(contract.predict_address(alice, salt), contract.predict_address(bob, salt));
+        assert_ne!(
+            contract.predict_address(alice, salt),
+            contract.predict_address(alice, B256::ZERO)
+        );
+
+        // Instances need an owner
+        assert!(contract.create_instance(Address::ZERO, salt).is_err());
+
+        // The registry starts empty
+        assert_eq!(contract.instance_count(), U256::ZERO);
+        assert_eq!(contract.instance_at(U256::ZERO), Address::ZERO);
+        assert!(contract.instances_of(alice).is_empty());
+        assert!(!contract.is_instance(alice));
+    }
+
+    #[test]
+    fn test_create_instance() {
+        use stylus_sdk::alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let mut contract = MultiSenderFactory::from(&vm);
+        let implementation = Address::from([7u8; 20]);
+        let instance = Address::from([8u8; 20]);
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+        let salt = B256::from([1u8; 32]);
+        vm.set_code(implementation, vec![0xef, 0xf0, 0x00, 0x00]);
+        assert!(contract.constructor(implementation).is_ok());
+
+        // Bob deploys a clone for Alice, which is initialized with her as owner
+        vm.set_sender(bob);
+        let scoped_salt = MultiSenderFactory::scoped_salt(bob, salt);
+        vm.mock_deploy(contract.clone_code(), Some(scoped_salt), Ok(instance));
+        let initialize = IMultiSender::initializeCall { initialOwner: alice };
+        vm.mock_call(instance, initialize.abi_encode(), Ok(Vec::new()));
+        assert_eq!(contract.create_instance(alice, salt).ok(), Some(instance));
+
+        // The instance is registered
+        assert_eq!(contract.instance_count(), U256::from(1));
+        assert_eq!(contract.instance_at(U256::ZERO), instance);
+        assert_eq!(contract.instances_of(alice), vec![instance]);
+        assert!(contract.instances_of(bob).is_empty());
+        assert!(contract.is_instance(instance));
+
+        // The creation is logged
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = InstanceCreated::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!(
+            (event.instance, event.owner, event.creator, event.salt),
+            (instance, alice, bob, salt)
+        );
+
+        // A failed initialization leaves the registry untouched
+        vm.mock_call(instance, initialize.abi_encode(), Err(Vec::new()));
+        assert!(matches!(
+            contract.create_instance(alice, salt),
+            Err(Error::InitializationFailed(_))
+        ));
+        assert_eq!(contract.instance_count(), U256::from(1));
+
+        // A failed deployment is reported
+        vm.mock_deploy(contract.clone_code(), Some(scoped_salt), Err(Vec::new()));
+        assert!(matches!(
+            contract.create_instance(alice, salt),
+            Err(Error::DeploymentFailed(_))
+        ));
+    }
+}
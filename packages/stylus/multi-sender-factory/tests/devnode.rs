@@ -0,0 +1,101 @@
+//! Integration tests against a local nitro-devnode (see `tests-common/devnode.rs`)
+
+#[path = "../../tests-common/devnode.rs"]
+mod devnode;
+
+use ethers::contract::parse_log;
+use ethers::prelude::*;
+use eyre::Result;
+
+abigen!(
+    MultiSenderFactory,
+    r#"[
+        function implementation() external view returns (address)
+        function createInstance(address initialOwner, bytes32 salt) external returns (address)
+        function predictAddress(address creator, bytes32 salt) external view returns (address)
+        function instanceCount() external view returns (uint256)
+        function instanceAt(uint256 index) external view returns (address)
+        function instancesOf(address owner) external view returns (address[])
+        function isInstance(address account) external view returns (bool)
+        event InstanceCreated(address indexed instance, address indexed owner, address indexed creator, bytes32 salt)
+    ]"#
+);
+
+abigen!(
+    MultiSender,
+    r#"[
+        function owner() external view returns (address)
+        function initialize(address initialOwner) external
+        function batchSendEth(address[] recipients, uint256[] amounts) external payable returns (uint256, uint256, uint8[], uint256)
+        function totalTransactions() external view returns (uint256)
+        function upgradeToAndCall(address newImplementation, bytes data) external payable
+        error UUPSUnauthorizedCallContext()
+    ]"#
+);
+
+#[tokio::test]
+async fn test_factory_on_devnode() -> Result<()> {
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let creator = client.address();
+    let implementation = devnode::deploy_crate("multi-sender", &[format!("{creator:?}")])?;
+    let address = devnode::deploy(&[format!("{implementation:?}")])?;
+    let factory = MultiSenderFactory::new(address, client.clone());
+    assert_eq!(factory.implementation().call().await?, implementation);
+
+    // The instance lands on the predicted address, initialized for its owner
+    let team = devnode::funded_account(&client, U256::exp10(17)).await?;
+    let salt = [9u8; 32];
+    let predicted = factory.predict_address(creator, salt).call().await?;
+    let receipt = factory
+        .create_instance(team.address(), salt)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+    let created: Vec<InstanceCreatedFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(created, vec![InstanceCreatedFilter {
+        instance: predicted,
+        owner: team.address(),
+        creator,
+        salt,
+    }]);
+    assert_eq!(factory.instance_count().call().await?, U256::one());
+    assert_eq!(factory.instance_at(U256::zero()).call().await?, predicted);
+    assert_eq!(factory.instances_of(team.address()).call().await?, vec![predicted]);
+    assert!(factory.is_instance(predicted).call().await?);
+
+    // The same creator and salt cannot deploy twice
+    assert!(factory.create_instance(creator, salt).send().await.is_err());
+
+    // The instance works as a MultiSender with its own owner and state
+    let instance = MultiSender::new(predicted, team.clone());
+    assert_eq!(instance.owner().call().await?, team.address());
+    assert!(instance.initialize(creator).send().await.is_err());
+    let recipient = Address::random();
+    instance
+        .batch_send_eth(vec![recipient], vec![U256::from(1_000)])
+        .value(1_000)
+        .send()
+        .await?
+        .await?;
+    assert_eq!(client.get_balance(recipient, None).await?, U256::from(1_000));
+    assert_eq!(instance.total_transactions().call().await?, U256::one());
+    let shared = MultiSender::new(implementation, client.clone());
+    assert_eq!(shared.total_transactions().call().await?, U256::zero());
+
+    // Clones have no ERC-1967 slot, so even their owner cannot upgrade them
+    let err = instance
+        .upgrade_to_and_call(implementation, Bytes::new())
+        .send()
+        .await
+        .expect_err("clone upgrade");
+    assert!(err.decode_revert::<UUPSUnauthorizedCallContext>().is_some());
+
+    Ok(())
+}
@@ -6,6 +6,28 @@
 //!
 //! Features:
 //! - Batch ETH transfers
+//! - Batch ERC20 token transfers
+//! - Stored batches processed in resumable chunks
+//! - Internal deposit ledger for ETH and ERC20 with ledger-to-ledger batches
+//! - Recurring payroll schedules executable by keepers
+//! - Saved recipient groups with default amounts
+//! - WETH fallback for recipients that reject ETH
+//! - Operator-gated multicall to owner-allowlisted targets with value forwarding
+//! - UUPS upgrades behind the `erc1967-proxy` crate
+//! - Owner recovery of ERC20, ERC721 and ERC1155 assets sent by mistake
+//! - Receive and fallback handlers with an option to reject unsolicited ETH
+//! - Compact event mode with failure codes and a per-batch success bitmap
+//! - Per-recipient batch results (batch id, statuses, refund) for composing contracts
+//! - Batch L2-to-L1 ETH withdrawals through the ArbSys precompile
+//! - Gas and L1 fee estimates priced by the ArbGasInfo precompile
+//! - Idempotency keys so clients can retry batch sends without paying twice
+//! - Sender-defined per-asset limits on batches, schedules, L1 withdrawals and swaps
+//! - Gas-aware partial batches that stop before running out of gas and report where to resume
+//! - Invoices issued by recipients and settled in bulk by their payer
+//! - Many-to-one token collection from payers who authorized the collector, or signed for it
+//! - Swap-and-distribute through a Uniswap-V2-style router with a minimum output guard
+//! - ERC-1363 `transferAndCall` distributions from owner-accepted tokens
+//! - Commit-reveal ETH raffles on a future L2 block hash, split evenly if never revealed
 //! - Gas optimization through single transaction
 //! - Safety checks and event emission
 //! - Basic access control
@@ -16,6 +38,7 @@
 // Allow `cargo stylus export-abi` to generate a main function.
 #![cfg_attr(not(any(test, feature = "export-abi")), no_main)]
 #![cfg_attr(not(any(test, feature = "export-abi")), no_std)]
+#![recursion_limit = "256"]
 
 #[macro_use]
 extern crate alloc;
@@ -25,22 +48,496 @@ use alloc::vec::Vec;
 
 /// Import items from the SDK. The prelude contains common traits and macros.
 use stylus_sdk::{
-    alloy_primitives::{Address, U256},
-    alloy_sol_types::sol,
+    abi::Bytes,
+    alloy_primitives::{address, keccak256, Address, FixedBytes, B256, U256, U8},
+    alloy_sol_types::{sol, SolCall, SolValue},
+    prelude::calls::context::Call,
     prelude::*,
     stylus_core::log,
+    ArbResult,
 };
 
+#[path = "../../uups-common/uups.rs"]
+mod uups;
+
+use uups::{ERC1967InvalidImplementation, UUPSUnauthorizedCallContext, UpgradeCallFailed};
+
 // Define events for batch transfers
 sol! {
     event BatchEthTransfer(address indexed sender, uint256 totalAmount, uint256 recipientCount);
     event BatchTokenTransfer(address indexed sender, address indexed token, uint256 totalAmount, uint256 recipientCount);
     event TransferSuccess(address indexed recipient, uint256 amount);
     event TransferFailed(address indexed recipient, uint256 amount, string reason);
+    event TransferFailedCode(address indexed recipient, uint256 amount, uint8 code);
+    event BatchTransferSummary(address indexed sender, address indexed token, uint256 totalAmount, uint256 recipientCount, uint256 successCount, bytes successBitmap, bytes wrappedBitmap);
+    event StoredBatchAppended(uint256 indexed batchId, address indexed sender, uint256 itemCount, uint256 totalAmount);
+    event StoredBatchFunded(uint256 indexed batchId, address indexed sender, uint256 totalAmount);
+    event StoredBatchProcessed(uint256 indexed batchId, uint256 cursor, uint256 remaining);
+    event StoredBatchCompleted(uint256 indexed batchId, uint256 successCount, uint256 refundedAmount);
+    event StoredBatchCancelled(uint256 indexed batchId, uint256 refundedAmount);
+    event LedgerDeposit(address indexed token, address indexed account, uint256 amount);
+    event LedgerWithdrawal(address indexed token, address indexed account, uint256 amount);
+    event LedgerCredit(address indexed token, address indexed from, address indexed to, uint256 amount);
+    event ScheduleCreated(uint256 indexed scheduleId, address indexed payer, address indexed asset, string name);
+    event ScheduleExecuted(uint256 indexed scheduleId, uint256 indexed period, uint256 totalAmount, uint256 recipientCount);
+    event SchedulePeriodsSkipped(uint256 indexed scheduleId, uint256 fromPeriod, uint256 toPeriod);
+    event SchedulePaused(uint256 indexed scheduleId);
+    event ScheduleResumed(uint256 indexed scheduleId);
+    event ScheduleCancelled(uint256 indexed scheduleId);
+    event GroupCreated(uint256 indexed groupId, address indexed owner, string name);
+    event GroupMemberSet(uint256 indexed groupId, address indexed member, uint256 defaultAmount);
+    event GroupMemberRemoved(uint256 indexed groupId, address indexed member);
+    event WrappedTransferSuccess(address indexed recipient, uint256 amount);
+    event WethFallbackUpdated(address indexed weth);
+    event MulticallOperatorUpdated(address indexed operator, bool allowed);
+    event MulticallTargetUpdated(address indexed target, bool allowed);
+    event MulticallCallResult(uint256 indexed index, address indexed target, uint256 value, bool success);
+    event MulticallExecuted(address indexed caller, uint256 callCount, uint256 successCount);
+    event ERC20Recovered(address indexed token, address indexed to, uint256 amount);
+    event ERC721Recovered(address indexed token, address indexed to, uint256 tokenId);
+    event ERC1155Recovered(address indexed token, address indexed to, uint256 id, uint256 amount);
+    event Deposit(address indexed sender, uint256 amount);
+    event UnsolicitedEthPolicyUpdated(bool rejected);
+    event SenderLimitsUpdated(address indexed sender, address indexed asset, uint256 maxAmount, uint256 maxTotal, uint256 maxBatchesPerDay);
+    event SenderLimitsQueued(address indexed sender, address indexed asset, uint256 maxAmount, uint256 maxTotal, uint256 maxBatchesPerDay, uint256 effectiveAt);
+    event BatchStoppedEarly(address indexed sender, address indexed token, uint256 nextIndex, uint256 recipientCount);
+    event InvoiceCreated(uint256 indexed invoiceId, address indexed issuer, address indexed payer, address asset, uint256 amount, uint256 dueDate, string reference);
+    event InvoicePaid(uint256 indexed invoiceId, address indexed payer, address indexed issuer, address asset, uint256 amount);
+    event InvoiceCancelled(uint256 indexed invoiceId);
+    event CollectorApproval(address indexed payer, address indexed collector, address indexed token, uint256 amount);
+    event CollectionSuccess(address indexed payer, uint256 amount);
+    event CollectionFailed(address indexed payer, uint256 amount, string reason);
+    event CollectionPermitFailed(address indexed payer, address indexed token);
+    event BatchTokenCollection(address indexed collector, address indexed token, address indexed to, uint256 totalAmount, uint256 payerCount);
+    event SwapRouterUpdated(address indexed router);
+    event SwapAndDistribute(address indexed sender, address indexed tokenIn, address indexed tokenOut, uint256 amountIn, uint256 amountOut, uint256 recipientCount);
+    event Erc1363TokenUpdated(address indexed token, bool accepted);
+    event RaffleCreated(uint256 indexed raffleId, address indexed sender, uint256 pool, uint256 participantCount, uint256 winnerCount, uint256 drawBlock);
+    event RaffleDrawn(uint256 indexed raffleId, bytes32 seed, bytes32 blockHash, address[] winners);
+    event RaffleCancelled(uint256 indexed raffleId, uint256 refundedAmount);
+    event RaffleExpired(uint256 indexed raffleId, uint256 pool, uint256 participantCount);
+    event L1WithdrawalInitiated(address indexed l1Recipient, uint256 amount, uint256 indexed messageId);
+    event BatchL1Withdrawal(address indexed sender, uint256 totalAmount, uint256 recipientCount);
+}
+
+// ERC20 interface for token transfers
+sol! {
+    interface IERC20 {
+        function transfer(address to, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+        function balanceOf(address account) external view returns (uint256);
+        function approve(address spender, uint256 amount) external returns (bool);
+    }
+
+    interface IUniswapV2Router {
+        function WETH() external view returns (address);
+        function swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline) external payable returns (uint256[] amounts);
+        function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external returns (uint256[] amounts);
+    }
+
+    interface IERC20Permit {
+        function permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external;
+    }
+
+    interface IERC1363Receiver {
+        function onTransferReceived(address operator, address from, uint256 value, bytes data) external returns (bytes4);
+    }
+
+    interface IERC721 {
+        function safeTransferFrom(address from, address to, uint256 tokenId) external;
+    }
+
+    interface IERC1155 {
+        function safeTransferFrom(address from, address to, uint256 id, uint256 value, bytes data) external;
+    }
+
+    interface IArbSys {
+        function withdrawEth(address destination) external payable returns (uint256);
+        function arbBlockNumber() external view returns (uint256);
+        function arbBlockHash(uint256 arbBlockNum) external view returns (bytes32);
+    }
+
+    interface IArbGasInfo {
+        function getPricesInWei() external view returns (uint256 perL2Tx, uint256 perL1CalldataByte, uint256 perStorageAllocation, uint256 perArbGasBase, uint256 perArbGasCongestion, uint256 perArbGasTotal);
+    }
+
+    interface IWETH {
+        function deposit() external payable;
+    }
+}
+
+// Define errors for functions that revert instead of returning early
+sol! {
+    error ArrayLengthMismatch();
+    error InvalidRecipient(address recipient);
+    error InvalidAmount();
+    error InsufficientValue(uint256 required, uint256 provided);
+    error UnauthorizedBatchSender(uint256 batchId, address caller);
+    error UnknownBatch(uint256 batchId);
+    error BatchAlreadyFunded(uint256 batchId);
+    error BatchNotFunded(uint256 batchId);
+    error BatchClosed(uint256 batchId);
+    error InsufficientLedgerBalance(address token, uint256 available, uint256 required);
+    error TokenTransferFailed(address token);
+    error EthTransferFailed(address recipient);
+    error InvalidSchedule();
+    error UnknownSchedule(uint256 scheduleId);
+    error UnauthorizedSchedulePayer(uint256 scheduleId, address caller);
+    error ScheduleNotDue(uint256 scheduleId, uint256 nextDue);
+    error ScheduleFinished(uint256 scheduleId);
+    error ScheduleIsPaused(uint256 scheduleId);
+    error ScheduleIsCancelled(uint256 scheduleId);
+    error ScheduleNotPaused(uint256 scheduleId);
+    error UnknownGroup(uint256 groupId);
+    error UnauthorizedGroupOwner(uint256 groupId, address caller);
+    error NotGroupMember(uint256 groupId, address member);
+    error UnauthorizedAccount(address account);
+    error ForbiddenCall(uint256 index, address target);
+    error MulticallFailed(uint256 index, bytes returnData);
+    error ReservedFundsTouched(address asset);
+    error AlreadyInitialized();
+    error InsufficientRecoverableBalance(address token, uint256 available, uint256 required);
+    error UnsolicitedEthRejected(address sender, uint256 amount);
+    error UnknownFunction(bytes4 selector);
+    error ArithmeticOverflow();
+    error InvalidEventMode(uint8 mode);
+    error L1WithdrawalFailed(address l1Recipient);
+    error BatchAlreadyExecuted(address sender, bytes32 batchKey);
+    error AmountAboveLimit(address asset, uint256 amount, uint256 limit);
+    error BatchTotalAboveLimit(address asset, uint256 total, uint256 limit);
+    error DailyBatchLimitReached(address asset, uint256 limit);
+    error NoPendingLimits(address asset);
+    error LimitsNotYetEffective(address asset, uint256 effectiveAt);
+    error UnknownInvoice(uint256 invoiceId);
+    error InvoiceNotOpen(uint256 invoiceId, uint8 status);
+    error UnauthorizedInvoicePayer(uint256 invoiceId, address caller);
+    error UnauthorizedInvoiceIssuer(uint256 invoiceId, address caller);
+    error SwapRouterNotSet();
+    error SwapFailed(bytes returnData);
+    error InsufficientSwapOutput(uint256 amountOut, uint256 minOut);
+    error UnsupportedToken(address token);
+    error InvalidTransferData();
+    error TransferAmountMismatch(uint256 received, uint256 total);
+    error UnknownRaffle(uint256 raffleId);
+    error UnauthorizedRaffleSender(uint256 raffleId, address caller);
+    error RaffleNotOpen(uint256 raffleId, uint8 status);
+    error TooManyWinners(uint256 winners, uint256 participants);
+    error TooManyParticipants(uint256 participants, uint256 max);
+    error InvalidRaffleSeed(uint256 raffleId);
+    error RaffleNotDrawable(uint256 raffleId, uint256 drawBlock);
+    error RaffleDrawExpired(uint256 raffleId, uint256 drawBlock);
+    error RaffleAwaitingReveal(uint256 raffleId, uint256 drawBlock);
+    error BlockHashUnavailable(uint256 blockNumber);
+    error GasPriceUnavailable();
+    error CollectorAuthorizationExpired(uint256 deadline);
+    error InvalidCollectorSignature(address payer);
+    error RaffleCommitmentUsed(bytes32 commitment);
+}
+
+/// Error types for the contract
+#[derive(SolidityError)]
+pub enum Error {
+    ArrayLengthMismatch(ArrayLengthMismatch),
+    InvalidRecipient(InvalidRecipient),
+    InvalidAmount(InvalidAmount),
+    InsufficientValue(InsufficientValue),
+    UnauthorizedBatchSender(UnauthorizedBatchSender),
+    UnknownBatch(UnknownBatch),
+    BatchAlreadyFunded(BatchAlreadyFunded),
+    BatchNotFunded(BatchNotFunded),
+    BatchClosed(BatchClosed),
+    InsufficientLedgerBalance(InsufficientLedgerBalance),
+    TokenTransferFailed(TokenTransferFailed),
+    EthTransferFailed(EthTransferFailed),
+    InvalidSchedule(InvalidSchedule),
+    UnknownSchedule(UnknownSchedule),
+    UnauthorizedSchedulePayer(UnauthorizedSchedulePayer),
+    ScheduleNotDue(ScheduleNotDue),
+    ScheduleFinished(ScheduleFinished),
+    ScheduleIsPaused(ScheduleIsPaused),
+    ScheduleIsCancelled(ScheduleIsCancelled),
+    ScheduleNotPaused(ScheduleNotPaused),
+    UnknownGroup(UnknownGroup),
+    UnauthorizedGroupOwner(UnauthorizedGroupOwner),
+    NotGroupMember(NotGroupMember),
+    UnauthorizedAccount(UnauthorizedAccount),
+    ForbiddenCall(ForbiddenCall),
+    MulticallFailed(MulticallFailed),
+    ReservedFundsTouched(ReservedFundsTouched),
+    AlreadyInitialized(AlreadyInitialized),
+    InvalidImplementation(ERC1967InvalidImplementation),
+    UpgradeCallFailed(UpgradeCallFailed),
+    UnauthorizedCallContext(UUPSUnauthorizedCallContext),
+    InsufficientRecoverableBalance(InsufficientRecoverableBalance),
+    UnsolicitedEthRejected(UnsolicitedEthRejected),
+    UnknownFunction(UnknownFunction),
+    ArithmeticOverflow(ArithmeticOverflow),
+    InvalidEventMode(InvalidEventMode),
+    L1WithdrawalFailed(L1WithdrawalFailed),
+    BatchAlreadyExecuted(BatchAlreadyExecuted),
+    AmountAboveLimit(AmountAboveLimit),
+    BatchTotalAboveLimit(BatchTotalAboveLimit),
+    DailyBatchLimitReached(DailyBatchLimitReached),
+    NoPendingLimits(NoPendingLimits),
+    LimitsNotYetEffective(LimitsNotYetEffective),
+    UnknownInvoice(UnknownInvoice),
+    InvoiceNotOpen(InvoiceNotOpen),
+    UnauthorizedInvoicePayer(UnauthorizedInvoicePayer),
+    UnauthorizedInvoiceIssuer(UnauthorizedInvoiceIssuer),
+    SwapRouterNotSet(SwapRouterNotSet),
+    SwapFailed(SwapFailed),
+    InsufficientSwapOutput(InsufficientSwapOutput),
+    UnsupportedToken(UnsupportedToken),
+    InvalidTransferData(InvalidTransferData),
+    TransferAmountMismatch(TransferAmountMismatch),
+    UnknownRaffle(UnknownRaffle),
+    UnauthorizedRaffleSender(UnauthorizedRaffleSender),
+    RaffleNotOpen(RaffleNotOpen),
+    TooManyWinners(TooManyWinners),
+    TooManyParticipants(TooManyParticipants),
+    InvalidRaffleSeed(InvalidRaffleSeed),
+    RaffleNotDrawable(RaffleNotDrawable),
+    RaffleDrawExpired(RaffleDrawExpired),
+    RaffleAwaitingReveal(RaffleAwaitingReveal),
+    BlockHashUnavailable(BlockHashUnavailable),
+    GasPriceUnavailable(GasPriceUnavailable),
+    CollectorAuthorizationExpired(CollectorAuthorizationExpired),
+    InvalidCollectorSignature(InvalidCollectorSignature),
+    RaffleCommitmentUsed(RaffleCommitmentUsed),
+}
+
+impl From<uups::UupsError> for Error {
+    fn from(value: uups::UupsError) -> Self {
+        match value {
+            uups::UupsError::InvalidImplementation(e) => Error::InvalidImplementation(e),
+            uups::UupsError::UpgradeCallFailed(e) => Error::UpgradeCallFailed(e),
+            uups::UupsError::UnauthorizedCallContext(e) => Error::UnauthorizedCallContext(e),
+        }
+    }
+}
+
+/// Add two amounts, failing instead of wrapping around
+fn checked_add(a: U256, b: U256) -> Result<U256, Error> {
+    a.checked_add(b).ok_or(Error::ArithmeticOverflow(ArithmeticOverflow {}))
+}
+
+/// Subtract two amounts, failing instead of wrapping around
+fn checked_sub(a: U256, b: U256) -> Result<U256, Error> {
+    a.checked_sub(b).ok_or(Error::ArithmeticOverflow(ArithmeticOverflow {}))
+}
+
+/// Multiply two amounts, failing instead of wrapping around
+fn checked_mul(a: U256, b: U256) -> Result<U256, Error> {
+    a.checked_mul(b).ok_or(Error::ArithmeticOverflow(ArithmeticOverflow {}))
+}
+
+/// Reason logged by `TransferFailed` for a failure code
+fn failure_reason(code: u8) -> &'static str {
+    match code {
+        FAILURE_INVALID_RECIPIENT => "Invalid recipient address",
+        FAILURE_TOKEN_RETURNED_FALSE => "Token transfer returned false",
+        FAILURE_TOKEN_BAD_RETURN => "Failed to decode transfer result",
+        FAILURE_TOKEN_CALL => "Token contract call failed",
+        FAILURE_COLLECTOR_ALLOWANCE => "Collector allowance exceeded",
+        _ => "Transfer failed",
+    }
+}
+
+/// Count the recipients that were paid
+fn success_count(statuses: &[u8]) -> u32 {
+    statuses.iter().filter(|status| **status == TRANSFER_OK).count() as u32
+}
+
+/// Whether `new` limits at least as much as `current`, where zero means unlimited
+fn is_tighter(new: U256, current: U256) -> bool {
+    current == U256::ZERO || (new != U256::ZERO && new <= current)
+}
+
+/// Sum the amounts of the recipients that were processed, paid or not
+fn processed_amount(statuses: &[u8], amounts: &[U256]) -> Result<U256, Error> {
+    amounts[..statuses.len()]
+        .iter()
+        .try_fold(U256::ZERO, |total, amount| checked_add(total, *amount))
+}
+
+/// Sum the amounts of the recipients that were not paid
+fn unpaid_amount(statuses: &[u8], amounts: &[U256]) -> Result<U256, Error> {
+    statuses
+        .iter()
+        .zip(amounts)
+        .filter(|(status, _)| **status != TRANSFER_OK)
+        .try_fold(U256::ZERO, |total, (_, amount)| checked_add(total, *amount))
 }
 
+/// Draw `count` distinct entries of `participants` from `seed` and `block_hash` with a partial
+/// Fisher-Yates shuffle. An address listed twice holds two entries.
+fn draw_winners(
+    seed: B256,
+    block_hash: B256,
+    mut participants: Vec<Address>,
+    count: usize,
+) -> Vec<Address> {
+    let length = participants.len();
+    for i in 0..count.min(length) {
+        let word = keccak256((seed, block_hash, U256::from(i)).abi_encode());
+        let offset = U256::from_be_bytes(word.0) % U256::from(length - i);
+        participants.swap(i, i + offset.to::<usize>());
+    }
+    participants.truncate(count);
+    participants
+}
+
+/// Pack the statuses into a bitmap, bit `i % 8` of byte `i / 8` is set if recipient `i` was paid
+fn success_bitmap(statuses: &[u8]) -> Vec<u8> {
+    let mut bitmap = vec![0u8; statuses.len().div_ceil(8)];
+    for (i, status) in statuses.iter().enumerate() {
+        if *status == TRANSFER_OK {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bitmap
+}
+
+/// Gas of a log with `topics` topics and `data_len` bytes of data
+fn log_gas(topics: u64, data_len: u64) -> u64 {
+    LOG_GAS + topics * LOG_TOPIC_GAS + data_len * LOG_DATA_BYTE_GAS
+}
+
+/// Intrinsic calldata gas of a call: its selector and the ABI-encoded `params`
+fn calldata_gas(params: &[u8]) -> u64 {
+    let zero_bytes = params.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zero_bytes = 4 + params.len() as u64 - zero_bytes;
+    zero_bytes * CALLDATA_ZERO_BYTE_GAS + non_zero_bytes * CALLDATA_BYTE_GAS
+}
+
+/// Split a 65-byte `r`, `s`, `v` signature, or return `None` for any other length
+fn split_signature(signature: &[u8]) -> Option<(u8, B256, B256)> {
+    if signature.len() != 65 {
+        return None;
+    }
+    let r = B256::from_slice(&signature[..32]);
+    let s = B256::from_slice(&signature[32..64]);
+    Some((signature[64], r, s))
+}
+
+/// Asset key used for native ETH in the deposit ledger
+pub const NATIVE_ETH: Address = Address::ZERO;
+
+/// Schedule funding source: the payer's deposit ledger balance
+pub const FUNDING_LEDGER: u8 = 0;
+/// Schedule funding source: ERC20 allowance granted by the payer
+pub const FUNDING_ALLOWANCE: u8 = 1;
+
+/// Event mode: `TransferSuccess`/`TransferFailed` per recipient and a batch event
+pub const EVENTS_VERBOSE: u8 = 0;
+/// Event mode: `TransferFailedCode` per failure and one `BatchTransferSummary`, whose
+/// `wrappedBitmap` marks the ETH recipients that were paid in WETH
+pub const EVENTS_COMPACT: u8 = 1;
+
+/// Invoice status: waiting for the payer
+pub const INVOICE_OPEN: u8 = 1;
+/// Invoice status: settled by `pay_invoices`
+pub const INVOICE_PAID: u8 = 2;
+/// Invoice status: withdrawn by its issuer
+pub const INVOICE_CANCELLED: u8 = 3;
+
+/// Raffle status: funded and waiting for the seed to be revealed
+pub const RAFFLE_OPEN: u8 = 1;
+/// Raffle status: winners drawn and paid by `reveal_raffle`
+pub const RAFFLE_DRAWN: u8 = 2;
+/// Raffle status: refunded to its sender by `cancel_raffle` before the draw block
+pub const RAFFLE_CANCELLED: u8 = 3;
+/// Raffle status: never revealed, pool split among the participants by `settle_expired_raffle`
+pub const RAFFLE_EXPIRED: u8 = 4;
+
+/// L2 blocks between creating a raffle and the block whose hash is mixed into the draw
+pub const RAFFLE_DRAW_DELAY: u64 = 10;
+
+/// L2 blocks after the draw block during which the seed can be revealed. `arbBlockHash` only
+/// serves the last 256 blocks.
+pub const RAFFLE_REVEAL_WINDOW: u64 = 256;
+
+/// Most participants a raffle can have, so that `settle_expired_raffle`, which credits every
+/// participant in one call, always fits in a block
+pub const MAX_RAFFLE_PARTICIPANTS: usize = 500;
+
+/// Transfer status of a recipient that was paid (directly or as WETH)
+pub const TRANSFER_OK: u8 = 0;
+/// Failure code: the recipient is the zero address
+pub const FAILURE_INVALID_RECIPIENT: u8 = 1;
+/// Failure code: the ETH transfer was rejected
+pub const FAILURE_ETH_TRANSFER: u8 = 2;
+/// Failure code: the token returned `false`
+pub const FAILURE_TOKEN_RETURNED_FALSE: u8 = 3;
+/// Failure code: the token returned data that is not a bool
+pub const FAILURE_TOKEN_BAD_RETURN: u8 = 4;
+/// Failure code: the token call reverted
+pub const FAILURE_TOKEN_CALL: u8 = 5;
+/// Failure code: the payer did not authorize the collector for the amount
+pub const FAILURE_COLLECTOR_ALLOWANCE: u8 = 6;
+
+/// Outcome of a direct batch: `(batchId, successCount, statuses, refunded)`.
+/// `statuses[i]` is `TRANSFER_OK` or the failure code of recipient `i`, and `refunded` is the
+/// ETH returned to the sender (excess value plus failed transfers). A refund the sender rejects
+/// is credited to their ledger balance instead, to be claimed with `withdraw`.
+pub type BatchResult = (U256, U256, Vec<u8>, U256);
+
+/// Outcome of a partial batch: a `BatchResult` for the processed recipients plus `nextIndex`,
+/// the first recipient that was not processed (the input length when all were).
+pub type PartialBatchResult = (U256, U256, Vec<u8>, U256, U256);
+
+/// Gas kept back by partial batches when the caller passes zero: enough for one more transfer
+/// with a WETH fallback plus the statistics, events and refund that close the batch
+pub const DEFAULT_GAS_RESERVE: u64 = 150_000;
+
+/// Seconds before a raised sender limit can be applied
+pub const LIMIT_RAISE_DELAY: u64 = 86_400;
+
+/// Length of the window counted by `maxBatchesPerDay`
+const LIMIT_DAY: u64 = 86_400;
+
+/// ArbSys precompile, which bridges ETH to L1 with `withdrawEth`
+pub const ARB_SYS: Address = address!("0000000000000000000000000000000000000064");
+
+/// ArbGasInfo precompile, which reports current L2 gas and L1 calldata prices
+pub const ARB_GAS_INFO: Address = address!("000000000000000000000000000000000000006c");
+
+/// ecrecover precompile, which returns the signer of an EIP-712 digest
+pub const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+
+/// EIP-712 type of `approve_collector_by_sig` authorizations
+pub const COLLECTOR_AUTHORIZATION_TYPE: &str = concat!(
+    "CollectorAuthorization(address payer,address collector,address token,",
+    "uint256 amount,uint256 nonce,uint256 deadline)"
+);
+
+/// Intrinsic gas of a transaction
+const TX_BASE_GAS: u64 = 21_000;
+/// Intrinsic gas per zero and non-zero calldata byte (EIP-2028)
+const CALLDATA_ZERO_BYTE_GAS: u64 = 4;
+const CALLDATA_BYTE_GAS: u64 = 16;
+/// Account and storage slot access costs (EIP-2929)
+const COLD_ACCOUNT_GAS: u64 = 2_600;
+const WARM_ACCOUNT_GAS: u64 = 100;
+const COLD_SLOT_GAS: u64 = 2_100;
+/// Storage write from zero and from a non-zero value
+const SLOT_SET_GAS: u64 = 20_000;
+const SLOT_RESET_GAS: u64 = 2_900;
+/// Surcharge of a call with value, and of one that creates the recipient account
+const CALL_VALUE_GAS: u64 = 9_000;
+const NEW_ACCOUNT_GAS: u64 = 25_000;
+/// Log costs: base, per topic and per data byte
+const LOG_GAS: u64 = 375;
+const LOG_TOPIC_GAS: u64 = 375;
+const LOG_DATA_BYTE_GAS: u64 = 8;
+/// Allowance for the receive logic of a recipient contract, which cannot be priced ahead
+const RECEIVE_HOOK_GAS: u64 = 10_000;
+
 // Define persistent storage using the Solidity ABI.
 // `MultiSender` will be the entrypoint.
+//
+// The contract can run behind an ERC-1967 proxy, so the layout is append-only:
+// new fields go at the end of a struct and existing fields are never removed or reordered.
 sol_storage! {
     #[entrypoint]
     pub struct MultiSender {
@@ -48,6 +545,107 @@ sol_storage! {
         uint256 total_transactions;
         uint256 total_recipients;
         mapping(address => uint256) user_transaction_count;
+        mapping(uint256 => StoredBatch) stored_batches;
+        uint256 reserved_eth;
+        mapping(address => uint256) reserved_tokens;
+        mapping(address => mapping(address => uint256)) ledger_balances;
+        mapping(uint256 => Schedule) schedules;
+        uint256 schedule_count;
+        mapping(uint256 => RecipientGroup) groups;
+        uint256 group_count;
+        address weth;
+        mapping(address => bool) multicall_operators;
+        bool initialized;
+        bool reject_unsolicited_eth;
+        mapping(address => mapping(bytes32 => bool)) executed_batch_keys;
+        mapping(address => mapping(address => SenderLimits)) sender_limits;
+        mapping(uint256 => Invoice) invoices;
+        uint256 invoice_count;
+        mapping(address => mapping(address => mapping(address => uint256))) collector_allowances;
+        address swap_router;
+        mapping(address => bool) erc1363_tokens;
+        mapping(uint256 => Raffle) raffles;
+        uint256 raffle_count;
+        mapping(address => bool) multicall_targets;
+        mapping(address => uint256) collector_nonces;
+        mapping(bytes32 => bool) used_raffle_commitments;
+    }
+
+    /// An ETH prize pool drawn among `participants` once the sender reveals the committed seed.
+    pub struct Raffle {
+        address sender;
+        address[] participants;
+        uint256[] prizes;
+        uint256 pool;
+        bytes32 commitment;
+        uint256 draw_block;
+        uint8 status;
+    }
+
+    /// A payment request from `issuer` to `payer`.
+    pub struct Invoice {
+        address issuer;
+        address payer;
+        address asset;
+        uint256 amount;
+        uint256 due_date;
+        string reference;
+        uint8 status;
+    }
+
+    /// Limits a sender puts on their own batches of one asset; zero means unlimited.
+    pub struct SenderLimits {
+        uint256 max_amount;
+        uint256 max_total;
+        uint256 max_batches_per_day;
+        uint256 day;
+        uint256 day_batches;
+        uint256 pending_max_amount;
+        uint256 pending_max_total;
+        uint256 pending_max_batches_per_day;
+        /// When the pending limits can be applied, zero without pending limits
+        uint256 pending_effective_at;
+    }
+
+    /// A batch uploaded over several transactions and paid out in chunks.
+    pub struct StoredBatch {
+        address sender;
+        address[] recipients;
+        uint256[] amounts;
+        uint256 total_amount;
+        uint256 paid_amount;
+        uint256 cursor;
+        uint256 success_count;
+        bool funded;
+        bool closed;
+    }
+
+    /// A recurring payment executed once per interval by any keeper.
+    pub struct Schedule {
+        address payer;
+        string name;
+        address asset;
+        address[] recipients;
+        uint256[] amounts;
+        uint256 total_amount;
+        uint256 interval;
+        uint256 start;
+        uint256 end;
+        uint256 executed_periods;
+        bool from_allowance;
+        bool catch_up;
+        bool paused;
+        bool cancelled;
+    }
+
+    /// A saved list of recipients with a default amount each.
+    pub struct RecipientGroup {
+        address owner;
+        string name;
+        address[] members;
+        mapping(address => uint256) default_amounts;
+        /// Position in `members` plus one, zero for non-members
+        mapping(address => uint256) member_positions;
     }
 }
 
@@ -59,6 +657,20 @@ impl MultiSender {
         self.owner.set(initial_owner);
         self.total_transactions.set(U256::ZERO);
         self.total_recipients.set(U256::ZERO);
+        // Also locks `initialize` on implementations deployed for a proxy
+        self.initialized.set(true);
+    }
+
+    /// Initializer used instead of the constructor when deployed behind a proxy
+    pub fn initialize(&mut self, initial_owner: Address) -> Result<(), Error> {
+        // Contracts deployed before `initialized` existed already have an owner
+        if self.initialized.get() || self.owner.get() != Address::ZERO {
+            return Err(Error::AlreadyInitialized(AlreadyInitialized {}));
+        }
+
+        self.owner.set(initial_owner);
+        self.initialized.set(true);
+        Ok(())
     }
 
     /// Get the contract owner
@@ -87,157 +699,5282 @@ impl MultiSender {
         &mut self,
         recipients: Vec<Address>,
         amounts: Vec<U256>,
-    ) {
-        // Validate input arrays
-        if recipients.len() != amounts.len() {
-            return;
+    ) -> Result<BatchResult, Error> {
+        self.batch_send_eth_with_mode(recipients, amounts, EVENTS_VERBOSE)
+    }
+
+    /// Batch send ETH once per `batch_key` of the sender, so a retried submission reverts with
+    /// `BatchAlreadyExecuted` instead of paying twice. A zero key disables the check.
+    #[payable]
+    pub fn batch_send_eth_with_key(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        event_mode: u8,
+        batch_key: B256,
+    ) -> Result<BatchResult, Error> {
+        self.send_eth_batch(recipients, amounts, event_mode, 0, batch_key)
+    }
+
+    /// Batch send ETH to multiple recipients, logging `EVENTS_VERBOSE` or `EVENTS_COMPACT` events
+    #[payable]
+    pub fn batch_send_eth_with_mode(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        event_mode: u8,
+    ) -> Result<BatchResult, Error> {
+        self.send_eth_batch(recipients, amounts, event_mode, 0, B256::ZERO)
+    }
+
+    /// Batch send ETH until at most `gas_reserve` gas is left (`DEFAULT_GAS_RESERVE` for zero),
+    /// refunding the unprocessed amounts. Resume with the recipients from `nextIndex` on; each
+    /// resumed call is a new batch and counts against the sender's `maxBatchesPerDay`.
+    #[payable]
+    pub fn batch_send_eth_partial(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        gas_reserve: u64,
+    ) -> Result<PartialBatchResult, Error> {
+        let count = recipients.len();
+        let gas_reserve = if gas_reserve == 0 { DEFAULT_GAS_RESERVE } else { gas_reserve };
+        let (batch_id, successes, statuses, refunded) =
+            self.send_eth_batch(recipients, amounts, EVENTS_VERBOSE, gas_reserve, B256::ZERO)?;
+        let next_index = self.log_partial_batch(NATIVE_ETH, statuses.len(), count);
+        Ok((batch_id, successes, statuses, refunded, next_index))
+    }
+
+    /// Batch send ERC20 tokens to multiple recipients
+    pub fn batch_send_token(
+        &mut self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<BatchResult, Error> {
+        self.batch_send_token_with_mode(token, recipients, amounts, EVENTS_VERBOSE)
+    }
+
+    /// Batch send ERC20 tokens once per `batch_key` of the sender, so a retried submission
+    /// reverts with `BatchAlreadyExecuted` instead of paying twice. A zero key disables the check.
+    pub fn batch_send_token_with_key(
+        &mut self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        event_mode: u8,
+        batch_key: B256,
+    ) -> Result<BatchResult, Error> {
+        self.send_token_batch(token, recipients, amounts, event_mode, 0, batch_key)
+    }
+
+    /// Check whether `sender` already executed a batch with `batch_key`
+    pub fn is_batch_executed(&self, sender: Address, batch_key: B256) -> bool {
+        self.executed_batch_keys.getter(sender).get(batch_key)
+    }
+
+    /// Set the caller's limits for batches of `asset` (`NATIVE_ETH` for ETH), zero meaning
+    /// unlimited. Tighter limits apply at once; raised limits are queued for
+    /// `LIMIT_RAISE_DELAY` seconds and applied with `apply_sender_limits`. A mixed update
+    /// applies its tighter fields now and queues the whole update.
+    pub fn set_sender_limits(
+        &mut self,
+        asset: Address,
+        max_amount: U256,
+        max_total: U256,
+        max_batches_per_day: U256,
+    ) -> Result<(), Error> {
+        let sender = self.vm().msg_sender();
+        let (current_amount, current_total, current_batches) = self.sender_limits(sender, asset);
+        let tighter = (
+            is_tighter(max_amount, current_amount),
+            is_tighter(max_total, current_total),
+            is_tighter(max_batches_per_day, current_batches),
+        );
+        let raised = !(tighter.0 && tighter.1 && tighter.2);
+        let applied = (
+            if tighter.0 { max_amount } else { current_amount },
+            if tighter.1 { max_total } else { current_total },
+            if tighter.2 { max_batches_per_day } else { current_batches },
+        );
+
+        if applied != (current_amount, current_total, current_batches) || !raised {
+            // Tightening every field also drops any queued raise
+            {
+                let mut outer = self.sender_limits.setter(sender);
+                let mut limits = outer.setter(asset);
+                limits.max_amount.set(applied.0);
+                limits.max_total.set(applied.1);
+                limits.max_batches_per_day.set(applied.2);
+                if !raised {
+                    limits.pending_effective_at.set(U256::ZERO);
+                }
+            }
+            log(self.vm(), SenderLimitsUpdated {
+                sender,
+                asset,
+                maxAmount: applied.0,
+                maxTotal: applied.1,
+                maxBatchesPerDay: applied.2,
+            });
+        }
+        if !raised {
+            return Ok(());
         }
 
-        if recipients.is_empty() {
-            return;
+        let now = U256::from(self.vm().block_timestamp());
+        let effective_at = checked_add(now, U256::from(LIMIT_RAISE_DELAY))?;
+        {
+            let mut outer = self.sender_limits.setter(sender);
+            let mut limits = outer.setter(asset);
+            limits.pending_max_amount.set(max_amount);
+            limits.pending_max_total.set(max_total);
+            limits.pending_max_batches_per_day.set(max_batches_per_day);
+            limits.pending_effective_at.set(effective_at);
         }
+        log(self.vm(), SenderLimitsQueued {
+            sender,
+            asset,
+            maxAmount: max_amount,
+            maxTotal: max_total,
+            maxBatchesPerDay: max_batches_per_day,
+            effectiveAt: effective_at,
+        });
+        Ok(())
+    }
 
+    /// Apply the caller's queued limits for `asset` once their delay has passed
+    pub fn apply_sender_limits(&mut self, asset: Address) -> Result<(), Error> {
         let sender = self.vm().msg_sender();
-        let msg_value = self.vm().msg_value();
-        
-        // Calculate total amount needed
-        let mut total_amount = U256::ZERO;
-        for amount in &amounts {
-            if *amount == U256::ZERO {
-                return;
-            }
-            total_amount += *amount;
+        let (max_amount, max_total, max_batches_per_day, effective_at) =
+            self.pending_sender_limits(sender, asset);
+        if effective_at == U256::ZERO {
+            return Err(Error::NoPendingLimits(NoPendingLimits { asset }));
         }
 
-        // Check if sent value covers total amount
-        if msg_value < total_amount {
-            return;
+        let now = U256::from(self.vm().block_timestamp());
+        if now < effective_at {
+            return Err(Error::LimitsNotYetEffective(LimitsNotYetEffective {
+                asset,
+                effectiveAt: effective_at,
+            }));
         }
 
+        {
+            let mut outer = self.sender_limits.setter(sender);
+            let mut limits = outer.setter(asset);
+            limits.max_amount.set(max_amount);
+            limits.max_total.set(max_total);
+            limits.max_batches_per_day.set(max_batches_per_day);
+            limits.pending_effective_at.set(U256::ZERO);
+        }
+        log(self.vm(), SenderLimitsUpdated {
+            sender,
+            asset,
+            maxAmount: max_amount,
+            maxTotal: max_total,
+            maxBatchesPerDay: max_batches_per_day,
+        });
+        Ok(())
+    }
+
+    /// Get `(maxAmount, maxTotal, maxBatchesPerDay)` of `sender` for `asset`
+    pub fn sender_limits(&self, sender: Address, asset: Address) -> (U256, U256, U256) {
+        let outer = self.sender_limits.getter(sender);
+        let limits = outer.getter(asset);
+        (
+            limits.max_amount.get(),
+            limits.max_total.get(),
+            limits.max_batches_per_day.get(),
+        )
+    }
+
+    /// Get the queued `(maxAmount, maxTotal, maxBatchesPerDay, effectiveAt)` of `sender` for
+    /// `asset`, with a zero `effectiveAt` when nothing is queued
+    pub fn pending_sender_limits(
+        &self,
+        sender: Address,
+        asset: Address,
+    ) -> (U256, U256, U256, U256) {
+        let outer = self.sender_limits.getter(sender);
+        let limits = outer.getter(asset);
+        (
+            limits.pending_max_amount.get(),
+            limits.pending_max_total.get(),
+            limits.pending_max_batches_per_day.get(),
+            limits.pending_effective_at.get(),
+        )
+    }
+
+    /// Batch send ERC20 tokens to multiple recipients, logging `EVENTS_VERBOSE` or
+    /// `EVENTS_COMPACT` events
+    pub fn batch_send_token_with_mode(
+        &mut self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        event_mode: u8,
+    ) -> Result<BatchResult, Error> {
+        self.send_token_batch(token, recipients, amounts, event_mode, 0, B256::ZERO)
+    }
+
+    /// Batch send ERC20 tokens until at most `gas_reserve` gas is left (`DEFAULT_GAS_RESERVE`
+    /// for zero). Resume with the recipients from `nextIndex` on; each resumed call counts
+    /// against the sender's `maxBatchesPerDay`.
+    pub fn batch_send_token_partial(
+        &mut self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        gas_reserve: u64,
+    ) -> Result<PartialBatchResult, Error> {
+        let count = recipients.len();
+        let gas_reserve = if gas_reserve == 0 { DEFAULT_GAS_RESERVE } else { gas_reserve };
+        let (batch_id, successes, statuses, refunded) = self.send_token_batch(
+            token,
+            recipients,
+            amounts,
+            EVENTS_VERBOSE,
+            gas_reserve,
+            B256::ZERO,
+        )?;
+        let next_index = self.log_partial_batch(token, statuses.len(), count);
+        Ok((batch_id, successes, statuses, refunded, next_index))
+    }
+
+    /// Withdraw ETH to multiple L1 recipients through ArbSys, returning the L2-to-L1 message ids.
+    /// The batch is all-or-nothing: any failed withdrawal reverts it.
+    #[payable]
+    pub fn batch_withdraw_to_l1(
+        &mut self,
+        l1_recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<Vec<U256>, Error> {
+        self.withdraw_to_l1(l1_recipients, amounts, B256::ZERO)
+    }
+
+    /// Withdraw ETH to multiple L1 recipients once per `batch_key` of the sender, like
+    /// `batch_send_eth_with_key`
+    #[payable]
+    pub fn batch_withdraw_to_l1_with_key(
+        &mut self,
+        l1_recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        batch_key: B256,
+    ) -> Result<Vec<U256>, Error> {
+        self.withdraw_to_l1(l1_recipients, amounts, batch_key)
+    }
+
+    /// Append recipients to a stored batch, creating it on first use
+    pub fn append_batch(
+        &mut self,
+        batch_id: U256,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<(), Error> {
+        self.append_to_batch(batch_id, recipients, amounts, B256::ZERO)
+    }
+
+    /// Append recipients to a stored batch once per `batch_key` of the sender, so a retried
+    /// submission reverts with `BatchAlreadyExecuted` instead of listing them twice
+    pub fn append_batch_with_key(
+        &mut self,
+        batch_id: U256,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        batch_key: B256,
+    ) -> Result<(), Error> {
+        self.append_to_batch(batch_id, recipients, amounts, batch_key)
+    }
+
+    /// Fund a stored batch with its total amount, locking it against further appends
+    #[payable]
+    pub fn fund_batch(&mut self, batch_id: U256) -> Result<(), Error> {
+        let sender = self.vm().msg_sender();
+        let msg_value = self.vm().msg_value();
+
+        let total_amount = {
+            let mut batch = self.stored_batches.setter(batch_id);
+            Self::check_batch_sender(batch_id, batch.sender.get(), sender)?;
+
+            if batch.closed.get() {
+                return Err(Error::BatchClosed(BatchClosed { batchId: batch_id }));
+            }
+
+            if batch.funded.get() {
+                return Err(Error::BatchAlreadyFunded(BatchAlreadyFunded { batchId: batch_id }));
+            }
+
+            let total_amount = batch.total_amount.get();
+            if msg_value < total_amount {
+                return Err(Error::InsufficientValue(InsufficientValue {
+                    required: total_amount,
+                    provided: msg_value,
+                }));
+            }
+
+            batch.funded.set(true);
+            total_amount
+        };
+
+        let amounts = {
+            let batch = self.stored_batches.getter(batch_id);
+            (0..batch.amounts.len())
+                .map(|index| batch.amounts.get(index).unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+        self.enforce_limits(sender, NATIVE_ETH, &amounts, total_amount)?;
+        self.reserve_funds(NATIVE_ETH, total_amount)?;
+
+        log(self.vm(), StoredBatchFunded {
+            batchId: batch_id,
+            sender,
+            totalAmount: total_amount,
+        });
+
+        // Return excess ETH if any
+        self.refund(NATIVE_ETH, sender, msg_value - total_amount)?;
+
+        Ok(())
+    }
+
+    /// Pay out up to `max_items` entries of a funded batch, returning the new cursor.
+    /// Anyone may call this until the batch is complete.
+    pub fn process_batch(&mut self, batch_id: U256, max_items: U256) -> Result<U256, Error> {
+        let (sender, length, start) = {
+            let batch = self.stored_batches.getter(batch_id);
+            let sender = batch.sender.get();
+            if sender == Address::ZERO {
+                return Err(Error::UnknownBatch(UnknownBatch { batchId: batch_id }));
+            }
+            if batch.closed.get() {
+                return Err(Error::BatchClosed(BatchClosed { batchId: batch_id }));
+            }
+            if !batch.funded.get() {
+                return Err(Error::BatchNotFunded(BatchNotFunded { batchId: batch_id }));
+            }
+            (sender, batch.recipients.len(), batch.cursor.get().to::<usize>())
+        };
+
+        let remaining = length - start;
+        let end = if max_items < U256::from(remaining) {
+            start + max_items.to::<usize>()
+        } else {
+            length
+        };
+
         // Perform transfers
+        let mut paid = U256::ZERO;
         let mut successful_transfers = 0u32;
-        for (i, &recipient) in recipients.iter().enumerate() {
-            if recipient == Address::ZERO {
-                log(self.vm(), TransferFailed {
-                    recipient,
-                    amount: amounts[i],
-                    reason: "Invalid recipient address".to_string(),
-                });
-                continue;
-            }
+        for index in start..end {
+            let (recipient, amount) = {
+                let batch = self.stored_batches.getter(batch_id);
+                (
+                    batch.recipients.get(index).unwrap_or_default(),
+                    batch.amounts.get(index).unwrap_or_default(),
+                )
+            };
 
-            // Attempt transfer
-            match self.vm().transfer_eth(recipient, amounts[i]) {
+            match self.vm().transfer_eth(recipient, amount) {
                 Ok(_) => {
                     successful_transfers += 1;
-                    log(self.vm(), TransferSuccess {
-                        recipient,
-                        amount: amounts[i],
-                    });
+                    paid = checked_add(paid, amount)?;
+                    log(self.vm(), TransferSuccess { recipient, amount });
                 }
                 Err(_) => {
                     log(self.vm(), TransferFailed {
                         recipient,
-                        amount: amounts[i],
+                        amount,
                         reason: "Transfer failed".to_string(),
                     });
                 }
             }
         }
 
-        // Update statistics
-        let current_total_tx = self.total_transactions.get();
-        self.total_transactions.set(current_total_tx + U256::from(1));
-        
-        let current_total_recipients = self.total_recipients.get();
-        self.total_recipients.set(current_total_recipients + U256::from(successful_transfers));
-        
-        let current_user_count = self.user_transaction_count.get(sender);
-        self.user_transaction_count.insert(sender, current_user_count + U256::from(1));
+        let (paid_amount, success_count, refund) = {
+            let mut batch = self.stored_batches.setter(batch_id);
+            batch.cursor.set(U256::from(end));
+            let paid_amount = checked_add(batch.paid_amount.get(), paid)?;
+            batch.paid_amount.set(paid_amount);
+            let success_count =
+                checked_add(batch.success_count.get(), U256::from(successful_transfers))?;
+            batch.success_count.set(success_count);
 
-        // Emit batch transfer event
-        log(self.vm(), BatchEthTransfer {
-            sender,
-            totalAmount: total_amount,
-            recipientCount: U256::from(successful_transfers),
+            // Failed transfers stay in the contract until the batch completes
+            let refund = batch.total_amount.get() - paid_amount;
+            if end == length {
+                batch.closed.set(true);
+            }
+            (paid_amount, success_count, refund)
+        };
+
+        // Release what left the contract, plus the refund if the batch is done
+        let released = if end == length { checked_add(paid, refund)? } else { paid };
+        self.release_funds(NATIVE_ETH, released)?;
+
+        log(self.vm(), StoredBatchProcessed {
+            batchId: batch_id,
+            cursor: U256::from(end),
+            remaining: U256::from(length - end),
         });
 
-        // Return excess ETH if any
-        let excess = msg_value - total_amount;
-        if excess > U256::ZERO {
-            let _ = self.vm().transfer_eth(sender, excess);
+        if end == length {
+            self.record_batch(sender, success_count)?;
+
+            log(self.vm(), BatchEthTransfer {
+                sender,
+                totalAmount: paid_amount,
+                recipientCount: success_count,
+            });
+
+            log(self.vm(), StoredBatchCompleted {
+                batchId: batch_id,
+                successCount: success_count,
+                refundedAmount: refund,
+            });
+
+            // A sender that rejects the refund can claim it from the ledger
+            self.refund(NATIVE_ETH, sender, refund)?;
         }
+
+        Ok(U256::from(end))
     }
 
-    /// Emergency withdraw function (owner only)
-    pub fn emergency_withdraw(&mut self) {
+    /// Cancel a stored batch and refund everything that has not been paid out (batch sender only)
+    pub fn cancel_batch(&mut self, batch_id: U256) -> Result<(), Error> {
         let caller = self.vm().msg_sender();
-        let owner = self.owner.get();
-        
-        if caller != owner {
-            return;
-        }
-        
+
+        let (refund, success_count) = {
+            let mut batch = self.stored_batches.setter(batch_id);
+            Self::check_batch_sender(batch_id, batch.sender.get(), caller)?;
+
+            if batch.closed.get() {
+                return Err(Error::BatchClosed(BatchClosed { batchId: batch_id }));
+            }
+
+            batch.closed.set(true);
+            let refund = if batch.funded.get() {
+                batch.total_amount.get() - batch.paid_amount.get()
+            } else {
+                U256::ZERO
+            };
+            (refund, batch.success_count.get())
+        };
+
+        self.release_funds(NATIVE_ETH, refund)?;
+
+        // Count partially processed batches like any other batch
+        if success_count > U256::ZERO {
+            self.record_batch(caller, success_count)?;
+        }
+
+        log(self.vm(), StoredBatchCancelled {
+            batchId: batch_id,
+            refundedAmount: refund,
+        });
+
+        self.refund(NATIVE_ETH, caller, refund)?;
+
+        Ok(())
+    }
+
+    /// Get the progress of a stored batch as
+    /// (sender, cursor, item count, total amount, paid amount, funded, closed)
+    pub fn batch_progress(
+        &self,
+        batch_id: U256,
+    ) -> (Address, U256, U256, U256, U256, bool, bool) {
+        let batch = self.stored_batches.getter(batch_id);
+        (
+            batch.sender.get(),
+            batch.cursor.get(),
+            U256::from(batch.recipients.len()),
+            batch.total_amount.get(),
+            batch.paid_amount.get(),
+            batch.funded.get(),
+            batch.closed.get(),
+        )
+    }
+
+    /// Deposit ETH into the caller's ledger balance
+    #[payable]
+    pub fn deposit_eth(&mut self) -> Result<(), Error> {
+        let sender = self.vm().msg_sender();
+        let amount = self.vm().msg_value();
+
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        self.credit_ledger(NATIVE_ETH, sender, amount)?;
+        self.reserve_funds(NATIVE_ETH, amount)?;
+
+        log(self.vm(), LedgerDeposit {
+            token: NATIVE_ETH,
+            account: sender,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit approved ERC20 tokens into the caller's ledger balance.
+    /// Only the amount actually received is credited, so fee-on-transfer tokens stay solvent.
+    pub fn deposit_token(&mut self, token: Address, amount: U256) -> Result<(), Error> {
+        if token == NATIVE_ETH {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: token }));
+        }
+
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+        let contract = self.vm().contract_address();
+
+        let balance_before = self.token_balance(token, contract)?;
+        if self.token_transfer_from(token, sender, contract, amount).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token }));
+        }
+        let received = self
+            .token_balance(token, contract)?
+            .saturating_sub(balance_before);
+
+        self.credit_ledger(token, sender, received)?;
+        self.reserve_funds(token, received)?;
+
+        log(self.vm(), LedgerDeposit {
+            token,
+            account: sender,
+            amount: received,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw from the caller's ledger balance (`token` is the zero address for ETH)
+    pub fn withdraw(&mut self, token: Address, amount: U256) -> Result<(), Error> {
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+        self.debit_ledger(token, sender, amount)?;
+        self.release_funds(token, amount)?;
+
+        if token == NATIVE_ETH {
+            if self.vm().transfer_eth(sender, amount).is_err() {
+                return Err(Error::EthTransferFailed(EthTransferFailed { recipient: sender }));
+            }
+        } else if self.token_transfer(token, sender, amount).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token }));
+        }
+
+        log(self.vm(), LedgerWithdrawal {
+            token,
+            account: sender,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Move ledger balance from the caller to many recipients without any external calls
+    pub fn batch_credit(
+        &mut self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<(), Error> {
+        // Validate input arrays
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+
+        // Calculate total amount needed
+        let mut total_amount = U256::ZERO;
+        for (i, &recipient) in recipients.iter().enumerate() {
+            if recipient == Address::ZERO {
+                return Err(Error::InvalidRecipient(InvalidRecipient { recipient }));
+            }
+            if amounts[i] == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, amounts[i])?;
+        }
+
+        self.enforce_limits(sender, token, &amounts, total_amount)?;
+        self.debit_ledger(token, sender, total_amount)?;
+
+        for (i, &recipient) in recipients.iter().enumerate() {
+            self.credit_ledger(token, recipient, amounts[i])?;
+            log(self.vm(), LedgerCredit {
+                token,
+                from: sender,
+                to: recipient,
+                amount: amounts[i],
+            });
+        }
+
+        self.record_batch(sender, U256::from(recipients.len()))?;
+
+        Ok(())
+    }
+
+    /// Get the ledger balance of `account` for `token` (the zero address for ETH)
+    pub fn ledger_balance(&self, token: Address, account: Address) -> U256 {
+        self.ledger_balances.getter(token).get(account)
+    }
+
+    /// Register a recurring payment schedule and return its id.
+    /// `asset` is the zero address for ETH, which can only be funded from the ledger.
+    /// A `start` of zero means now and an `end` of zero means no end.
+    /// With `catch_up` set, missed periods are paid one per execution; otherwise they are skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_schedule(
+        &mut self,
+        name: String,
+        asset: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        interval: U256,
+        start: U256,
+        end: U256,
+        funding_source: u8,
+        catch_up: bool,
+    ) -> Result<U256, Error> {
+        // Validate input arrays
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let now = U256::from(self.vm().block_timestamp());
+        let start = if start == U256::ZERO { now } else { start };
+        let from_allowance = match funding_source {
+            FUNDING_LEDGER => false,
+            FUNDING_ALLOWANCE if asset != NATIVE_ETH => true,
+            _ => return Err(Error::InvalidSchedule(InvalidSchedule {})),
+        };
+
+        if interval == U256::ZERO || (end != U256::ZERO && end < start) {
+            return Err(Error::InvalidSchedule(InvalidSchedule {}));
+        }
+
+        let mut total_amount = U256::ZERO;
+        for (i, &recipient) in recipients.iter().enumerate() {
+            if recipient == Address::ZERO {
+                return Err(Error::InvalidRecipient(InvalidRecipient { recipient }));
+            }
+            if amounts[i] == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, amounts[i])?;
+        }
+
+        let payer = self.vm().msg_sender();
+        let schedule_id = checked_add(self.schedule_count.get(), U256::from(1))?;
+        self.schedule_count.set(schedule_id);
+
+        {
+            let mut schedule = self.schedules.setter(schedule_id);
+            schedule.payer.set(payer);
+            schedule.name.set_str(&name);
+            schedule.asset.set(asset);
+            for (i, &recipient) in recipients.iter().enumerate() {
+                schedule.recipients.push(recipient);
+                schedule.amounts.push(amounts[i]);
+            }
+            schedule.total_amount.set(total_amount);
+            schedule.interval.set(interval);
+            schedule.start.set(start);
+            schedule.end.set(end);
+            schedule.from_allowance.set(from_allowance);
+            schedule.catch_up.set(catch_up);
+        }
+
+        log(self.vm(), ScheduleCreated {
+            scheduleId: schedule_id,
+            payer,
+            asset,
+            name,
+        });
+
+        Ok(schedule_id)
+    }
+
+    /// Pay the next due period of a schedule and return its index. Callable by anyone.
+    pub fn execute_schedule(&mut self, schedule_id: U256) -> Result<U256, Error> {
+        let now = U256::from(self.vm().block_timestamp());
+
+        let (payer, asset, total_amount, from_allowance, skipped_from, period) = {
+            let mut schedule = self.schedules.setter(schedule_id);
+            if schedule.payer.get() == Address::ZERO {
+                return Err(Error::UnknownSchedule(UnknownSchedule { scheduleId: schedule_id }));
+            }
+            if schedule.cancelled.get() {
+                return Err(Error::ScheduleIsCancelled(ScheduleIsCancelled { scheduleId: schedule_id }));
+            }
+            if schedule.paused.get() {
+                return Err(Error::ScheduleIsPaused(ScheduleIsPaused { scheduleId: schedule_id }));
+            }
+
+            let start = schedule.start.get();
+            let interval = schedule.interval.get();
+            let end = schedule.end.get();
+            let skipped_from = schedule.executed_periods.get();
+            let mut period = skipped_from;
+
+            // Without catch-up only the most recent elapsed period is paid
+            if !schedule.catch_up.get() && now >= start {
+                let current = (now - start) / interval;
+                if current > period {
+                    period = current;
+                }
+            }
+
+            let due = checked_add(start, checked_mul(period, interval)?)?;
+            if end != U256::ZERO && due > end {
+                return Err(Error::ScheduleFinished(ScheduleFinished { scheduleId: schedule_id }));
+            }
+            if now < due {
+                return Err(Error::ScheduleNotDue(ScheduleNotDue {
+                    scheduleId: schedule_id,
+                    nextDue: due,
+                }));
+            }
+
+            // Mark the period as paid before any transfer so it can never run twice
+            schedule.executed_periods.set(checked_add(period, U256::from(1))?);
+            (
+                schedule.payer.get(),
+                schedule.asset.get(),
+                schedule.total_amount.get(),
+                schedule.from_allowance.get(),
+                skipped_from,
+                period,
+            )
+        };
+
+        if period > skipped_from {
+            log(self.vm(), SchedulePeriodsSkipped {
+                scheduleId: schedule_id,
+                fromPeriod: skipped_from,
+                toPeriod: period,
+            });
+        }
+
+        let (recipients, amounts): (Vec<Address>, Vec<U256>) = {
+            let schedule = self.schedules.getter(schedule_id);
+            (0..schedule.recipients.len())
+                .map(|index| {
+                    (
+                        schedule.recipients.get(index).unwrap_or_default(),
+                        schedule.amounts.get(index).unwrap_or_default(),
+                    )
+                })
+                .unzip()
+        };
+        self.enforce_limits(payer, asset, &amounts, total_amount)?;
+
+        if !from_allowance {
+            self.debit_ledger(asset, payer, total_amount)?;
+            self.release_funds(asset, total_amount)?;
+        }
+
+        let mut successful_transfers = 0u32;
+        for (&recipient, &amount) in recipients.iter().zip(&amounts) {
+
+            let result = if from_allowance {
+                self.token_transfer_from(asset, payer, recipient, amount)
+            } else {
+                self.send_asset(asset, recipient, amount)
+            };
+
+            match result {
+                Ok(_) => {
+                    successful_transfers += 1;
+                    log(self.vm(), TransferSuccess { recipient, amount });
+                }
+                Err(code) => {
+                    // Ledger-funded payments stay claimable by the recipient
+                    if !from_allowance {
+                        self.credit_ledger(asset, recipient, amount)?;
+                        self.reserve_funds(asset, amount)?;
+                    }
+                    log(self.vm(), TransferFailed {
+                        recipient,
+                        amount,
+                        reason: failure_reason(code).to_string(),
+                    });
+                }
+            }
+        }
+
+        self.record_batch(payer, U256::from(successful_transfers))?;
+
+        log(self.vm(), ScheduleExecuted {
+            scheduleId: schedule_id,
+            period,
+            totalAmount: total_amount,
+            recipientCount: U256::from(successful_transfers),
+        });
+
+        Ok(period)
+    }
+
+    /// Pause a schedule (schedule payer only)
+    pub fn pause_schedule(&mut self, schedule_id: U256) -> Result<(), Error> {
+        self.check_schedule_payer(schedule_id)?;
+        self.schedules.setter(schedule_id).paused.set(true);
+        log(self.vm(), SchedulePaused { scheduleId: schedule_id });
+        Ok(())
+    }
+
+    /// Resume a paused schedule (schedule payer only). Cancelled schedules stay cancelled.
+    pub fn resume_schedule(&mut self, schedule_id: U256) -> Result<(), Error> {
+        self.check_schedule_payer(schedule_id)?;
+        let (paused, cancelled) = {
+            let schedule = self.schedules.getter(schedule_id);
+            (schedule.paused.get(), schedule.cancelled.get())
+        };
+        if cancelled {
+            return Err(Error::ScheduleIsCancelled(ScheduleIsCancelled { scheduleId: schedule_id }));
+        }
+        if !paused {
+            return Err(Error::ScheduleNotPaused(ScheduleNotPaused { scheduleId: schedule_id }));
+        }
+
+        self.schedules.setter(schedule_id).paused.set(false);
+        log(self.vm(), ScheduleResumed { scheduleId: schedule_id });
+        Ok(())
+    }
+
+    /// Permanently cancel a schedule (schedule payer only)
+    pub fn cancel_schedule(&mut self, schedule_id: U256) -> Result<(), Error> {
+        self.check_schedule_payer(schedule_id)?;
+        self.schedules.setter(schedule_id).cancelled.set(true);
+        log(self.vm(), ScheduleCancelled { scheduleId: schedule_id });
+        Ok(())
+    }
+
+    /// Get the number of schedules ever created
+    pub fn schedule_count(&self) -> U256 {
+        self.schedule_count.get()
+    }
+
+    /// Get a schedule as
+    /// (payer, name, asset, total per period, interval, start, end, executed periods, paused, cancelled)
+    #[allow(clippy::type_complexity)]
+    pub fn schedule_info(
+        &self,
+        schedule_id: U256,
+    ) -> (Address, String, Address, U256, U256, U256, U256, U256, bool, bool) {
+        let schedule = self.schedules.getter(schedule_id);
+        (
+            schedule.payer.get(),
+            schedule.name.get_string(),
+            schedule.asset.get(),
+            schedule.total_amount.get(),
+            schedule.interval.get(),
+            schedule.start.get(),
+            schedule.end.get(),
+            schedule.executed_periods.get(),
+            schedule.paused.get(),
+            schedule.cancelled.get(),
+        )
+    }
+
+    /// Get the recipients and per-period amounts of a schedule
+    pub fn schedule_recipients(&self, schedule_id: U256) -> (Vec<Address>, Vec<U256>) {
+        let schedule = self.schedules.getter(schedule_id);
+        let count = schedule.recipients.len();
+        let mut recipients = Vec::with_capacity(count);
+        let mut amounts = Vec::with_capacity(count);
+        for index in 0..count {
+            recipients.push(schedule.recipients.get(index).unwrap_or_default());
+            amounts.push(schedule.amounts.get(index).unwrap_or_default());
+        }
+        (recipients, amounts)
+    }
+
+    /// Create an empty recipient group owned by the caller and return its id
+    pub fn create_group(&mut self, name: String) -> Result<U256, Error> {
+        let owner = self.vm().msg_sender();
+        let group_id = checked_add(self.group_count.get(), U256::from(1))?;
+        self.group_count.set(group_id);
+
+        {
+            let mut group = self.groups.setter(group_id);
+            group.owner.set(owner);
+            group.name.set_str(&name);
+        }
+
+        log(self.vm(), GroupCreated {
+            groupId: group_id,
+            owner,
+            name,
+        });
+
+        Ok(group_id)
+    }
+
+    /// Add members to a group, or update the default amount of existing members (group owner only)
+    pub fn add_group_members(
+        &mut self,
+        group_id: U256,
+        members: Vec<Address>,
+        default_amounts: Vec<U256>,
+    ) -> Result<(), Error> {
+        // Validate input arrays
+        if members.len() != default_amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        self.check_group_owner(group_id)?;
+
+        for (i, &member) in members.iter().enumerate() {
+            if member == Address::ZERO {
+                return Err(Error::InvalidRecipient(InvalidRecipient { recipient: member }));
+            }
+            if default_amounts[i] == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+
+            {
+                let mut group = self.groups.setter(group_id);
+                if group.member_positions.get(member) == U256::ZERO {
+                    group.members.push(member);
+                    let position = U256::from(group.members.len());
+                    group.member_positions.insert(member, position);
+                }
+                group.default_amounts.insert(member, default_amounts[i]);
+            }
+
+            log(self.vm(), GroupMemberSet {
+                groupId: group_id,
+                member,
+                defaultAmount: default_amounts[i],
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Remove members from a group (group owner only)
+    pub fn remove_group_members(&mut self, group_id: U256, members: Vec<Address>) -> Result<(), Error> {
+        self.check_group_owner(group_id)?;
+
+        for &member in &members {
+            {
+                let mut group = self.groups.setter(group_id);
+                let position = group.member_positions.get(member);
+                if position == U256::ZERO {
+                    return Err(Error::NotGroupMember(NotGroupMember {
+                        groupId: group_id,
+                        member,
+                    }));
+                }
+
+                // Move the last member into the freed slot
+                let index = position.to::<usize>() - 1;
+                let last = group.members.pop().unwrap_or_default();
+                if last != member {
+                    if let Some(mut slot) = group.members.setter(index) {
+                        slot.set(last);
+                    }
+                    group.member_positions.insert(last, position);
+                }
+                group.member_positions.delete(member);
+                group.default_amounts.delete(member);
+            }
+
+            log(self.vm(), GroupMemberRemoved {
+                groupId: group_id,
+                member,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Get the number of groups ever created
+    pub fn group_count(&self) -> U256 {
+        self.group_count.get()
+    }
+
+    /// Get a group as (owner, name, member count)
+    pub fn group_info(&self, group_id: U256) -> (Address, String, U256) {
+        let group = self.groups.getter(group_id);
+        (
+            group.owner.get(),
+            group.name.get_string(),
+            U256::from(group.members.len()),
+        )
+    }
+
+    /// Get the members of a group with their default amounts
+    pub fn group_members(&self, group_id: U256) -> (Vec<Address>, Vec<U256>) {
+        let group = self.groups.getter(group_id);
+        let count = group.members.len();
+        let mut members = Vec::with_capacity(count);
+        let mut amounts = Vec::with_capacity(count);
+        for index in 0..count {
+            let member = group.members.get(index).unwrap_or_default();
+            members.push(member);
+            amounts.push(group.default_amounts.get(member));
+        }
+        (members, amounts)
+    }
+
+    /// Batch send ETH to every member of a group using their default amounts
+    #[payable]
+    pub fn batch_send_eth_to_group(&mut self, group_id: U256) -> Result<BatchResult, Error> {
+        self.batch_send_eth_to_group_with_key(group_id, B256::ZERO)
+    }
+
+    /// Batch send ETH to every member of a group once per `batch_key` of the sender, like
+    /// `batch_send_eth_with_key`
+    #[payable]
+    pub fn batch_send_eth_to_group_with_key(
+        &mut self,
+        group_id: U256,
+        batch_key: B256,
+    ) -> Result<BatchResult, Error> {
+        let (recipients, amounts) = self.group_batch(group_id)?;
+        self.send_eth_batch(recipients, amounts, EVENTS_VERBOSE, 0, batch_key)
+    }
+
+    /// Batch send ERC20 tokens to every member of a group.
+    /// Each default amount is multiplied by `scale`, so one group can serve tokens
+    /// with different decimals (e.g. a scale of 10^6 for USDC).
+    pub fn batch_send_token_to_group(
+        &mut self,
+        token: Address,
+        group_id: U256,
+        scale: U256,
+    ) -> Result<BatchResult, Error> {
+        self.batch_send_token_to_group_with_key(token, group_id, scale, B256::ZERO)
+    }
+
+    /// Batch send ERC20 tokens to every member of a group once per `batch_key` of the sender,
+    /// like `batch_send_token_with_key`
+    pub fn batch_send_token_to_group_with_key(
+        &mut self,
+        token: Address,
+        group_id: U256,
+        scale: U256,
+        batch_key: B256,
+    ) -> Result<BatchResult, Error> {
+        if scale == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let (recipients, default_amounts) = self.group_batch(group_id)?;
+
+        let mut amounts = Vec::with_capacity(default_amounts.len());
+        for amount in &default_amounts {
+            amounts.push(checked_mul(*amount, scale)?);
+        }
+        self.send_token_batch(token, recipients, amounts, EVENTS_VERBOSE, 0, batch_key)
+    }
+
+    /// Request `amount` of `asset` (`NATIVE_ETH` for ETH) from `payer`, returning the invoice id
+    pub fn create_invoice(
+        &mut self,
+        payer: Address,
+        asset: Address,
+        amount: U256,
+        due_date: U256,
+        reference: String,
+    ) -> Result<U256, Error> {
+        if payer == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: payer }));
+        }
+
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let issuer = self.vm().msg_sender();
+        let invoice_id = checked_add(self.invoice_count.get(), U256::from(1))?;
+        self.invoice_count.set(invoice_id);
+
+        {
+            let mut invoice = self.invoices.setter(invoice_id);
+            invoice.issuer.set(issuer);
+            invoice.payer.set(payer);
+            invoice.asset.set(asset);
+            invoice.amount.set(amount);
+            invoice.due_date.set(due_date);
+            invoice.reference.set_str(&reference);
+            invoice.status.set(U8::from(INVOICE_OPEN));
+        }
+
+        log(self.vm(), InvoiceCreated {
+            invoiceId: invoice_id,
+            issuer,
+            payer,
+            asset,
+            amount,
+            dueDate: due_date,
+            reference,
+        });
+
+        Ok(invoice_id)
+    }
+
+    /// Withdraw an open invoice (issuer only)
+    pub fn cancel_invoice(&mut self, invoice_id: U256) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        let (issuer, status) = {
+            let invoice = self.invoices.getter(invoice_id);
+            (invoice.issuer.get(), invoice.status.get().to::<u8>())
+        };
+        if issuer == Address::ZERO {
+            return Err(Error::UnknownInvoice(UnknownInvoice { invoiceId: invoice_id }));
+        }
+        if issuer != caller {
+            return Err(Error::UnauthorizedInvoiceIssuer(UnauthorizedInvoiceIssuer {
+                invoiceId: invoice_id,
+                caller,
+            }));
+        }
+        if status != INVOICE_OPEN {
+            return Err(Error::InvoiceNotOpen(InvoiceNotOpen { invoiceId: invoice_id, status }));
+        }
+
+        self.invoices.setter(invoice_id).status.set(U8::from(INVOICE_CANCELLED));
+        log(self.vm(), InvoiceCancelled { invoiceId: invoice_id });
+        Ok(())
+    }
+
+    /// Settle open invoices addressed to the caller. ETH invoices are paid from the sent value,
+    /// excess is refunded (or credited to the caller's ledger if refused); token invoices are pulled from the caller's allowance.
+    /// Any invoice that cannot be paid reverts the whole call.
+    #[payable]
+    pub fn pay_invoices(&mut self, invoice_ids: Vec<U256>) -> Result<U256, Error> {
+        if invoice_ids.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let payer = self.vm().msg_sender();
+        let msg_value = self.vm().msg_value();
+        let mut eth_spent = U256::ZERO;
+
+        for &invoice_id in &invoice_ids {
+            let (issuer, asset, amount) = self.check_invoice_payer(invoice_id)?;
+
+            // Mark as paid first so a repeated id in the same call is rejected
+            self.invoices.setter(invoice_id).status.set(U8::from(INVOICE_PAID));
+
+            if asset == NATIVE_ETH {
+                eth_spent = checked_add(eth_spent, amount)?;
+                if eth_spent > msg_value {
+                    return Err(Error::InsufficientValue(InsufficientValue {
+                        required: eth_spent,
+                        provided: msg_value,
+                    }));
+                }
+                if self.vm().transfer_eth(issuer, amount).is_err() {
+                    return Err(Error::EthTransferFailed(EthTransferFailed { recipient: issuer }));
+                }
+            } else if self.token_transfer_from(asset, payer, issuer, amount).is_err() {
+                return Err(Error::TokenTransferFailed(TokenTransferFailed { token: asset }));
+            }
+
+            log(self.vm(), InvoicePaid {
+                invoiceId: invoice_id,
+                payer,
+                issuer,
+                asset,
+                amount,
+            });
+        }
+
+        // Update statistics
+        let paid = U256::from(invoice_ids.len());
+        self.record_batch(payer, paid)?;
+
+        // Return excess ETH if any
+        self.refund(NATIVE_ETH, payer, msg_value - eth_spent)?;
+
+        Ok(paid)
+    }
+
+    /// Get the total number of invoices
+    pub fn invoice_count(&self) -> U256 {
+        self.invoice_count.get()
+    }
+
+    /// Get `(issuer, payer, asset, amount, dueDate, reference, status)` of an invoice
+    pub fn invoice_info(
+        &self,
+        invoice_id: U256,
+    ) -> (Address, Address, Address, U256, U256, String, u8) {
+        let invoice = self.invoices.getter(invoice_id);
+        (
+            invoice.issuer.get(),
+            invoice.payer.get(),
+            invoice.asset.get(),
+            invoice.amount.get(),
+            invoice.due_date.get(),
+            invoice.reference.get_string(),
+            invoice.status.get().to::<u8>(),
+        )
+    }
+
+    /// Let `collector` pull up to `amount` of `token` from the caller with `batch_collect_token`,
+    /// `U256::MAX` for no limit. The caller must also approve this contract on the token.
+    pub fn approve_collector(&mut self, collector: Address, token: Address, amount: U256) {
+        let payer = self.vm().msg_sender();
+        self.collector_allowances
+            .setter(payer)
+            .setter(collector)
+            .insert(token, amount);
+        log(self.vm(), CollectorApproval {
+            payer,
+            collector,
+            token,
+            amount,
+        });
+    }
+
+    /// Get how much of `token` `collector` may still pull from `payer`
+    pub fn collector_allowance(&self, payer: Address, collector: Address, token: Address) -> U256 {
+        self.collector_allowances.getter(payer).getter(collector).get(token)
+    }
+
+    /// Set a collector allowance from the payer's EIP-712 `CollectorAuthorization` signature,
+    /// so payers can authorize a collector without a transaction. Each signature is bound to
+    /// the payer's current `collector_nonce` and used once.
+    #[allow(clippy::too_many_arguments)]
+    pub fn approve_collector_by_sig(
+        &mut self,
+        payer: Address,
+        collector: Address,
+        token: Address,
+        amount: U256,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256,
+    ) -> Result<(), Error> {
+        let authorization = (payer, collector, token, amount, deadline);
+        self.authorize_collector(authorization, v, r, s)
+    }
+
+    /// Get the nonce the next `CollectorAuthorization` of `payer` must sign
+    pub fn collector_nonce(&self, payer: Address) -> U256 {
+        self.collector_nonces.get(payer)
+    }
+
+    /// Get the EIP-712 domain separator of `CollectorAuthorization` signatures
+    pub fn domain_separator(&self) -> B256 {
+        let domain_type = keccak256(
+            "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)",
+        );
+        let encoded = (
+            domain_type,
+            keccak256("MultiSender"),
+            keccak256("1"),
+            U256::from(self.vm().chain_id()),
+            self.vm().contract_address(),
+        )
+            .abi_encode();
+        keccak256(encoded)
+    }
+
+    /// Pull tokens from many payers into `to`. Each payer must have authorized the caller with
+    /// `approve_collector` and approved this contract on the token; payers that did not are
+    /// skipped with `CollectionFailed`.
+    pub fn batch_collect_token(
+        &mut self,
+        token: Address,
+        payers: Vec<Address>,
+        amounts: Vec<U256>,
+        to: Address,
+    ) -> Result<BatchResult, Error> {
+        self.collect_token_batch(token, &payers, &amounts, to)
+    }
+
+    /// Like `batch_collect_token` for payers who only signed: each payer's EIP-2612 permit for
+    /// this contract and `CollectorAuthorization` for the caller are applied first, both for
+    /// the payer's amount and deadline. Signatures are 65 bytes (`r`, `s`, `v`); an empty one
+    /// is skipped. A failed permit, e.g. one already submitted by someone else, is logged with
+    /// `CollectionPermitFailed` and the pull is tried anyway; an invalid authorization reverts.
+    #[allow(clippy::too_many_arguments)]
+    pub fn batch_collect_token_with_permit(
+        &mut self,
+        token: Address,
+        payers: Vec<Address>,
+        amounts: Vec<U256>,
+        to: Address,
+        deadlines: Vec<U256>,
+        permits: Vec<Bytes>,
+        authorizations: Vec<Bytes>,
+    ) -> Result<BatchResult, Error> {
+        let count = payers.len();
+        let lengths = [amounts.len(), deadlines.len(), permits.len(), authorizations.len()];
+        if lengths.iter().any(|len| *len != count) {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        let spender = self.vm().contract_address();
+        let collector = self.vm().msg_sender();
+        for i in 0..count {
+            let (payer, amount, deadline) = (payers[i], amounts[i], deadlines[i]);
+            if !permits[i].is_empty() {
+                let permitted = split_signature(&permits[i]).is_some_and(|(v, r, s)| {
+                    let call = IERC20Permit::permitCall {
+                        owner: payer,
+                        spender,
+                        value: amount,
+                        deadline,
+                        v,
+                        r,
+                        s,
+                    };
+                    let config = Call::new();
+                    self.vm().call(&config, token, &call.abi_encode()).is_ok()
+                });
+                if !permitted {
+                    log(self.vm(), CollectionPermitFailed { payer, token });
+                }
+            }
+            if !authorizations[i].is_empty() {
+                let (v, r, s) = split_signature(&authorizations[i]).ok_or(
+                    Error::InvalidCollectorSignature(InvalidCollectorSignature { payer }),
+                )?;
+                let authorization = (payer, collector, token, amount, deadline);
+                self.authorize_collector(authorization, v, r, s)?;
+            }
+        }
+
+        self.collect_token_batch(token, &payers, &amounts, to)
+    }
+
+    /// Get the WETH contract used for recipients that reject ETH (zero when disabled)
+    pub fn weth(&self) -> Address {
+        self.weth.get()
+    }
+
+    /// Set the WETH contract used for recipients that reject ETH, or zero to disable it (owner only)
+    pub fn set_weth(&mut self, weth: Address) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        self.weth.set(weth);
+        log(self.vm(), WethFallbackUpdated { weth });
+
+        Ok(())
+    }
+
+    /// Get the Uniswap-V2-style router used by the swap-and-distribute functions (zero when unset)
+    pub fn swap_router(&self) -> Address {
+        self.swap_router.get()
+    }
+
+    /// Set the swap router, or disable swaps with zero (owner only)
+    pub fn set_swap_router(&mut self, router: Address) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        self.swap_router.set(router);
+        log(self.vm(), SwapRouterUpdated { router });
+
+        Ok(())
+    }
+
+    /// Swap the sent ETH for `token_out` and split the output between `recipients` in proportion
+    /// to `weights`, returning `(batchId, amountOut, statuses)`. Reverts if the swap yields less
+    /// than `min_out`; rounding dust and failed shares go back to the sender, or to their ledger
+    /// balance if the sender rejects them. Only exact-input routers are supported: the router
+    /// must spend all the sent ETH, as `swapExactETHForTokens` does, since it cannot send any
+    /// back while the swap is running.
+    #[payable]
+    pub fn swap_eth_and_distribute(
+        &mut self,
+        token_out: Address,
+        recipients: Vec<Address>,
+        weights: Vec<U256>,
+        min_out: U256,
+        deadline: U256,
+    ) -> Result<(U256, U256, Vec<u8>), Error> {
+        let router = self.check_swap(token_out, &recipients, &weights)?;
+        let sender = self.vm().msg_sender();
+        let amount_in = self.vm().msg_value();
+        if amount_in == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+        self.enforce_limits(sender, NATIVE_ETH, &[amount_in], amount_in)?;
+
+        let weth = self
+            .vm()
+            .static_call(&Call::new(), router, &IUniswapV2Router::WETHCall {}.abi_encode())
+            .ok()
+            .and_then(|result| Address::abi_decode(&result, true).ok())
+            .ok_or(Error::SwapFailed(SwapFailed { returnData: Default::default() }))?;
+
+        // Measure what the swap actually moved rather than trusting the router's return value
+        let this = self.vm().contract_address();
+        let out_before = self.token_balance(token_out, this)?;
+        let call = IUniswapV2Router::swapExactETHForTokensCall {
+            amountOutMin: min_out,
+            path: vec![weth, token_out],
+            to: this,
+            deadline,
+        };
+        let config = Call::new().value(amount_in);
+        if let Err(err) = self.vm().call(&config, router, &call.abi_encode()) {
+            return Err(Error::SwapFailed(SwapFailed {
+                returnData: Vec::<u8>::from(err).into(),
+            }));
+        }
+        let amount_out = self.swap_output(token_out, out_before, min_out)?;
+
+        let swap = (NATIVE_ETH, amount_in, token_out, amount_out);
+        self.distribute_swapped(sender, swap, &recipients, &weights)
+    }
+
+    /// Swap `amount_in` of `token_in`, pulled from the caller, for `token_out` and split the
+    /// output like `swap_eth_and_distribute`
+    #[allow(clippy::too_many_arguments)]
+    pub fn swap_token_and_distribute(
+        &mut self,
+        token_in: Address,
+        amount_in: U256,
+        token_out: Address,
+        recipients: Vec<Address>,
+        weights: Vec<U256>,
+        min_out: U256,
+        deadline: U256,
+    ) -> Result<(U256, U256, Vec<u8>), Error> {
+        let router = self.check_swap(token_out, &recipients, &weights)?;
+        if token_in == Address::ZERO || token_in == token_out {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: token_in }));
+        }
+        if amount_in == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+        self.enforce_limits(sender, token_in, &[amount_in], amount_in)?;
+        let this = self.vm().contract_address();
+        let in_before = self.token_balance(token_in, this)?;
+        if self.token_transfer_from(token_in, sender, this, amount_in).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token: token_in }));
+        }
+        // Fee-on-transfer tokens deliver less than `amount_in`
+        let received = self.token_balance(token_in, this)?.saturating_sub(in_before);
+
+        let approve = IERC20::approveCall { spender: router, amount: received };
+        if self.call_token(token_in, &approve.abi_encode()).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token: token_in }));
+        }
+
+        let out_before = self.token_balance(token_out, this)?;
+        let call = IUniswapV2Router::swapExactTokensForTokensCall {
+            amountIn: received,
+            amountOutMin: min_out,
+            path: vec![token_in, token_out],
+            to: this,
+            deadline,
+        };
+        let config = Call::new();
+        if let Err(err) = self.vm().call(&config, router, &call.abi_encode()) {
+            return Err(Error::SwapFailed(SwapFailed {
+                returnData: Vec::<u8>::from(err).into(),
+            }));
+        }
+        let amount_out = self.swap_output(token_out, out_before, min_out)?;
+
+        // Drop any allowance the router left, which could otherwise pull reserved balances,
+        // and return unused input
+        let reset = IERC20::approveCall { spender: router, amount: U256::ZERO };
+        if self.call_token(token_in, &reset.abi_encode()).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token: token_in }));
+        }
+        let unused = self.token_balance(token_in, this)?.saturating_sub(in_before);
+        self.refund(token_in, sender, unused)?;
+
+        let spent = received - unused.min(received);
+        let swap = (token_in, spent, token_out, amount_out);
+        self.distribute_swapped(sender, swap, &recipients, &weights)
+    }
+
+    /// Check whether an ERC-1363 token may distribute through `onTransferReceived`
+    pub fn is_erc1363_token(&self, token: Address) -> bool {
+        self.erc1363_tokens.get(token)
+    }
+
+    /// Accept or stop accepting `transferAndCall` distributions from an ERC-1363 token
+    /// (owner only)
+    pub fn set_erc1363_token(&mut self, token: Address, accepted: bool) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        self.erc1363_tokens.insert(token, accepted);
+        log(self.vm(), Erc1363TokenUpdated { token, accepted });
+
+        Ok(())
+    }
+
+    /// ERC-1363 receiver hook: distribute the `value` tokens just sent by `from` to the
+    /// `(address[] recipients, uint256[] amounts)` ABI-encoded in `data`. Unknown tokens,
+    /// malformed data and a `value` the contract's unreserved balance does not cover revert the
+    /// whole transfer; failed transfers are returned to `from`, or credited to their ledger
+    /// balance if the return fails.
+    #[selector(name = "onTransferReceived")]
+    pub fn on_transfer_received(
+        &mut self,
+        _operator: Address,
+        from: Address,
+        value: U256,
+        data: Bytes,
+    ) -> Result<FixedBytes<4>, Error> {
+        // The calling token is the one that was received
+        let token = self.vm().msg_sender();
+        if !self.erc1363_tokens.get(token) {
+            return Err(Error::UnsupportedToken(UnsupportedToken { token }));
+        }
+
+        let (recipients, amounts) = <(Vec<Address>, Vec<U256>)>::abi_decode_params(&data, true)
+            .map_err(|_| Error::InvalidTransferData(InvalidTransferData {}))?;
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let mut total_amount = U256::ZERO;
+        for amount in &amounts {
+            if *amount == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, *amount)?;
+        }
+
+        if total_amount != value {
+            return Err(Error::TransferAmountMismatch(TransferAmountMismatch {
+                received: value,
+                total: total_amount,
+            }));
+        }
+
+        // Don't trust the reported value: the tokens must actually be here, on top of the
+        // reserved ledger and batch funds
+        let this = self.vm().contract_address();
+        let available = self
+            .token_balance(token, this)?
+            .saturating_sub(self.reserved_tokens.get(token));
+        if available < total_amount {
+            return Err(Error::TransferAmountMismatch(TransferAmountMismatch {
+                received: available,
+                total: total_amount,
+            }));
+        }
+
+        self.enforce_limits(from, token, &amounts, total_amount)?;
+
+        // The tokens are already here, so pay out of the contract's balance
+        let mut statuses = Vec::with_capacity(recipients.len());
+        for (&recipient, &amount) in recipients.iter().zip(&amounts) {
+            let status = if recipient == Address::ZERO {
+                FAILURE_INVALID_RECIPIENT
+            } else {
+                match self.token_transfer(token, recipient, amount) {
+                    Ok(_) => TRANSFER_OK,
+                    Err(code) => code,
+                }
+            };
+
+            self.log_transfer(recipient, amount, status, EVENTS_VERBOSE);
+            statuses.push(status);
+        }
+
+        // Return failed transfers if any
+        self.refund(token, from, unpaid_amount(&statuses, &amounts)?)?;
+
+        // Update statistics
+        let successes = U256::from(success_count(&statuses));
+        self.record_batch(from, successes)?;
+        self.log_batch(from, token, total_amount, &statuses, &[], EVENTS_VERBOSE);
+
+        Ok(IERC1363Receiver::onTransferReceivedCall::SELECTOR.into())
+    }
+
+    /// Commitment to pass to `create_raffle` for `seed`, `keccak256(abi.encode(sender, seed))`
+    pub fn raffle_commitment(&self, sender: Address, seed: B256) -> B256 {
+        keccak256((sender, seed).abi_encode())
+    }
+
+    /// Get the number of raffles created
+    pub fn raffle_count(&self) -> U256 {
+        self.raffle_count.get()
+    }
+
+    /// Get `(sender, pool, participantCount, prizes, drawBlock, status)` of a raffle
+    pub fn raffle_info(&self, raffle_id: U256) -> (Address, U256, U256, Vec<U256>, U256, u8) {
+        let raffle = self.raffles.getter(raffle_id);
+        let prizes = (0..raffle.prizes.len())
+            .map(|i| raffle.prizes.get(i).unwrap_or_default())
+            .collect();
+        (
+            raffle.sender.get(),
+            raffle.pool.get(),
+            U256::from(raffle.participants.len()),
+            prizes,
+            raffle.draw_block.get(),
+            raffle.status.get().to::<u8>(),
+        )
+    }
+
+    /// Fund a raffle paying `prizes[i]` to the `i`-th winner drawn from `participants` (at most
+    /// `MAX_RAFFLE_PARTICIPANTS`), and return its id. `commitment` is
+    /// `raffle_commitment(sender, seed)` for a secret seed that the sender reveals after
+    /// `RAFFLE_DRAW_DELAY` L2 blocks; excess value is refunded.
+    /// Each commitment can be used once, so a revealed seed never decides another raffle.
+    #[payable]
+    pub fn create_raffle(
+        &mut self,
+        participants: Vec<Address>,
+        prizes: Vec<U256>,
+        commitment: B256,
+    ) -> Result<U256, Error> {
+        if participants.is_empty() || prizes.is_empty() || commitment == B256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        if participants.len() > MAX_RAFFLE_PARTICIPANTS {
+            return Err(Error::TooManyParticipants(TooManyParticipants {
+                participants: U256::from(participants.len()),
+                max: U256::from(MAX_RAFFLE_PARTICIPANTS),
+            }));
+        }
+
+        if prizes.len() > participants.len() {
+            return Err(Error::TooManyWinners(TooManyWinners {
+                winners: U256::from(prizes.len()),
+                participants: U256::from(participants.len()),
+            }));
+        }
+
+        if let Some(&recipient) = participants.iter().find(|p| **p == Address::ZERO) {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient }));
+        }
+
+        let mut pool = U256::ZERO;
+        for prize in &prizes {
+            if *prize == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            pool = checked_add(pool, *prize)?;
+        }
+
+        let sender = self.vm().msg_sender();
+        let msg_value = self.vm().msg_value();
+        if msg_value < pool {
+            return Err(Error::InsufficientValue(InsufficientValue {
+                required: pool,
+                provided: msg_value,
+            }));
+        }
+
+        self.enforce_limits(sender, NATIVE_ETH, &prizes, pool)?;
+
+        if self.used_raffle_commitments.get(commitment) {
+            return Err(Error::RaffleCommitmentUsed(RaffleCommitmentUsed { commitment }));
+        }
+        self.used_raffle_commitments.insert(commitment, true);
+
+        let draw_block = checked_add(self.arb_block_number()?, U256::from(RAFFLE_DRAW_DELAY))?;
+        let raffle_id = checked_add(self.raffle_count.get(), U256::from(1))?;
+        self.raffle_count.set(raffle_id);
+
+        {
+            let mut raffle = self.raffles.setter(raffle_id);
+            raffle.sender.set(sender);
+            for &participant in &participants {
+                raffle.participants.push(participant);
+            }
+            for &prize in &prizes {
+                raffle.prizes.push(prize);
+            }
+            raffle.pool.set(pool);
+            raffle.commitment.set(commitment);
+            raffle.draw_block.set(draw_block);
+            raffle.status.set(U8::from(RAFFLE_OPEN));
+        }
+
+        self.reserve_funds(NATIVE_ETH, pool)?;
+
+        log(self.vm(), RaffleCreated {
+            raffleId: raffle_id,
+            sender,
+            pool,
+            participantCount: U256::from(participants.len()),
+            winnerCount: U256::from(prizes.len()),
+            drawBlock: draw_block,
+        });
+
+        // Return excess ETH if any
+        self.refund(NATIVE_ETH, sender, msg_value - pool)?;
+
+        Ok(raffle_id)
+    }
+
+    /// Reveal the seed of a raffle once its draw block is final and pay the winners drawn from
+    /// the seed mixed with the draw block's hash, returning `(batchId, winners, statuses,
+    /// refunded)`. Anyone holding the seed may reveal it within `RAFFLE_REVEAL_WINDOW` blocks;
+    /// prizes that cannot be delivered go back to the sender, or to their ledger balance.
+    pub fn reveal_raffle(
+        &mut self,
+        raffle_id: U256,
+        seed: B256,
+    ) -> Result<(U256, Vec<Address>, Vec<u8>, U256), Error> {
+        let (sender, commitment, draw_block, status) = {
+            let raffle = self.raffles.getter(raffle_id);
+            (
+                raffle.sender.get(),
+                raffle.commitment.get(),
+                raffle.draw_block.get(),
+                raffle.status.get().to::<u8>(),
+            )
+        };
+        if sender == Address::ZERO {
+            return Err(Error::UnknownRaffle(UnknownRaffle { raffleId: raffle_id }));
+        }
+        if status != RAFFLE_OPEN {
+            return Err(Error::RaffleNotOpen(RaffleNotOpen { raffleId: raffle_id, status }));
+        }
+        if self.raffle_commitment(sender, seed) != commitment {
+            return Err(Error::InvalidRaffleSeed(InvalidRaffleSeed { raffleId: raffle_id }));
+        }
+
+        let current = self.arb_block_number()?;
+        if current <= draw_block {
+            return Err(Error::RaffleNotDrawable(RaffleNotDrawable {
+                raffleId: raffle_id,
+                drawBlock: draw_block,
+            }));
+        }
+        if current > checked_add(draw_block, U256::from(RAFFLE_REVEAL_WINDOW))? {
+            return Err(Error::RaffleDrawExpired(RaffleDrawExpired {
+                raffleId: raffle_id,
+                drawBlock: draw_block,
+            }));
+        }
+        let block_hash = self.arb_block_hash(draw_block)?;
+
+        // Close the raffle before paying anyone
+        let (participants, prizes, pool) = {
+            let mut raffle = self.raffles.setter(raffle_id);
+            raffle.status.set(U8::from(RAFFLE_DRAWN));
+            let participants: Vec<Address> = (0..raffle.participants.len())
+                .map(|i| raffle.participants.get(i).unwrap_or_default())
+                .collect();
+            let prizes: Vec<U256> = (0..raffle.prizes.len())
+                .map(|i| raffle.prizes.get(i).unwrap_or_default())
+                .collect();
+            (participants, prizes, raffle.pool.get())
+        };
+        self.release_funds(NATIVE_ETH, pool)?;
+
+        let winners = draw_winners(seed, block_hash, participants, prizes.len());
+        log(self.vm(), RaffleDrawn {
+            raffleId: raffle_id,
+            seed,
+            blockHash: block_hash,
+            winners: winners.clone(),
+        });
+
+        // Pay the winners through the regular batch path
+        let (statuses, wrapped) = self.distribute_eth(&winners, &prizes, EVENTS_VERBOSE, 0);
+        let successes = U256::from(success_count(&statuses));
+        let batch_id = self.record_batch(sender, successes)?;
+        self.log_batch(sender, NATIVE_ETH, pool, &statuses, &wrapped, EVENTS_VERBOSE);
+
+        // Return undelivered prizes if any
+        let refunded = unpaid_amount(&statuses, &prizes)?;
+        self.refund(NATIVE_ETH, sender, refunded)?;
+
+        Ok((batch_id, winners, statuses, refunded))
+    }
+
+    /// Refund an open raffle to its sender, or to their ledger balance if the sender rejects
+    /// the ETH (sender only). Only allowed until the draw block, while no one can know the
+    /// winners yet; later the sender either reveals the seed or the raffle expires into
+    /// `settle_expired_raffle`.
+    pub fn cancel_raffle(&mut self, raffle_id: U256) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        let (sender, draw_block, pool, status) = {
+            let raffle = self.raffles.getter(raffle_id);
+            (
+                raffle.sender.get(),
+                raffle.draw_block.get(),
+                raffle.pool.get(),
+                raffle.status.get().to::<u8>(),
+            )
+        };
+        if sender == Address::ZERO {
+            return Err(Error::UnknownRaffle(UnknownRaffle { raffleId: raffle_id }));
+        }
+        if sender != caller {
+            return Err(Error::UnauthorizedRaffleSender(UnauthorizedRaffleSender {
+                raffleId: raffle_id,
+                caller,
+            }));
+        }
+        if status != RAFFLE_OPEN {
+            return Err(Error::RaffleNotOpen(RaffleNotOpen { raffleId: raffle_id, status }));
+        }
+
+        let current = self.arb_block_number()?;
+        if current > checked_add(draw_block, U256::from(RAFFLE_REVEAL_WINDOW))? {
+            return Err(Error::RaffleDrawExpired(RaffleDrawExpired {
+                raffleId: raffle_id,
+                drawBlock: draw_block,
+            }));
+        }
+        if current > draw_block {
+            return Err(Error::RaffleAwaitingReveal(RaffleAwaitingReveal {
+                raffleId: raffle_id,
+                drawBlock: draw_block,
+            }));
+        }
+
+        self.raffles.setter(raffle_id).status.set(U8::from(RAFFLE_CANCELLED));
+        self.release_funds(NATIVE_ETH, pool)?;
+        log(self.vm(), RaffleCancelled {
+            raffleId: raffle_id,
+            refundedAmount: pool,
+        });
+
+        self.refund(NATIVE_ETH, caller, pool)
+    }
+
+    /// Split the pool of a raffle whose seed was not revealed in time evenly between its
+    /// participants, credited to their ledger balances to claim with `withdraw`. Callable by
+    /// anyone, so a sender who dislikes the draw gains nothing by withholding the seed.
+    pub fn settle_expired_raffle(&mut self, raffle_id: U256) -> Result<(), Error> {
+        let (sender, draw_block, pool, status) = {
+            let raffle = self.raffles.getter(raffle_id);
+            (
+                raffle.sender.get(),
+                raffle.draw_block.get(),
+                raffle.pool.get(),
+                raffle.status.get().to::<u8>(),
+            )
+        };
+        if sender == Address::ZERO {
+            return Err(Error::UnknownRaffle(UnknownRaffle { raffleId: raffle_id }));
+        }
+        if status != RAFFLE_OPEN {
+            return Err(Error::RaffleNotOpen(RaffleNotOpen { raffleId: raffle_id, status }));
+        }
+
+        let current = self.arb_block_number()?;
+        if current <= checked_add(draw_block, U256::from(RAFFLE_REVEAL_WINDOW))? {
+            return Err(Error::RaffleAwaitingReveal(RaffleAwaitingReveal {
+                raffleId: raffle_id,
+                drawBlock: draw_block,
+            }));
+        }
+
+        let participants: Vec<Address> = {
+            let mut raffle = self.raffles.setter(raffle_id);
+            raffle.status.set(U8::from(RAFFLE_EXPIRED));
+            (0..raffle.participants.len())
+                .map(|i| raffle.participants.get(i).unwrap_or_default())
+                .collect()
+        };
+
+        // The pool stays reserved, it only moves to the participants' ledger balances.
+        // The first `pool % count` entries get one extra wei so nothing is left over.
+        let count = U256::from(participants.len());
+        let (share, remainder) = (pool / count, pool % count);
+        for (i, &participant) in participants.iter().enumerate() {
+            let amount = if U256::from(i) < remainder { share + U256::from(1) } else { share };
+            if amount == U256::ZERO {
+                continue;
+            }
+            self.credit_ledger(NATIVE_ETH, participant, amount)?;
+            log(self.vm(), LedgerCredit {
+                token: NATIVE_ETH,
+                from: sender,
+                to: participant,
+                amount,
+            });
+        }
+
+        log(self.vm(), RaffleExpired {
+            raffleId: raffle_id,
+            pool,
+            participantCount: count,
+        });
+        Ok(())
+    }
+
+    /// Check whether plain ETH transfers to the contract are rejected
+    pub fn reject_unsolicited_eth(&self) -> bool {
+        self.reject_unsolicited_eth.get()
+    }
+
+    /// Reject or accept plain ETH transfers to the contract (owner only).
+    /// Payable functions such as `deposit_eth` and `fund_batch` are not affected.
+    pub fn set_reject_unsolicited_eth(&mut self, rejected: bool) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        self.reject_unsolicited_eth.set(rejected);
+        log(self.vm(), UnsolicitedEthPolicyUpdated { rejected });
+
+        Ok(())
+    }
+
+    /// Check whether an account may use `multicall` besides the owner
+    pub fn is_multicall_operator(&self, account: Address) -> bool {
+        self.multicall_operators.get(account)
+    }
+
+    /// Grant or revoke the multicall operator role (owner only)
+    pub fn set_multicall_operator(&mut self, operator: Address, allowed: bool) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        self.multicall_operators.insert(operator, allowed);
+        log(self.vm(), MulticallOperatorUpdated { operator, allowed });
+
+        Ok(())
+    }
+
+    /// Check whether `multicall` may call `target`
+    pub fn is_multicall_target(&self, target: Address) -> bool {
+        self.multicall_targets.get(target)
+    }
+
+    /// Allow or forbid `multicall` to call `target` (owner only).
+    ///
+    /// Senders approve this contract for batch sends, schedules and collection, so a call to a
+    /// token or to any contract that spends allowances (such as a router) can move their funds.
+    /// Only allow contracts like vesting or payroll that cannot. The contract itself and tokens
+    /// it holds for users are always refused.
+    pub fn set_multicall_target(&mut self, target: Address, allowed: bool) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        let refused = target == self.vm().contract_address()
+            || target == Address::ZERO
+            || self.reserved_tokens.get(target) > U256::ZERO;
+        if allowed && refused {
+            return Err(Error::ForbiddenCall(ForbiddenCall { index: U256::ZERO, target }));
+        }
+
+        self.multicall_targets.insert(target, allowed);
+        log(self.vm(), MulticallTargetUpdated { target, allowed });
+
+        Ok(())
+    }
+
+    /// Execute calls as (target, value, calldata, allow_failure) to targets allowed with
+    /// `set_multicall_target`, forwarding ETH from `msg.value` only. Returns (success, return
+    /// data) per call and refunds unspent value. A failing call without `allow_failure` reverts
+    /// the whole multicall (owner or operator only).
+    #[payable]
+    pub fn multicall(
+        &mut self,
+        calls: Vec<(Address, U256, Bytes, bool)>,
+    ) -> Result<Vec<(bool, Bytes)>, Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() && !self.multicall_operators.get(caller) {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        let msg_value = self.vm().msg_value();
+        let contract = self.vm().contract_address();
+
+        // Check that the forwarded value is covered before making any call
+        let mut total_value = U256::ZERO;
+        for (index, (target, value, _, _)) in calls.iter().enumerate() {
+            if !self.multicall_targets.get(*target) {
+                return Err(Error::ForbiddenCall(ForbiddenCall {
+                    index: U256::from(index),
+                    target: *target,
+                }));
+            }
+            total_value = checked_add(total_value, *value)?;
+        }
+
+        if msg_value < total_value {
+            return Err(Error::InsufficientValue(InsufficientValue {
+                required: total_value,
+                provided: msg_value,
+            }));
+        }
+
+        let mut results = Vec::with_capacity(calls.len());
+        let mut spent = U256::ZERO;
+        let mut successful_calls = 0u32;
+        for (index, (target, value, calldata, allow_failure)) in calls.into_iter().enumerate() {
+            let config = Call::new().value(value);
+            let (success, return_data) = match self.vm().call(&config, target, &calldata) {
+                Ok(data) => (true, data),
+                Err(err) => (false, err.into()),
+            };
+
+            if !success && !allow_failure {
+                return Err(Error::MulticallFailed(MulticallFailed {
+                    index: U256::from(index),
+                    returnData: return_data.into(),
+                }));
+            }
+
+            // Tokens held for users must still be fully backed after the call. This is a backstop:
+            // approvals granted by a call only show up later, which is why targets are allowlisted.
+            let reserved = self.reserved_tokens.get(target);
+            if reserved > U256::ZERO && self.token_balance(target, contract)? < reserved {
+                return Err(Error::ReservedFundsTouched(ReservedFundsTouched { asset: target }));
+            }
+
+            if success {
+                successful_calls += 1;
+                spent = checked_add(spent, value)?;
+            }
+
+            log(self.vm(), MulticallCallResult {
+                index: U256::from(index),
+                target,
+                value,
+                success,
+            });
+
+            results.push((success, Bytes::from(return_data)));
+        }
+
+        log(self.vm(), MulticallExecuted {
+            caller,
+            callCount: U256::from(results.len()),
+            successCount: U256::from(successful_calls),
+        });
+
+        // Return unspent ETH if any
+        self.refund(NATIVE_ETH, caller, msg_value - spent)?;
+
+        Ok(results)
+    }
+
+    /// Get the UUPS identifier, which is the ERC-1967 implementation slot
+    /// Fails through a proxy so the proxy is never taken for an implementation
+    #[selector(name = "proxiableUUID")]
+    pub fn proxiable_uuid(&self) -> Result<B256, Error> {
+        uups::not_delegated(self)?;
+        Ok(uups::IMPLEMENTATION_SLOT)
+    }
+
+    /// Upgrade the proxy to a new implementation and optionally call it (owner only, through a
+    /// proxy)
+    #[payable]
+    pub fn upgrade_to_and_call(
+        &mut self,
+        new_implementation: Address,
+        data: Bytes,
+    ) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+
+        uups::upgrade_to_and_call(self, new_implementation, &data)?;
+        Ok(())
+    }
+
+    /// Emergency withdraw function (owner only)
+    pub fn emergency_withdraw(&mut self) {
+        let caller = self.vm().msg_sender();
+        let owner = self.owner.get();
+        
+        if caller != owner {
+            return;
+        }
+        
+        // ETH held for stored batches and ledger balances is not swept
         let balance = self.vm().balance(self.vm().contract_address());
-        if balance > U256::ZERO {
-            let _ = self.vm().transfer_eth(owner, balance);
+        let available = balance.saturating_sub(self.reserved_eth.get());
+        if available > U256::ZERO {
+            let _ = self.vm().transfer_eth(owner, available);
+        }
+    }
+
+    /// Recover ERC20 tokens sent to the contract by mistake (owner only).
+    /// Tokens reserved for stored batches and ledger balances cannot be recovered.
+    pub fn recover_erc20(
+        &mut self,
+        token: Address,
+        to: Address,
+        amount: U256,
+    ) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: to }));
+        }
+        if token == NATIVE_ETH || amount == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        // Only the balance above the reserved amount is stuck
+        let balance = self.token_balance(token, self.vm().contract_address())?;
+        let available = balance.saturating_sub(self.reserved_tokens.get(token));
+        if amount > available {
+            return Err(Error::InsufficientRecoverableBalance(InsufficientRecoverableBalance {
+                token,
+                available,
+                required: amount,
+            }));
+        }
+
+        if self.token_transfer(token, to, amount).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token }));
+        }
+
+        log(self.vm(), ERC20Recovered { token, to, amount });
+        Ok(())
+    }
+
+    /// Recover an ERC721 token sent to the contract by mistake (owner only)
+    pub fn recover_erc721(
+        &mut self,
+        token: Address,
+        to: Address,
+        token_id: U256,
+    ) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: to }));
+        }
+
+        // The contract never holds NFTs on behalf of users, so nothing is reserved
+        let call = IERC721::safeTransferFromCall {
+            from: self.vm().contract_address(),
+            to,
+            tokenId: token_id,
+        };
+        if self.call_token(token, &call.abi_encode()).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token }));
+        }
+
+        log(self.vm(), ERC721Recovered {
+            token,
+            to,
+            tokenId: token_id,
+        });
+        Ok(())
+    }
+
+    /// Recover ERC1155 tokens sent to the contract by mistake (owner only)
+    pub fn recover_erc1155(
+        &mut self,
+        token: Address,
+        to: Address,
+        id: U256,
+        amount: U256,
+    ) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        if caller != self.owner.get() {
+            return Err(Error::UnauthorizedAccount(UnauthorizedAccount { account: caller }));
+        }
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: to }));
+        }
+        if amount == U256::ZERO {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let call = IERC1155::safeTransferFromCall {
+            from: self.vm().contract_address(),
+            to,
+            id,
+            value: amount,
+            data: Default::default(),
+        };
+        if self.call_token(token, &call.abi_encode()).is_err() {
+            return Err(Error::TokenTransferFailed(TokenTransferFailed { token }));
+        }
+
+        log(self.vm(), ERC1155Recovered { token, to, id, amount });
+        Ok(())
+    }
+
+    /// Transfer ownership to a new owner (owner only)
+    pub fn transfer_ownership(&mut self, new_owner: Address) {
+        let caller = self.vm().msg_sender();
+        let owner = self.owner.get();
+        
+        if caller != owner || new_owner == Address::ZERO {
+            return;
+        }
+        
+        self.owner.set(new_owner);
+    }
+
+    /// Get estimated L2 gas for a batch ETH transfer to `recipient_count` new accounts,
+    /// the most expensive kind of recipient. Use `estimate_batch_eth_cost` to price a batch.
+    pub fn estimate_batch_eth_gas(&self, recipient_count: U256) -> U256 {
+        let per_recipient = Self::eth_transfer_gas(false, true) + 64 * CALLDATA_BYTE_GAS;
+        U256::from(per_recipient)
+            .saturating_mul(recipient_count)
+            .saturating_add(U256::from(Self::batch_overhead_gas() + calldata_gas(&[0; 128])))
+    }
+
+    /// Get estimated L2 gas for a batch token transfer to `recipient_count` accounts without a
+    /// balance. Use `estimate_batch_token_cost` to price a batch.
+    pub fn estimate_batch_token_gas(&self, recipient_count: U256) -> U256 {
+        let per_recipient = Self::token_transfer_gas(true) + 64 * CALLDATA_BYTE_GAS;
+        let overhead = Self::batch_overhead_gas() + Self::token_overhead_gas();
+        U256::from(per_recipient)
+            .saturating_mul(recipient_count)
+            .saturating_add(U256::from(overhead + calldata_gas(&[0; 160])))
+    }
+
+    /// Price `batch_send_eth(recipients, amounts)` as `(l2Gas, l1Fee, totalCost)`, fees in wei.
+    /// Each recipient is priced from its state: new accounts cost more and contracts get an
+    /// allowance for their receive logic.
+    pub fn estimate_batch_eth_cost(
+        &self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<(U256, U256, U256), Error> {
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+        let mut l2_gas = Self::batch_overhead_gas();
+        for recipient in &recipients {
+            let is_contract = self.vm().code_size(*recipient) > 0;
+            let is_new = !is_contract && self.vm().balance(*recipient) == U256::ZERO;
+            l2_gas = l2_gas.saturating_add(Self::eth_transfer_gas(is_contract, is_new));
+        }
+        self.price_batch(l2_gas, &(recipients, amounts).abi_encode_params())
+    }
+
+    /// Price `batch_send_token(token, recipients, amounts)` as `(l2Gas, l1Fee, totalCost)`,
+    /// fees in wei. Recipients without a token balance cost a fresh storage slot.
+    pub fn estimate_batch_token_cost(
+        &self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<(U256, U256, U256), Error> {
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+        let mut l2_gas = Self::batch_overhead_gas() + Self::token_overhead_gas();
+        for recipient in &recipients {
+            let is_new = self.token_balance(token, *recipient)? == U256::ZERO;
+            l2_gas = l2_gas.saturating_add(Self::token_transfer_gas(is_new));
+        }
+        self.price_batch(l2_gas, &(token, recipients, amounts).abi_encode_params())
+    }
+
+    /// Allow contract to receive ETH, kept for callers of the old deposit method
+    #[payable]
+    pub fn receive_ether(&mut self) -> Result<(), Error> {
+        self.accept_eth()
+    }
+
+    /// Handle plain ETH transfers
+    #[receive]
+    #[payable]
+    pub fn receive(&mut self) -> Result<(), Vec<u8>> {
+        Ok(self.accept_eth()?)
+    }
+
+    /// Reject calls to unknown functions, with or without ETH, so a mistyped payable call
+    /// reverts instead of being kept as a deposit. Plain transfers go to `receive`.
+    #[fallback]
+    #[payable]
+    pub fn fallback(&mut self, calldata: &[u8]) -> ArbResult {
+        let mut selector = [0u8; 4];
+        let len = calldata.len().min(4);
+        selector[..len].copy_from_slice(&calldata[..len]);
+        Err(Error::UnknownFunction(UnknownFunction {
+            selector: selector.into(),
+        })
+        .into())
+    }
+}
+
+impl MultiSender {
+    /// Accept unsolicited ETH and emit `Deposit`, unless the owner rejects it.
+    /// Plain ETH is not credited to the ledger and can be swept by `emergency_withdraw`.
+    fn accept_eth(&mut self) -> Result<(), Error> {
+        let sender = self.vm().msg_sender();
+        let amount = self.vm().msg_value();
+        if self.reject_unsolicited_eth.get() {
+            return Err(Error::UnsolicitedEthRejected(UnsolicitedEthRejected { sender, amount }));
+        }
+
+        log(self.vm(), Deposit { sender, amount });
+        Ok(())
+    }
+
+    /// Check a batch against the sender's limits for `asset` and count it for the day
+    fn enforce_limits(
+        &mut self,
+        sender: Address,
+        asset: Address,
+        amounts: &[U256],
+        total_amount: U256,
+    ) -> Result<(), Error> {
+        let (max_amount, max_total, max_batches_per_day) = self.sender_limits(sender, asset);
+
+        if max_amount != U256::ZERO {
+            if let Some(amount) = amounts.iter().find(|amount| **amount > max_amount) {
+                return Err(Error::AmountAboveLimit(AmountAboveLimit {
+                    asset,
+                    amount: *amount,
+                    limit: max_amount,
+                }));
+            }
+        }
+
+        if max_total != U256::ZERO && total_amount > max_total {
+            return Err(Error::BatchTotalAboveLimit(BatchTotalAboveLimit {
+                asset,
+                total: total_amount,
+                limit: max_total,
+            }));
+        }
+
+        if max_batches_per_day == U256::ZERO {
+            return Ok(());
+        }
+
+        let day = U256::from(self.vm().block_timestamp() / LIMIT_DAY);
+        let mut outer = self.sender_limits.setter(sender);
+        let mut limits = outer.setter(asset);
+        let day_batches = if limits.day.get() == day {
+            limits.day_batches.get()
+        } else {
+            U256::ZERO
+        };
+        if day_batches >= max_batches_per_day {
+            return Err(Error::DailyBatchLimitReached(DailyBatchLimitReached {
+                asset,
+                limit: max_batches_per_day,
+            }));
+        }
+        limits.day.set(day);
+        limits.day_batches.set(checked_add(day_batches, U256::from(1))?);
+        Ok(())
+    }
+
+    /// Start one L2-to-L1 withdrawal per recipient, claiming `batch_key` unless it is zero
+    fn withdraw_to_l1(
+        &mut self,
+        l1_recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        batch_key: B256,
+    ) -> Result<Vec<U256>, Error> {
+        // Validate input arrays
+        if l1_recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if l1_recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+        let msg_value = self.vm().msg_value();
+
+        // Calculate total amount needed
+        let mut total_amount = U256::ZERO;
+        for (recipient, amount) in l1_recipients.iter().zip(&amounts) {
+            if *recipient == Address::ZERO {
+                return Err(Error::InvalidRecipient(InvalidRecipient { recipient: *recipient }));
+            }
+            if *amount == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, *amount)?;
+        }
+
+        // Check if sent value covers total amount
+        if msg_value < total_amount {
+            return Err(Error::InsufficientValue(InsufficientValue {
+                required: total_amount,
+                provided: msg_value,
+            }));
+        }
+
+        self.enforce_limits(sender, NATIVE_ETH, &amounts, total_amount)?;
+        self.claim_batch_key(batch_key)?;
+
+        // Start one L2-to-L1 message per recipient
+        let mut message_ids = Vec::with_capacity(l1_recipients.len());
+        for (&l1_recipient, &amount) in l1_recipients.iter().zip(&amounts) {
+            let call = IArbSys::withdrawEthCall { destination: l1_recipient };
+            let config = Call::new().value(amount);
+            let message_id = self
+                .vm()
+                .call(&config, ARB_SYS, &call.abi_encode())
+                .ok()
+                .and_then(|result| U256::abi_decode(&result, true).ok())
+                .ok_or(Error::L1WithdrawalFailed(L1WithdrawalFailed {
+                    l1Recipient: l1_recipient,
+                }))?;
+
+            log(self.vm(), L1WithdrawalInitiated {
+                l1Recipient: l1_recipient,
+                amount,
+                messageId: message_id,
+            });
+            message_ids.push(message_id);
+        }
+
+        // Update statistics
+        self.record_batch(sender, U256::from(message_ids.len()))?;
+
+        log(self.vm(), BatchL1Withdrawal {
+            sender,
+            totalAmount: total_amount,
+            recipientCount: U256::from(message_ids.len()),
+        });
+
+        // Return excess ETH if any
+        self.refund(NATIVE_ETH, sender, msg_value - total_amount)?;
+
+        Ok(message_ids)
+    }
+
+    /// Validate and append recipients to a stored batch, claiming `batch_key` unless it is zero
+    fn append_to_batch(
+        &mut self,
+        batch_id: U256,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        batch_key: B256,
+    ) -> Result<(), Error> {
+        // Validate input arrays
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+
+        let mut added_amount = U256::ZERO;
+        for (i, &recipient) in recipients.iter().enumerate() {
+            if recipient == Address::ZERO {
+                return Err(Error::InvalidRecipient(InvalidRecipient { recipient }));
+            }
+            if amounts[i] == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            added_amount = checked_add(added_amount, amounts[i])?;
+        }
+
+        self.claim_batch_key(batch_key)?;
+
+        let (total_amount, item_count) = {
+            let mut batch = self.stored_batches.setter(batch_id);
+            let batch_sender = batch.sender.get();
+            if batch_sender == Address::ZERO {
+                batch.sender.set(sender);
+            } else if batch_sender != sender {
+                return Err(Error::UnauthorizedBatchSender(UnauthorizedBatchSender {
+                    batchId: batch_id,
+                    caller: sender,
+                }));
+            }
+
+            if batch.closed.get() {
+                return Err(Error::BatchClosed(BatchClosed { batchId: batch_id }));
+            }
+
+            if batch.funded.get() {
+                return Err(Error::BatchAlreadyFunded(BatchAlreadyFunded { batchId: batch_id }));
+            }
+
+            let total_amount = checked_add(batch.total_amount.get(), added_amount)?;
+            for (i, &recipient) in recipients.iter().enumerate() {
+                batch.recipients.push(recipient);
+                batch.amounts.push(amounts[i]);
+            }
+            batch.total_amount.set(total_amount);
+            (total_amount, U256::from(batch.recipients.len()))
+        };
+
+        log(self.vm(), StoredBatchAppended {
+            batchId: batch_id,
+            sender,
+            itemCount: item_count,
+            totalAmount: total_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Mark `batch_key` as used by the caller, failing if it already was.
+    /// The mark is reverted with the batch, so a failed batch can be retried with the same key.
+    fn claim_batch_key(&mut self, batch_key: B256) -> Result<(), Error> {
+        if batch_key == B256::ZERO {
+            return Ok(());
+        }
+
+        let sender = self.vm().msg_sender();
+        if self.is_batch_executed(sender, batch_key) {
+            return Err(Error::BatchAlreadyExecuted(BatchAlreadyExecuted {
+                sender,
+                batchKey: batch_key,
+            }));
+        }
+        self.executed_batch_keys.setter(sender).insert(batch_key, true);
+        Ok(())
+    }
+
+    /// Add a finished batch to the contract statistics, returning its batch id
+    /// (the new `total_transactions`)
+    fn record_batch(&mut self, sender: Address, successful_transfers: U256) -> Result<U256, Error> {
+        let current_total_tx = self.total_transactions.get();
+        let batch_id = checked_add(current_total_tx, U256::from(1))?;
+        self.total_transactions.set(batch_id);
+
+        let current_total_recipients = self.total_recipients.get();
+        self.total_recipients.set(checked_add(current_total_recipients, successful_transfers)?);
+
+        let current_user_count = self.user_transaction_count.get(sender);
+        let user_count = checked_add(current_user_count, U256::from(1))?;
+        self.user_transaction_count.insert(sender, user_count);
+        Ok(batch_id)
+    }
+
+    /// Ensure `caller` created the stored batch `batch_id`
+    fn check_batch_sender(
+        batch_id: U256,
+        batch_sender: Address,
+        caller: Address,
+    ) -> Result<(), Error> {
+        if batch_sender == Address::ZERO {
+            return Err(Error::UnknownBatch(UnknownBatch { batchId: batch_id }));
+        }
+        if batch_sender != caller {
+            return Err(Error::UnauthorizedBatchSender(UnauthorizedBatchSender {
+                batchId: batch_id,
+                caller,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Track funds the contract holds on behalf of users (`NATIVE_ETH` for ETH)
+    fn reserve_funds(&mut self, asset: Address, amount: U256) -> Result<(), Error> {
+        if asset == NATIVE_ETH {
+            let reserved = self.reserved_eth.get();
+            self.reserved_eth.set(checked_add(reserved, amount)?);
+        } else {
+            let reserved = self.reserved_tokens.get(asset);
+            self.reserved_tokens.insert(asset, checked_add(reserved, amount)?);
+        }
+        Ok(())
+    }
+
+    /// Stop tracking funds that left the contract or were returned to their owner.
+    /// Releasing more than is reserved is an accounting bug and fails.
+    fn release_funds(&mut self, asset: Address, amount: U256) -> Result<(), Error> {
+        if asset == NATIVE_ETH {
+            let reserved = self.reserved_eth.get();
+            self.reserved_eth.set(checked_sub(reserved, amount)?);
+        } else {
+            let reserved = self.reserved_tokens.get(asset);
+            self.reserved_tokens.insert(asset, checked_sub(reserved, amount)?);
+        }
+        Ok(())
+    }
+
+    /// Increase a ledger balance
+    fn credit_ledger(
+        &mut self,
+        token: Address,
+        account: Address,
+        amount: U256,
+    ) -> Result<(), Error> {
+        let mut balances = self.ledger_balances.setter(token);
+        let balance = balances.get(account);
+        balances.insert(account, checked_add(balance, amount)?);
+        Ok(())
+    }
+
+    /// Decrease a ledger balance, failing if it does not cover `amount`
+    fn debit_ledger(&mut self, token: Address, account: Address, amount: U256) -> Result<(), Error> {
+        let mut balances = self.ledger_balances.setter(token);
+        let balance = balances.get(account);
+        if balance < amount {
+            return Err(Error::InsufficientLedgerBalance(InsufficientLedgerBalance {
+                token,
+                available: balance,
+                required: amount,
+            }));
+        }
+        balances.insert(account, balance - amount);
+        Ok(())
+    }
+
+    /// Ensure the caller created schedule `schedule_id`
+    fn check_schedule_payer(&self, schedule_id: U256) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        let payer = self.schedules.getter(schedule_id).payer.get();
+        if payer == Address::ZERO {
+            return Err(Error::UnknownSchedule(UnknownSchedule { scheduleId: schedule_id }));
+        }
+        if payer != caller {
+            return Err(Error::UnauthorizedSchedulePayer(UnauthorizedSchedulePayer {
+                scheduleId: schedule_id,
+                caller,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Ensure invoice `invoice_id` is open and addressed to the caller, returning
+    /// `(issuer, asset, amount)`
+    fn check_invoice_payer(&self, invoice_id: U256) -> Result<(Address, Address, U256), Error> {
+        let caller = self.vm().msg_sender();
+        let invoice = self.invoices.getter(invoice_id);
+        let issuer = invoice.issuer.get();
+        if issuer == Address::ZERO {
+            return Err(Error::UnknownInvoice(UnknownInvoice { invoiceId: invoice_id }));
+        }
+        if invoice.payer.get() != caller {
+            return Err(Error::UnauthorizedInvoicePayer(UnauthorizedInvoicePayer {
+                invoiceId: invoice_id,
+                caller,
+            }));
+        }
+        let status = invoice.status.get().to::<u8>();
+        if status != INVOICE_OPEN {
+            return Err(Error::InvoiceNotOpen(InvoiceNotOpen { invoiceId: invoice_id, status }));
+        }
+        Ok((issuer, invoice.asset.get(), invoice.amount.get()))
+    }
+
+    /// Get the members and default amounts of group `group_id` to send a batch to.
+    /// Groups that were never created are unknown; existing groups without members are empty.
+    fn group_batch(&self, group_id: U256) -> Result<(Vec<Address>, Vec<U256>), Error> {
+        if self.groups.getter(group_id).owner.get() == Address::ZERO {
+            return Err(Error::UnknownGroup(UnknownGroup { groupId: group_id }));
+        }
+
+        let (recipients, amounts) = self.group_members(group_id);
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+        Ok((recipients, amounts))
+    }
+
+    /// Ensure the caller owns group `group_id`
+    fn check_group_owner(&self, group_id: U256) -> Result<(), Error> {
+        let caller = self.vm().msg_sender();
+        let owner = self.groups.getter(group_id).owner.get();
+        if owner == Address::ZERO {
+            return Err(Error::UnknownGroup(UnknownGroup { groupId: group_id }));
+        }
+        if owner != caller {
+            return Err(Error::UnauthorizedGroupOwner(UnauthorizedGroupOwner {
+                groupId: group_id,
+                caller,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Gas of a batch before any recipient: the intrinsic cost, the statistics updates and the
+    /// batch event
+    fn batch_overhead_gas() -> u64 {
+        TX_BASE_GAS + 3 * (COLD_SLOT_GAS + SLOT_RESET_GAS) + log_gas(3, 64)
+    }
+
+    /// Extra gas of a token batch: the first access to the token, the sender balance and the
+    /// allowance
+    fn token_overhead_gas() -> u64 {
+        COLD_ACCOUNT_GAS - WARM_ACCOUNT_GAS + 2 * COLD_SLOT_GAS
+    }
+
+    /// Gas of paying one ETH recipient and logging `TransferSuccess`
+    fn eth_transfer_gas(is_contract: bool, is_new: bool) -> u64 {
+        let mut gas = COLD_ACCOUNT_GAS + CALL_VALUE_GAS + log_gas(2, 32);
+        if is_contract {
+            gas += RECEIVE_HOOK_GAS;
+        }
+        if is_new {
+            gas += NEW_ACCOUNT_GAS;
+        }
+        gas
+    }
+
+    /// Gas of one `transferFrom` and `TransferSuccess`. The token updates the sender balance,
+    /// the allowance and the recipient balance, then logs `Transfer`.
+    fn token_transfer_gas(is_new: bool) -> u64 {
+        let recipient_slot = if is_new { SLOT_SET_GAS } else { SLOT_RESET_GAS };
+        WARM_ACCOUNT_GAS
+            + 2 * SLOT_RESET_GAS
+            + COLD_SLOT_GAS
+            + recipient_slot
+            + log_gas(3, 32)
+            + log_gas(2, 32)
+    }
+
+    /// Price a batch of `execution_gas` with the current ArbGasInfo rates. The L2 gas adds the
+    /// intrinsic calldata gas of `params`; the L1 fee is the separate poster fee for the same
+    /// bytes, counted on their uncompressed size so it is an upper bound.
+    fn price_batch(
+        &self,
+        execution_gas: u64,
+        params: &[u8],
+    ) -> Result<(U256, U256, U256), Error> {
+        let call = IArbGasInfo::getPricesInWeiCall {};
+        let prices = self
+            .vm()
+            .static_call(&Call::new(), ARB_GAS_INFO, &call.abi_encode())
+            .ok()
+            .and_then(|result| {
+                IArbGasInfo::getPricesInWeiCall::abi_decode_returns(&result, true).ok()
+            })
+            .ok_or(Error::GasPriceUnavailable(GasPriceUnavailable {}))?;
+
+        let l2_gas = U256::from(execution_gas.saturating_add(calldata_gas(params)));
+        let l1_fee = prices
+            .perL1CalldataByte
+            .saturating_mul(U256::from(4 + params.len()))
+            .saturating_add(prices.perL2Tx);
+        let total_cost = l2_gas.saturating_mul(prices.perArbGasTotal).saturating_add(l1_fee);
+        Ok((l2_gas, l1_fee, total_cost))
+    }
+
+    /// Validate and pay an ETH batch, stopping once at most `gas_reserve` gas is left
+    /// (never for zero). The statuses only cover the recipients that were processed.
+    fn send_eth_batch(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        event_mode: u8,
+        gas_reserve: u64,
+        batch_key: B256,
+    ) -> Result<BatchResult, Error> {
+        // Validate input arrays
+        if event_mode > EVENTS_COMPACT {
+            return Err(Error::InvalidEventMode(InvalidEventMode { mode: event_mode }));
+        }
+
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        let sender = self.vm().msg_sender();
+        let msg_value = self.vm().msg_value();
+        
+        // Calculate total amount needed
+        let mut total_amount = U256::ZERO;
+        for amount in &amounts {
+            if *amount == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, *amount)?;
+        }
+
+        // Check if sent value covers total amount
+        if msg_value < total_amount {
+            return Err(Error::InsufficientValue(InsufficientValue {
+                required: total_amount,
+                provided: msg_value,
+            }));
+        }
+
+        self.enforce_limits(sender, NATIVE_ETH, &amounts, total_amount)?;
+        self.claim_batch_key(batch_key)?;
+
+        // Perform transfers
+        let (statuses, wrapped) =
+            self.distribute_eth(&recipients, &amounts, event_mode, gas_reserve);
+        let processed_total = processed_amount(&statuses, &amounts)?;
+
+        // Update statistics
+        let successes = U256::from(success_count(&statuses));
+        let batch_id = self.record_batch(sender, successes)?;
+
+        // Emit batch transfer event
+        self.log_batch(sender, NATIVE_ETH, processed_total, &statuses, &wrapped, event_mode);
+
+        // Return excess ETH, unprocessed and failed transfers if any
+        let unpaid = unpaid_amount(&statuses, &amounts)?;
+        let refunded = checked_add(msg_value - processed_total, unpaid)?;
+        self.refund(NATIVE_ETH, sender, refunded)?;
+
+        Ok((batch_id, successes, statuses, refunded))
+    }
+
+    /// Validate and pull a token batch, stopping once at most `gas_reserve` gas is left
+    /// (never for zero). The statuses only cover the recipients that were processed.
+    fn send_token_batch(
+        &mut self,
+        token: Address,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        event_mode: u8,
+        gas_reserve: u64,
+        batch_key: B256,
+    ) -> Result<BatchResult, Error> {
+        // Validate input arrays
+        if event_mode > EVENTS_COMPACT {
+            return Err(Error::InvalidEventMode(InvalidEventMode { mode: event_mode }));
+        }
+
+        if recipients.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        if token == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: token }));
+        }
+
+        let sender = self.vm().msg_sender();
+
+        // Calculate total amount needed
+        let mut total_amount = U256::ZERO;
+        for amount in &amounts {
+            if *amount == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, *amount)?;
+        }
+
+        self.enforce_limits(sender, token, &amounts, total_amount)?;
+        self.claim_batch_key(batch_key)?;
+
+        // Perform transfers using transferFrom
+        let statuses =
+            self.distribute_token(token, sender, &recipients, &amounts, event_mode, gas_reserve);
+
+        // Update statistics
+        let successes = U256::from(success_count(&statuses));
+        let batch_id = self.record_batch(sender, successes)?;
+
+        // Emit batch transfer event
+        let processed_total = processed_amount(&statuses, &amounts)?;
+        self.log_batch(sender, token, processed_total, &statuses, &[], event_mode);
+
+        // Failed and unprocessed pulls never leave the sender, so nothing is refunded
+        Ok((batch_id, successes, statuses, U256::ZERO))
+    }
+
+    /// Log `BatchStoppedEarly` when a partial batch stopped before `count` recipients and
+    /// return the index to resume from
+    fn log_partial_batch(&mut self, token: Address, processed: usize, count: usize) -> U256 {
+        let next_index = U256::from(processed);
+        if processed < count {
+            let sender = self.vm().msg_sender();
+            log(self.vm(), BatchStoppedEarly {
+                sender,
+                token,
+                nextIndex: next_index,
+                recipientCount: U256::from(count),
+            });
+        }
+        next_index
+    }
+
+    /// Send ETH to each recipient, returning a `TRANSFER_OK` or failure code per processed
+    /// recipient and a bitmap, laid out like `success_bitmap`, of the recipients paid in WETH.
+    /// Stops once at most `gas_reserve` gas is left, never for zero.
+    fn distribute_eth(
+        &mut self,
+        recipients: &[Address],
+        amounts: &[U256],
+        event_mode: u8,
+        gas_reserve: u64,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut statuses = Vec::with_capacity(recipients.len());
+        let mut wrapped_bitmap = vec![0u8; recipients.len().div_ceil(8)];
+        for (i, &recipient) in recipients.iter().enumerate() {
+            if self.gas_exhausted(gas_reserve) {
+                break;
+            }
+
+            // Attempt transfer, wrapping to WETH if the recipient rejects ETH
+            let mut wrapped = false;
+            let status = if recipient == Address::ZERO {
+                FAILURE_INVALID_RECIPIENT
+            } else if self.send_eth(recipient, amounts[i], gas_reserve) {
+                TRANSFER_OK
+            } else if self.wrap_and_transfer(recipient, amounts[i], gas_reserve) {
+                wrapped = true;
+                TRANSFER_OK
+            } else {
+                FAILURE_ETH_TRANSFER
+            };
+
+            if wrapped {
+                wrapped_bitmap[i / 8] |= 1 << (i % 8);
+            }
+            if wrapped && event_mode == EVENTS_VERBOSE {
+                log(self.vm(), WrappedTransferSuccess {
+                    recipient,
+                    amount: amounts[i],
+                });
+            } else {
+                self.log_transfer(recipient, amounts[i], status, event_mode);
+            }
+            statuses.push(status);
+        }
+        wrapped_bitmap.truncate(statuses.len().div_ceil(8));
+        (statuses, wrapped_bitmap)
+    }
+
+    /// Pull tokens from `sender` to each recipient, returning a `TRANSFER_OK` or failure code
+    /// per processed recipient. Stops once at most `gas_reserve` gas is left, never for zero.
+    fn distribute_token(
+        &mut self,
+        token: Address,
+        sender: Address,
+        recipients: &[Address],
+        amounts: &[U256],
+        event_mode: u8,
+        gas_reserve: u64,
+    ) -> Vec<u8> {
+        let mut statuses = Vec::with_capacity(recipients.len());
+        for (i, &recipient) in recipients.iter().enumerate() {
+            if self.gas_exhausted(gas_reserve) {
+                break;
+            }
+
+            let status = if recipient == Address::ZERO {
+                FAILURE_INVALID_RECIPIENT
+            } else {
+                // Forward only the gas above the reserve, like `send_eth`
+                let call = IERC20::transferFromCall {
+                    from: sender,
+                    to: recipient,
+                    amount: amounts[i],
+                };
+                let gas = self.call_gas(gas_reserve);
+                match self.call_token_with_gas(token, &call.abi_encode(), gas) {
+                    Ok(_) => TRANSFER_OK,
+                    Err(code) => code,
+                }
+            };
+
+            self.log_transfer(recipient, amounts[i], status, event_mode);
+            statuses.push(status);
+        }
+        statuses
+    }
+
+    /// Validate and pull a collection batch into `to` on behalf of the caller
+    fn collect_token_batch(
+        &mut self,
+        token: Address,
+        payers: &[Address],
+        amounts: &[U256],
+        to: Address,
+    ) -> Result<BatchResult, Error> {
+        // Validate input arrays
+        if payers.len() != amounts.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if payers.is_empty() {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        if token == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: token }));
+        }
+
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: to }));
+        }
+
+        let collector = self.vm().msg_sender();
+
+        let mut total_amount = U256::ZERO;
+        for amount in amounts {
+            if *amount == U256::ZERO {
+                return Err(Error::InvalidAmount(InvalidAmount {}));
+            }
+            total_amount = checked_add(total_amount, *amount)?;
+        }
+
+        // Pull from each payer, spending the collector allowance first
+        let mut statuses = Vec::with_capacity(payers.len());
+        let mut collected = U256::ZERO;
+        for (&payer, &amount) in payers.iter().zip(amounts) {
+            let status = if payer == Address::ZERO {
+                FAILURE_INVALID_RECIPIENT
+            } else {
+                self.pull_for_collector(token, payer, collector, to, amount)
+            };
+
+            if status == TRANSFER_OK {
+                collected = checked_add(collected, amount)?;
+                log(self.vm(), CollectionSuccess { payer, amount });
+            } else {
+                log(self.vm(), CollectionFailed {
+                    payer,
+                    amount,
+                    reason: failure_reason(status).to_string(),
+                });
+            }
+            statuses.push(status);
+        }
+
+        // Update statistics
+        let successes = U256::from(success_count(&statuses));
+        let batch_id = self.record_batch(collector, successes)?;
+
+        log(self.vm(), BatchTokenCollection {
+            collector,
+            token,
+            to,
+            totalAmount: collected,
+            payerCount: successes,
+        });
+
+        Ok((batch_id, successes, statuses, U256::ZERO))
+    }
+
+    /// Check a `CollectorAuthorization` signature over `(payer, collector, token, amount,
+    /// deadline)` and the payer's nonce, then set the collector allowance it grants
+    fn authorize_collector(
+        &mut self,
+        authorization: (Address, Address, Address, U256, U256),
+        v: u8,
+        r: B256,
+        s: B256,
+    ) -> Result<(), Error> {
+        let (payer, collector, token, amount, deadline) = authorization;
+        if U256::from(self.vm().block_timestamp()) > deadline {
+            return Err(Error::CollectorAuthorizationExpired(CollectorAuthorizationExpired {
+                deadline,
+            }));
+        }
+
+        let nonce = self.collector_nonce(payer);
+        let digest = self.collector_digest(authorization, nonce);
+        let input = (digest, U256::from(v), r, s).abi_encode();
+        let signer = self
+            .vm()
+            .static_call(&Call::new(), ECRECOVER, &input)
+            .ok()
+            .and_then(|result| Address::abi_decode(&result, true).ok());
+        if payer == Address::ZERO || signer != Some(payer) {
+            return Err(Error::InvalidCollectorSignature(InvalidCollectorSignature { payer }));
+        }
+
+        self.collector_nonces.insert(payer, checked_add(nonce, U256::from(1))?);
+        self.collector_allowances
+            .setter(payer)
+            .setter(collector)
+            .insert(token, amount);
+        log(self.vm(), CollectorApproval {
+            payer,
+            collector,
+            token,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// EIP-712 digest of a `CollectorAuthorization` signed with `nonce`
+    fn collector_digest(
+        &self,
+        authorization: (Address, Address, Address, U256, U256),
+        nonce: U256,
+    ) -> B256 {
+        let (payer, collector, token, amount, deadline) = authorization;
+        let type_hash = keccak256(COLLECTOR_AUTHORIZATION_TYPE);
+        let struct_hash =
+            keccak256((type_hash, payer, collector, token, amount, nonce, deadline).abi_encode());
+        let mut message = Vec::with_capacity(66);
+        message.extend_from_slice(b"\x19\x01");
+        message.extend_from_slice(self.domain_separator().as_slice());
+        message.extend_from_slice(struct_hash.as_slice());
+        keccak256(message)
+    }
+
+    /// Spend `amount` of the collector allowance and pull it from `payer` to `to`, restoring the
+    /// allowance if the pull fails
+    fn pull_for_collector(
+        &mut self,
+        token: Address,
+        payer: Address,
+        collector: Address,
+        to: Address,
+        amount: U256,
+    ) -> u8 {
+        let allowance = self.collector_allowance(payer, collector, token);
+        if allowance < amount {
+            return FAILURE_COLLECTOR_ALLOWANCE;
+        }
+
+        let limited = allowance != U256::MAX;
+        if limited {
+            self.collector_allowances
+                .setter(payer)
+                .setter(collector)
+                .insert(token, allowance - amount);
+        }
+
+        match self.token_transfer_from(token, payer, to, amount) {
+            Ok(_) => TRANSFER_OK,
+            Err(code) => {
+                if limited {
+                    self.collector_allowances
+                        .setter(payer)
+                        .setter(collector)
+                        .insert(token, allowance);
+                }
+                code
+            }
+        }
+    }
+
+    /// Current L2 block number from ArbSys
+    fn arb_block_number(&self) -> Result<U256, Error> {
+        let call = IArbSys::arbBlockNumberCall {};
+        self.vm()
+            .static_call(&Call::new(), ARB_SYS, &call.abi_encode())
+            .ok()
+            .and_then(|result| U256::abi_decode(&result, true).ok())
+            .ok_or(Error::BlockHashUnavailable(BlockHashUnavailable {
+                blockNumber: U256::ZERO,
+            }))
+    }
+
+    /// Hash of a recent L2 block from ArbSys, which only serves the last 256 blocks
+    fn arb_block_hash(&self, block_number: U256) -> Result<B256, Error> {
+        let call = IArbSys::arbBlockHashCall { arbBlockNum: block_number };
+        self.vm()
+            .static_call(&Call::new(), ARB_SYS, &call.abi_encode())
+            .ok()
+            .and_then(|result| B256::abi_decode(&result, true).ok())
+            .filter(|hash| *hash != B256::ZERO)
+            .ok_or(Error::BlockHashUnavailable(BlockHashUnavailable {
+                blockNumber: block_number,
+            }))
+    }
+
+    /// Validate a swap-and-distribute call, returning the configured router
+    fn check_swap(
+        &self,
+        token_out: Address,
+        recipients: &[Address],
+        weights: &[U256],
+    ) -> Result<Address, Error> {
+        let router = self.swap_router.get();
+        if router == Address::ZERO {
+            return Err(Error::SwapRouterNotSet(SwapRouterNotSet {}));
+        }
+
+        if recipients.len() != weights.len() {
+            return Err(Error::ArrayLengthMismatch(ArrayLengthMismatch {}));
+        }
+
+        if recipients.is_empty() || weights.contains(&U256::ZERO) {
+            return Err(Error::InvalidAmount(InvalidAmount {}));
+        }
+
+        if token_out == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: token_out }));
+        }
+
+        Ok(router)
+    }
+
+    /// Amount of `token_out` received since `out_before`, failing below `min_out`
+    fn swap_output(
+        &self,
+        token_out: Address,
+        out_before: U256,
+        min_out: U256,
+    ) -> Result<U256, Error> {
+        let this = self.vm().contract_address();
+        let amount_out = self.token_balance(token_out, this)?.saturating_sub(out_before);
+        if amount_out < min_out || amount_out == U256::ZERO {
+            return Err(Error::InsufficientSwapOutput(InsufficientSwapOutput {
+                amountOut: amount_out,
+                minOut: min_out,
+            }));
+        }
+        Ok(amount_out)
+    }
+
+    /// Send each recipient its weighted share of the swap output, returning rounding dust and
+    /// failed shares to the sender. `swap` is `(tokenIn, amountIn, tokenOut, amountOut)`.
+    fn distribute_swapped(
+        &mut self,
+        sender: Address,
+        swap: (Address, U256, Address, U256),
+        recipients: &[Address],
+        weights: &[U256],
+    ) -> Result<(U256, U256, Vec<u8>), Error> {
+        let (token_in, amount_in, token_out, amount_out) = swap;
+        let mut total_weight = U256::ZERO;
+        for weight in weights {
+            total_weight = checked_add(total_weight, *weight)?;
+        }
+
+        let mut statuses = Vec::with_capacity(recipients.len());
+        let mut sent = U256::ZERO;
+        for (&recipient, &weight) in recipients.iter().zip(weights) {
+            let share = checked_mul(amount_out, weight)? / total_weight;
+            let status = if recipient == Address::ZERO {
+                FAILURE_INVALID_RECIPIENT
+            } else if share == U256::ZERO {
+                TRANSFER_OK
+            } else {
+                match self.token_transfer(token_out, recipient, share) {
+                    Ok(_) => TRANSFER_OK,
+                    Err(code) => code,
+                }
+            };
+
+            if status == TRANSFER_OK {
+                sent = checked_add(sent, share)?;
+            }
+            self.log_transfer(recipient, share, status, EVENTS_VERBOSE);
+            statuses.push(status);
+        }
+
+        // Return rounding dust and failed shares if any
+        self.refund(token_out, sender, amount_out - sent)?;
+
+        // Update statistics
+        let successes = U256::from(success_count(&statuses));
+        let batch_id = self.record_batch(sender, successes)?;
+
+        log(self.vm(), SwapAndDistribute {
+            sender,
+            tokenIn: token_in,
+            tokenOut: token_out,
+            amountIn: amount_in,
+            amountOut: amount_out,
+            recipientCount: successes,
+        });
+
+        Ok((batch_id, amount_out, statuses))
+    }
+
+    /// Whether a partial batch should stop with at most `gas_reserve` gas left
+    fn gas_exhausted(&self, gas_reserve: u64) -> bool {
+        gas_reserve != 0 && self.vm().evm_gas_left() <= gas_reserve
+    }
+
+    /// Gas a call may forward without touching `gas_reserve` (all of it for zero), so a
+    /// recipient cannot burn the gas a partial batch keeps for closing itself
+    fn call_gas(&self, gas_reserve: u64) -> u64 {
+        if gas_reserve == 0 {
+            return u64::MAX;
+        }
+        self.vm().evm_gas_left().saturating_sub(gas_reserve)
+    }
+
+    /// Send `amount` of ETH to `recipient`, forwarding the gas above `gas_reserve`
+    fn send_eth(&mut self, recipient: Address, amount: U256, gas_reserve: u64) -> bool {
+        if gas_reserve == 0 {
+            return self.vm().transfer_eth(recipient, amount).is_ok();
+        }
+        let gas = self.call_gas(gas_reserve);
+        let config = Call::new().value(amount).gas(gas);
+        self.vm().call(&config, recipient, &[]).is_ok()
+    }
+
+    /// Log the outcome of one transfer in the requested event mode.
+    /// Compact mode only logs failures; successes are in the batch summary bitmap.
+    fn log_transfer(&mut self, recipient: Address, amount: U256, status: u8, event_mode: u8) {
+        match (event_mode, status) {
+            (EVENTS_VERBOSE, TRANSFER_OK) => log(self.vm(), TransferSuccess { recipient, amount }),
+            (EVENTS_VERBOSE, code) => log(self.vm(), TransferFailed {
+                recipient,
+                amount,
+                reason: failure_reason(code).to_string(),
+            }),
+            (_, TRANSFER_OK) => {}
+            (_, code) => log(self.vm(), TransferFailedCode {
+                recipient,
+                amount,
+                code,
+            }),
+        }
+    }
+
+    /// Log the batch event of the requested event mode (`NATIVE_ETH` as token for ETH).
+    /// `wrapped` is the WETH bitmap from `distribute_eth`, empty for tokens.
+    fn log_batch(
+        &mut self,
+        sender: Address,
+        token: Address,
+        total_amount: U256,
+        statuses: &[u8],
+        wrapped: &[u8],
+        event_mode: u8,
+    ) {
+        let recipient_count = U256::from(success_count(statuses));
+        if event_mode != EVENTS_VERBOSE {
+            log(self.vm(), BatchTransferSummary {
+                sender,
+                token,
+                totalAmount: total_amount,
+                recipientCount: U256::from(statuses.len()),
+                successCount: recipient_count,
+                successBitmap: success_bitmap(statuses).into(),
+                wrappedBitmap: wrapped.to_vec().into(),
+            });
+        } else if token == NATIVE_ETH {
+            log(self.vm(), BatchEthTransfer {
+                sender,
+                totalAmount: total_amount,
+                recipientCount: recipient_count,
+            });
+        } else {
+            log(self.vm(), BatchTokenTransfer {
+                sender,
+                token,
+                totalAmount: total_amount,
+                recipientCount: recipient_count,
+            });
+        }
+    }
+
+    /// Wrap `amount` into the configured WETH and transfer it to `recipient`.
+    /// Returns false, with the ETH still in the contract, if no WETH is set or wrapping fails.
+    /// WETH the recipient cannot receive is credited to their ledger instead; it is not
+    /// unwrapped, since WETH could not send the ETH back into the running batch.
+    /// Each call forwards only the gas above `gas_reserve`, like `send_eth`.
+    fn wrap_and_transfer(&mut self, recipient: Address, amount: U256, gas_reserve: u64) -> bool {
+        let weth = self.weth.get();
+        if weth == Address::ZERO {
+            return false;
+        }
+
+        let gas = self.call_gas(gas_reserve);
+        let config = Call::new().value(amount).gas(gas);
+        if self
+            .vm()
+            .call(&config, weth, &IWETH::depositCall {}.abi_encode())
+            .is_err()
+        {
+            return false;
+        }
+
+        let transfer = IERC20::transferCall { to: recipient, amount };
+        let gas = self.call_gas(gas_reserve);
+        if self.call_token_with_gas(weth, &transfer.abi_encode(), gas).is_ok() {
+            return true;
+        }
+
+        // Keep stuck WETH claimable by the recipient rather than stranding it in the contract
+        self.credit_ledger(weth, recipient, amount).is_ok()
+            && self.reserve_funds(weth, amount).is_ok()
+    }
+
+    /// Return `amount` of `asset` held by the contract to `to`. If the transfer fails, the
+    /// amount is credited to their ledger balance so it stays claimable with `withdraw`.
+    fn refund(&mut self, asset: Address, to: Address, amount: U256) -> Result<(), Error> {
+        if amount == U256::ZERO || self.send_asset(asset, to, amount).is_ok() {
+            return Ok(());
+        }
+
+        self.credit_ledger(asset, to, amount)?;
+        self.reserve_funds(asset, amount)?;
+        log(self.vm(), LedgerDeposit {
+            token: asset,
+            account: to,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Send ETH or tokens held by the contract, returning the failure code if it did not succeed
+    fn send_asset(&mut self, asset: Address, to: Address, amount: U256) -> Result<(), u8> {
+        if asset == NATIVE_ETH {
+            self.vm()
+                .transfer_eth(to, amount)
+                .map_err(|_| FAILURE_ETH_TRANSFER)
+        } else {
+            self.token_transfer(asset, to, amount)
+        }
+    }
+
+    /// Call `transfer` on `token`, returning the failure code if it did not succeed
+    fn token_transfer(&mut self, token: Address, to: Address, amount: U256) -> Result<(), u8> {
+        let call = IERC20::transferCall { to, amount };
+        self.call_token(token, &call.abi_encode())
+    }
+
+    /// Call `transferFrom` on `token`, returning the failure code if it did not succeed
+    fn token_transfer_from(
+        &mut self,
+        token: Address,
+        from: Address,
+        to: Address,
+        amount: U256,
+    ) -> Result<(), u8> {
+        let call = IERC20::transferFromCall { from, to, amount };
+        self.call_token(token, &call.abi_encode())
+    }
+
+    /// Execute a token transfer call forwarding all gas
+    fn call_token(&mut self, token: Address, calldata: &[u8]) -> Result<(), u8> {
+        self.call_token_with_gas(token, calldata, u64::MAX)
+    }
+
+    /// Execute a token transfer call forwarding at most `gas`. Tokens that return no data
+    /// succeed like those returning `true`; `false` fails with `FAILURE_TOKEN_RETURNED_FALSE`,
+    /// other data with `FAILURE_TOKEN_BAD_RETURN` and a revert with `FAILURE_TOKEN_CALL`.
+    fn call_token_with_gas(&mut self, token: Address, calldata: &[u8], gas: u64) -> Result<(), u8> {
+        let config = Call::new().gas(gas);
+        match self.vm().call(&config, token, calldata) {
+            Ok(result) if result.is_empty() => Ok(()),
+            Ok(result) => match bool::abi_decode(&result, true) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(FAILURE_TOKEN_RETURNED_FALSE),
+                Err(_) => Err(FAILURE_TOKEN_BAD_RETURN),
+            },
+            Err(_) => Err(FAILURE_TOKEN_CALL),
+        }
+    }
+
+    /// Read `balanceOf(account)` from `token`
+    fn token_balance(&self, token: Address, account: Address) -> Result<U256, Error> {
+        let call = IERC20::balanceOfCall { account };
+        self.vm()
+            .static_call(&Call::new(), token, &call.abi_encode())
+            .ok()
+            .and_then(|result| U256::abi_decode(&result, true).ok())
+            .ok_or(Error::TokenTransferFailed(TokenTransferFailed { token }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::Error;
+    use proptest::prelude::*;
+    use stylus_sdk::testing::*;
+
+    #[no_mangle]
+    pub unsafe extern "C" fn emit_log(_pointer: *const u8, _len: usize, _: usize) {}
+    #[no_mangle]
+    pub unsafe extern "C" fn msg_sender(_sender: *mut u8) {}
+
+    #[test]
+    fn test_multisender() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+
+        // Test initialization
+        let owner_addr = Address::from([1u8; 20]);
+        contract.constructor(owner_addr);
+
+        assert_eq!(contract.owner(), owner_addr);
+        assert_eq!(contract.total_transactions(), U256::ZERO);
+        assert_eq!(contract.total_recipients(), U256::ZERO);
+
+        // Test gas estimation
+        let gas_estimate = contract.estimate_batch_eth_gas(U256::from(5));
+        assert!(gas_estimate > U256::ZERO);
+    }
+
+    #[test]
+    fn test_gas_estimates() {
+        let vm = TestVM::default();
+        let contract = MultiSender::from(&vm);
+        let token = Address::from([7u8; 20]);
+        let new_account = Address::from([2u8; 20]);
+        let funded = Address::from([3u8; 20]);
+        let receiver = Address::from([4u8; 20]);
+        vm.set_balance(funded, U256::from(1));
+        vm.set_code(receiver, vec![0x60, 0x00]);
+        let recipients = vec![new_account, funded, receiver];
+        let amounts = vec![U256::from(100); 3];
+
+        // The count-based estimates keep their uint256 return and saturate
+        let one = contract.estimate_batch_eth_gas(U256::from(1));
+        let two = contract.estimate_batch_eth_gas(U256::from(2));
+        assert_eq!(two - one, U256::from(2_600 + 9_000 + 25_000 + 1_381 + 64 * 16));
+        let token_one = contract.estimate_batch_token_gas(U256::from(1));
+        let token_two = contract.estimate_batch_token_gas(U256::from(2));
+        let token_transfer = 100 + 2 * 2_900 + 2_100 + 20_000 + 1_756 + 1_381;
+        assert_eq!(token_two - token_one, U256::from(token_transfer + 64 * 16));
+        assert_eq!(contract.estimate_batch_token_gas(U256::MAX), U256::MAX);
+
+        // Pricing fails without ArbGasInfo instead of dropping the L1 fee
+        let result = contract.estimate_batch_eth_cost(recipients.clone(), amounts.clone());
+        assert!(matches!(result, Err(Error::GasPriceUnavailable(_))));
+
+        // Prices: 1000 wei per transaction, 10 wei per calldata byte and 2 wei per L2 gas
+        let prices = (
+            U256::from(1_000),
+            U256::from(10),
+            U256::ZERO,
+            U256::from(2),
+            U256::ZERO,
+            U256::from(2),
+        );
+        let call = IArbGasInfo::getPricesInWeiCall {};
+        vm.mock_static_call(ARB_GAS_INFO, call.abi_encode(), Ok(prices.abi_encode()));
+
+        // Each ETH recipient is priced from its state, calldata is counted once per layer
+        let params = (recipients.clone(), amounts.clone()).abi_encode_params();
+        let (l2_gas, l1_fee, total_cost) = contract
+            .estimate_batch_eth_cost(recipients.clone(), amounts.clone())
+            .ok()
+            .unwrap();
+        let transfers = 3 * (2_600 + 9_000 + 1_381) + 25_000 + 10_000;
+        let overhead = 21_000 + 3 * 5_000 + 2_012;
+        assert_eq!(l2_gas, U256::from(overhead + transfers + calldata_gas(&params)));
+        assert_eq!(l1_fee, U256::from(1_000 + (4 + params.len()) * 10));
+        assert_eq!(total_cost, l2_gas * U256::from(2) + l1_fee);
+
+        // Token recipients without a balance cost a fresh storage slot
+        let balance_call = |account| IERC20::balanceOfCall { account }.abi_encode();
+        vm.mock_static_call(token, balance_call(new_account), Ok(U256::ZERO.abi_encode()));
+        vm.mock_static_call(token, balance_call(funded), Ok(U256::from(5).abi_encode()));
+        let cost = |recipient: Address| {
+            contract
+                .estimate_batch_token_cost(token, vec![recipient], vec![U256::from(100)])
+                .ok()
+                .unwrap()
+                .0
+        };
+        assert_eq!(cost(new_account) - cost(funded), U256::from(20_000 - 2_900));
+
+        // Unreadable balances and mismatched arrays are rejected
+        let result = contract.estimate_batch_token_cost(token, vec![receiver], vec![U256::from(1)]);
+        assert!(matches!(result, Err(Error::TokenTransferFailed(_))));
+        let result = contract.estimate_batch_eth_cost(recipients, vec![U256::from(1)]);
+        assert!(matches!(result, Err(Error::ArrayLengthMismatch(_))));
+    }
+
+    #[test]
+    fn test_stored_batch() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let batch_id = U256::from(7);
+
+        // Upload the batch in two chunks
+        let first = vec![Address::from([2u8; 20]), Address::from([3u8; 20])];
+        assert!(contract
+            .append_batch(batch_id, first, vec![U256::from(10), U256::from(20)])
+            .is_ok());
+        assert!(contract
+            .append_batch(batch_id, vec![Address::from([4u8; 20])], vec![U256::from(30)])
+            .is_ok());
+
+        let (sender, cursor, count, total, paid, funded, closed) = contract.batch_progress(batch_id);
+        assert_eq!(sender, vm.msg_sender());
+        assert_eq!(cursor, U256::ZERO);
+        assert_eq!(count, U256::from(3));
+        assert_eq!(total, U256::from(60));
+        assert_eq!(paid, U256::ZERO);
+        assert!(!funded);
+        assert!(!closed);
+
+        // Processing requires funding
+        assert!(contract.process_batch(batch_id, U256::from(10)).is_err());
+
+        // Only the batch sender may extend it
+        vm.set_sender(Address::from([9u8; 20]));
+        assert!(contract
+            .append_batch(batch_id, vec![Address::from([5u8; 20])], vec![U256::from(1)])
+            .is_err());
+        assert!(contract.cancel_batch(batch_id).is_err());
+
+        // Cancelling an unfunded batch closes it without a refund
+        vm.set_sender(sender);
+        assert!(contract.cancel_batch(batch_id).is_ok());
+        let (_, _, _, _, _, _, closed) = contract.batch_progress(batch_id);
+        assert!(closed);
+        assert!(contract.fund_batch(batch_id).is_err());
+
+        // A funded batch is locked, paid out in chunks and released once complete
+        let batch_id = U256::from(8);
+        let recipients: Vec<Address> = (2..5u8).map(|i| Address::from([i; 20])).collect();
+        let amounts = vec![U256::from(10), U256::from(20), U256::from(30)];
+        assert!(contract
+            .append_batch(batch_id, recipients.clone(), amounts.clone())
+            .is_ok());
+        vm.set_value(U256::from(59));
+        let result = contract.fund_batch(batch_id);
+        assert!(matches!(result, Err(Error::InsufficientValue(_))));
+        vm.set_value(U256::from(60));
+        assert!(contract.fund_batch(batch_id).is_ok());
+        vm.set_value(U256::ZERO);
+        vm.set_balance(vm.contract_address(), U256::from(60));
+        assert_eq!(contract.reserved_eth.get(), U256::from(60));
+        let result = contract.append_batch(batch_id, recipients.clone(), amounts.clone());
+        assert!(matches!(result, Err(Error::BatchAlreadyFunded(_))));
+
+        assert_eq!(contract.process_batch(batch_id, U256::from(2)).ok(), Some(U256::from(2)));
+        let (_, cursor, _, _, paid, funded, closed) = contract.batch_progress(batch_id);
+        assert_eq!((cursor, paid), (U256::from(2), U256::from(30)));
+        assert!(funded && !closed);
+        assert_eq!(contract.reserved_eth.get(), U256::from(30));
+
+        assert_eq!(contract.process_batch(batch_id, U256::from(10)).ok(), Some(U256::from(3)));
+        let (_, _, _, _, paid, _, closed) = contract.batch_progress(batch_id);
+        assert_eq!(paid, U256::from(60));
+        assert!(closed);
+        assert_eq!(contract.reserved_eth.get(), U256::ZERO);
+        assert_eq!(contract.total_recipients(), U256::from(3));
+        let result = contract.process_batch(batch_id, U256::from(1));
+        assert!(matches!(result, Err(Error::BatchClosed(_))));
+
+        // Cancelling a partly processed batch refunds the rest and releases it
+        let batch_id = U256::from(9);
+        assert!(contract.append_batch(batch_id, recipients, amounts).is_ok());
+        vm.set_value(U256::from(60));
+        assert!(contract.fund_batch(batch_id).is_ok());
+        vm.set_value(U256::ZERO);
+        vm.set_balance(vm.contract_address(), U256::from(60));
+        assert!(contract.process_batch(batch_id, U256::from(1)).is_ok());
+        assert!(contract.cancel_batch(batch_id).is_ok());
+        assert_eq!(contract.reserved_eth.get(), U256::ZERO);
+        assert_eq!(contract.total_transactions(), U256::from(2));
+
+        // Releasing more than is reserved fails instead of hiding the mismatch
+        let result = contract.release_funds(NATIVE_ETH, U256::from(1));
+        assert!(matches!(result, Err(Error::ArithmeticOverflow(_))));
+    }
+
+    #[test]
+    fn test_ledger() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+
+        vm.set_value(U256::from(100));
+        assert!(contract.deposit_eth().is_ok());
+        vm.set_value(U256::ZERO);
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, sender), U256::from(100));
+
+        // Credits move value between ledger balances only
+        assert!(contract
+            .batch_credit(NATIVE_ETH, vec![alice, bob], vec![U256::from(30), U256::from(50)])
+            .is_ok());
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, sender), U256::from(20));
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, alice), U256::from(30));
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, bob), U256::from(50));
+        assert_eq!(contract.total_transactions(), U256::from(1));
+
+        // Overdrawing leaves balances untouched
+        assert!(contract
+            .batch_credit(NATIVE_ETH, vec![alice], vec![U256::from(21)])
+            .is_err());
+        vm.set_sender(alice);
+        assert!(contract.withdraw(NATIVE_ETH, U256::from(31)).is_err());
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, alice), U256::from(30));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let payer = vm.msg_sender();
+        let recipients = vec![Address::from([2u8; 20]), Address::from([3u8; 20])];
+        let amounts = vec![U256::from(10), U256::from(15)];
+
+        vm.set_block_timestamp(1_000);
+
+        // ETH schedules must be funded from the ledger
+        assert!(contract
+            .create_schedule(
+                "payroll".to_string(),
+                NATIVE_ETH,
+                recipients.clone(),
+                amounts.clone(),
+                U256::from(100),
+                U256::ZERO,
+                U256::ZERO,
+                FUNDING_ALLOWANCE,
+                true,
+            )
+            .is_err());
+
+        let schedule_id = contract
+            .create_schedule(
+                "payroll".to_string(),
+                NATIVE_ETH,
+                recipients.clone(),
+                amounts.clone(),
+                U256::from(100),
+                U256::ZERO,
+                U256::from(1_250),
+                FUNDING_LEDGER,
+                true,
+            )
+            .ok()
+            .unwrap();
+        assert_eq!(schedule_id, U256::from(1));
+        assert_eq!(contract.schedule_count(), U256::from(1));
+        assert_eq!(contract.schedule_recipients(schedule_id), (recipients, amounts));
+
+        let (info_payer, name, asset, total, _, start, _, executed, paused, cancelled) =
+            contract.schedule_info(schedule_id);
+        assert_eq!(info_payer, payer);
+        assert_eq!(name, "payroll");
+        assert_eq!(asset, NATIVE_ETH);
+        assert_eq!(total, U256::from(25));
+        assert_eq!(start, U256::from(1_000));
+        assert_eq!(executed, U256::ZERO);
+        assert!(!paused && !cancelled);
+
+        // Without a ledger balance the period cannot be paid
+        assert!(contract.execute_schedule(schedule_id).is_err());
+
+        // Only the payer controls the schedule
+        vm.set_sender(Address::from([9u8; 20]));
+        assert!(contract.pause_schedule(schedule_id).is_err());
+        vm.set_sender(payer);
+        assert!(contract.pause_schedule(schedule_id).is_ok());
+        assert!(contract.execute_schedule(schedule_id).is_err());
+        assert!(contract.resume_schedule(schedule_id).is_ok());
+        let result = contract.resume_schedule(schedule_id);
+        assert!(matches!(result, Err(Error::ScheduleNotPaused(_))));
+
+        // Cancelled schedules cannot be resumed
+        assert!(contract.pause_schedule(schedule_id).is_ok());
+        assert!(contract.cancel_schedule(schedule_id).is_ok());
+        let result = contract.resume_schedule(schedule_id);
+        assert!(matches!(result, Err(Error::ScheduleIsCancelled(_))));
+        assert!(contract.execute_schedule(schedule_id).is_err());
+    }
+
+    #[test]
+    fn test_recipient_groups() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+        let carol = Address::from([4u8; 20]);
+
+        let group_id = contract.create_group("contributors".to_string()).ok().unwrap();
+        assert_eq!(group_id, U256::from(1));
+        assert_eq!(contract.group_count(), U256::from(1));
+
+        assert!(contract
+            .add_group_members(
+                group_id,
+                vec![alice, bob, carol],
+                vec![U256::from(1), U256::from(2), U256::from(3)],
+            )
+            .is_ok());
+
+        // Re-adding a member only updates the default amount
+        assert!(contract
+            .add_group_members(group_id, vec![bob], vec![U256::from(5)])
+            .is_ok());
+        assert_eq!(
+            contract.group_members(group_id),
+            (vec![alice, bob, carol], vec![U256::from(1), U256::from(5), U256::from(3)])
+        );
+
+        // Removing swaps the last member into the freed slot
+        assert!(contract.remove_group_members(group_id, vec![alice]).is_ok());
+        assert_eq!(
+            contract.group_members(group_id),
+            (vec![carol, bob], vec![U256::from(3), U256::from(5)])
+        );
+        assert!(contract.remove_group_members(group_id, vec![alice]).is_err());
+
+        let (owner, name, count) = contract.group_info(group_id);
+        assert_eq!(owner, vm.msg_sender());
+        assert_eq!(name, "contributors");
+        assert_eq!(count, U256::from(2));
+
+        // Only the owner edits the group
+        vm.set_sender(alice);
+        assert!(contract
+            .add_group_members(group_id, vec![alice], vec![U256::from(1)])
+            .is_err());
+
+        // Sending requires enough value for every default amount
+        vm.set_value(U256::from(7));
+        let result = contract.batch_send_eth_to_group(group_id);
+        assert!(matches!(result, Err(Error::InsufficientValue(_))));
+        let result = contract.batch_send_eth_to_group(U256::from(99));
+        assert!(matches!(result, Err(Error::UnknownGroup(_))));
+
+        // A group whose members were all removed is empty, not unknown
+        vm.set_sender(contract.group_info(group_id).0);
+        assert!(contract.remove_group_members(group_id, vec![bob, carol]).is_ok());
+        let result = contract.batch_send_eth_to_group(group_id);
+        assert!(matches!(result, Err(Error::InvalidAmount(_))));
+        let result = contract.batch_send_token_to_group(carol, group_id, U256::from(1));
+        assert!(matches!(result, Err(Error::InvalidAmount(_))));
+    }
+
+    #[test]
+    fn test_weth_fallback_config() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let owner = vm.msg_sender();
+        let weth = Address::from([8u8; 20]);
+        contract.constructor(owner);
+
+        assert_eq!(contract.weth(), Address::ZERO);
+        assert!(contract.set_weth(weth).is_ok());
+        assert_eq!(contract.weth(), weth);
+
+        // Only the owner configures the fallback
+        vm.set_sender(Address::from([2u8; 20]));
+        assert!(contract.set_weth(Address::ZERO).is_err());
+        assert_eq!(contract.weth(), weth);
+    }
+
+    #[test]
+    fn test_multicall_access() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let owner = vm.msg_sender();
+        let operator = Address::from([2u8; 20]);
+        let target = Address::from([3u8; 20]);
+        contract.constructor(owner);
+
+        // Only allowlisted targets can be called, whatever the calldata
+        let calldata = Bytes::from(vec![0x12, 0x34, 0x56, 0x78]);
+        let result = contract.multicall(vec![(target, U256::ZERO, calldata.clone(), true)]);
+        assert!(matches!(result, Err(Error::ForbiddenCall(_))));
+
+        // The contract itself and tokens held for users cannot be allowlisted
+        let token = Address::from([4u8; 20]);
+        assert!(contract.reserve_funds(token, U256::from(1)).is_ok());
+        let result = contract.set_multicall_target(token, true);
+        assert!(matches!(result, Err(Error::ForbiddenCall(_))));
+        let result = contract.set_multicall_target(vm.contract_address(), true);
+        assert!(matches!(result, Err(Error::ForbiddenCall(_))));
+        assert!(contract.set_multicall_target(target, true).is_ok());
+        assert!(contract.is_multicall_target(target));
+
+        // Forwarded value must be covered by msg.value
+        let result = contract.multicall(vec![(target, U256::from(1), calldata, true)]);
+        assert!(matches!(result, Err(Error::InsufficientValue(_))));
+
+        // Operators need the role
+        vm.set_sender(operator);
+        assert!(contract.multicall(vec![]).is_err());
+        assert!(contract.set_multicall_operator(operator, true).is_err());
+        assert!(contract.set_multicall_target(Address::from([5u8; 20]), true).is_err());
+
+        vm.set_sender(owner);
+        assert!(contract.set_multicall_operator(operator, true).is_ok());
+        assert!(contract.is_multicall_operator(operator));
+
+        vm.set_sender(operator);
+        assert!(matches!(contract.multicall(vec![]), Ok(results) if results.is_empty()));
+    }
+
+    sol_storage! {
+        /// `MultiSender` with a field appended, as the next implementation would declare it.
+        pub struct MultiSenderV2 {
+            address owner;
+            uint256 total_transactions;
+            uint256 total_recipients;
+            mapping(address => uint256) user_transaction_count;
+            mapping(uint256 => StoredBatch) stored_batches;
+            uint256 reserved_eth;
+            mapping(address => uint256) reserved_tokens;
+            mapping(address => mapping(address => uint256)) ledger_balances;
+            mapping(uint256 => Schedule) schedules;
+            uint256 schedule_count;
+            mapping(uint256 => RecipientGroup) groups;
+            uint256 group_count;
+            address weth;
+            mapping(address => bool) multicall_operators;
+            bool initialized;
+            bool reject_unsolicited_eth;
+            mapping(address => mapping(bytes32 => bool)) executed_batch_keys;
+            mapping(address => mapping(address => SenderLimits)) sender_limits;
+            mapping(uint256 => Invoice) invoices;
+            uint256 invoice_count;
+            mapping(address => mapping(address => mapping(address => uint256))) collector_allowances;
+            address swap_router;
+            mapping(address => bool) erc1363_tokens;
+            mapping(uint256 => Raffle) raffles;
+            uint256 raffle_count;
+            mapping(address => bool) multicall_targets;
+            mapping(address => uint256) collector_nonces;
+            mapping(bytes32 => bool) used_raffle_commitments;
+            uint256 fee_bps;
         }
     }
 
-    /// Transfer ownership to a new owner (owner only)
-    pub fn transfer_ownership(&mut self, new_owner: Address) {
-        let caller = self.vm().msg_sender();
-        let owner = self.owner.get();
-        
-        if caller != owner || new_owner == Address::ZERO {
-            return;
+    #[test]
+    fn test_upgrade() {
+        use uups::{IERC1822Proxiable, IMPLEMENTATION_SLOT};
+        let vm = TestVM::default();
+        let owner = Address::from([1u8; 20]);
+        let new_implementation = Address::from([6u8; 20]);
+        let uuid_call = IERC1822Proxiable::proxiableUUIDCall {}.abi_encode();
+        let uuid = Ok(IMPLEMENTATION_SLOT.to_vec());
+
+        // A directly called implementation can be queried for its UUID but never upgraded
+        let mut implementation = MultiSender::from(&vm);
+        implementation.constructor(owner);
+        assert!(implementation.initialize(owner).is_err());
+        assert_eq!(implementation.proxiable_uuid().ok(), Some(IMPLEMENTATION_SLOT));
+        vm.mock_static_call(new_implementation, uuid_call.clone(), uuid.clone());
+        vm.set_sender(owner);
+        assert!(matches!(
+            implementation.upgrade_to_and_call(new_implementation, Bytes::from(vec![])),
+            Err(Error::UnauthorizedCallContext(_))
+        ));
+
+        // Fresh storage behind a proxy, which holds the current implementation
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let current_implementation = Address::from([5u8; 20]).into_word();
+        unsafe { vm.storage_cache_bytes32(uups::implementation_slot(), current_implementation) };
+        assert!(contract.proxiable_uuid().is_err());
+
+        // Proxies are set up through the initializer, exactly once
+        assert!(contract.initialize(owner).is_ok());
+        assert!(contract.initialize(Address::from([2u8; 20])).is_err());
+        assert_eq!(contract.owner(), owner);
+
+        vm.set_sender(owner);
+        vm.set_value(U256::from(100));
+        assert!(contract.deposit_eth().is_ok());
+        vm.set_value(U256::ZERO);
+        assert!(contract.set_multicall_target(Address::from([3u8; 20]), true).is_ok());
+
+        // Upgrades require a UUPS-compatible implementation
+        assert!(matches!(
+            contract.upgrade_to_and_call(new_implementation, Bytes::from(vec![])),
+            Err(Error::InvalidImplementation(_))
+        ));
+        vm.mock_static_call(new_implementation, uuid_call, uuid);
+
+        vm.set_sender(Address::from([2u8; 20]));
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_err());
+
+        vm.set_sender(owner);
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_ok());
+        assert_eq!(
+            Address::from_word(vm.storage_load_bytes32(uups::implementation_slot())),
+            new_implementation
+        );
+
+        // The appended layout reads the existing state and starts the new field empty
+        let upgraded = MultiSenderV2::from(&vm);
+        assert_eq!(upgraded.owner.get(), owner);
+        assert!(upgraded.initialized.get());
+        assert_eq!(upgraded.ledger_balances.getter(NATIVE_ETH).get(owner), U256::from(100));
+        assert_eq!(upgraded.reserved_eth.get(), U256::from(100));
+        assert!(upgraded.multicall_targets.get(Address::from([3u8; 20])));
+        assert_eq!(upgraded.fee_bps.get(), U256::ZERO);
+    }
+
+    #[test]
+    fn test_recover_assets() {
+        use stylus_sdk::alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let owner = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let nft = Address::from([5u8; 20]);
+        let alice = Address::from([2u8; 20]);
+        let id = U256::from(1);
+        contract.constructor(owner);
+
+        // 100 tokens held, 60 of them reserved for ledger balances
+        let balance_call = IERC20::balanceOfCall {
+            account: vm.contract_address(),
+        };
+        vm.mock_static_call(token, balance_call.abi_encode(), Ok(U256::from(100).abi_encode()));
+        contract.reserved_tokens.insert(token, U256::from(60));
+
+        // Reserved funds are never recovered
+        assert!(matches!(
+            contract.recover_erc20(token, alice, U256::from(41)),
+            Err(Error::InsufficientRecoverableBalance(e)) if e.available == U256::from(40)
+        ));
+        assert!(matches!(
+            contract.recover_erc20(token, Address::ZERO, U256::from(40)),
+            Err(Error::InvalidRecipient(_))
+        ));
+        assert!(matches!(
+            contract.recover_erc1155(nft, alice, id, U256::ZERO),
+            Err(Error::InvalidAmount(_))
+        ));
+
+        // The unreserved part is recovered and logged
+        let transfer = IERC20::transferCall { to: alice, amount: U256::from(40) };
+        vm.mock_call(token, transfer.abi_encode(), Ok(true.abi_encode()));
+        assert!(contract.recover_erc20(token, alice, U256::from(40)).is_ok());
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = ERC20Recovered::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!((event.token, event.to, event.amount), (token, alice, U256::from(40)));
+
+        // NFTs are sent with safeTransferFrom, which returns no data
+        let from = vm.contract_address();
+        let erc721 = IERC721::safeTransferFromCall { from, to: alice, tokenId: id };
+        vm.mock_call(nft, erc721.abi_encode(), Ok(vec![]));
+        assert!(contract.recover_erc721(nft, alice, id).is_ok());
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = ERC721Recovered::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!((event.token, event.to, event.tokenId), (nft, alice, id));
+
+        let erc1155 = IERC1155::safeTransferFromCall {
+            from,
+            to: alice,
+            id,
+            value: U256::from(3),
+            data: Default::default(),
+        };
+        vm.mock_call(nft, erc1155.abi_encode(), Ok(vec![]));
+        assert!(contract.recover_erc1155(nft, alice, id, U256::from(3)).is_ok());
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = ERC1155Recovered::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!(
+            (event.token, event.to, event.id, event.amount),
+            (nft, alice, id, U256::from(3))
+        );
+
+        // Only the owner recovers assets
+        vm.set_sender(alice);
+        assert!(matches!(
+            contract.recover_erc20(token, alice, U256::from(40)),
+            Err(Error::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.recover_erc721(nft, alice, id),
+            Err(Error::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.recover_erc1155(nft, alice, id, U256::from(1)),
+            Err(Error::UnauthorizedAccount(_))
+        ));
+    }
+
+    #[test]
+    fn test_receive_eth() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let owner = vm.msg_sender();
+        let alice = Address::from([2u8; 20]);
+        let weth = Address::from([8u8; 20]);
+        contract.constructor(owner);
+        assert!(contract.set_weth(weth).is_ok());
+
+        // Plain transfers are accepted by default
+        vm.set_sender(alice);
+        vm.set_value(U256::from(10));
+        assert!(contract.receive().is_ok());
+
+        // Unknown calls revert, with or without ETH
+        let selector = [0x12, 0x34, 0x56, 0x78];
+        let unknown: Vec<u8> = Error::UnknownFunction(UnknownFunction {
+            selector: selector.into(),
+        })
+        .into();
+        assert_eq!(contract.fallback(&selector).err(), Some(unknown.clone()));
+        vm.set_value(U256::ZERO);
+        assert_eq!(contract.fallback(&selector).err(), Some(unknown));
+
+        // Only the owner changes the policy
+        assert!(contract.set_reject_unsolicited_eth(true).is_err());
+        vm.set_sender(owner);
+        assert!(contract.set_reject_unsolicited_eth(true).is_ok());
+        assert!(contract.reject_unsolicited_eth());
+
+        vm.set_sender(alice);
+        vm.set_value(U256::from(10));
+        assert!(contract.receive().is_err());
+        assert!(contract.receive_ether().is_err());
+
+        // Deposits through the ledger still work
+        assert!(contract.deposit_eth().is_ok());
+
+        // WETH the recipient cannot receive is credited to their ledger
+        let deposit = IWETH::depositCall {}.abi_encode();
+        vm.mock_call(weth, deposit, Ok(vec![]));
+        let transfer = IERC20::transferCall {
+            to: alice,
+            amount: U256::from(10),
+        };
+        vm.mock_call(weth, transfer.abi_encode(), Err(vec![]));
+        assert!(contract.wrap_and_transfer(alice, U256::from(10), 0));
+        assert_eq!(contract.ledger_balance(weth, alice), U256::from(10));
+        assert_eq!(contract.reserved_tokens.get(weth), U256::from(10));
+    }
+
+    #[test]
+    fn test_token_paths() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(sender);
+
+        // Token responses modelled on the transfer modes of the `mock-erc20` crate.
+        // A re-entering token is blocked by Stylus and looks like a reverting call.
+        let responses = [
+            Ok(true.abi_encode()),
+            Ok(vec![]),
+            Ok(false.abi_encode()),
+            Err(vec![]),
+            Ok(vec![0x01]),
+        ];
+        let mut recipients = Vec::new();
+        for (i, response) in responses.into_iter().enumerate() {
+            let recipient = Address::from([0x10 + i as u8; 20]);
+            let call = IERC20::transferFromCall {
+                from: sender,
+                to: recipient,
+                amount,
+            };
+            vm.mock_call(token, call.abi_encode(), response);
+            recipients.push(recipient);
         }
-        
-        self.owner.set(new_owner);
+        recipients.push(Address::ZERO);
+
+        // Only the `true` and empty responses count as delivered
+        let amounts = vec![amount; recipients.len()];
+        assert!(contract
+            .batch_send_token(token, recipients.clone(), amounts.clone())
+            .is_ok());
+        assert_eq!(contract.total_transactions(), U256::from(1));
+        assert_eq!(contract.total_recipients(), U256::from(2));
+
+        // Invalid input reverts
+        assert!(contract
+            .batch_send_token(token, recipients.clone(), vec![amount])
+            .is_err());
+        assert!(contract.batch_send_token(token, vec![], vec![]).is_err());
+        assert!(contract
+            .batch_send_token(Address::ZERO, recipients.clone(), amounts)
+            .is_err());
+        assert!(contract
+            .batch_send_token(token, recipients, vec![U256::ZERO; 6])
+            .is_err());
+
+        // Ledger deposits fail when the pull fails
+        let balance_call = IERC20::balanceOfCall {
+            account: vm.contract_address(),
+        };
+        vm.mock_static_call(token, balance_call.abi_encode(), Ok(U256::ZERO.abi_encode()));
+        let pull = IERC20::transferFromCall {
+            from: sender,
+            to: vm.contract_address(),
+            amount,
+        };
+        vm.mock_call(token, pull.abi_encode(), Ok(false.abi_encode()));
+        assert!(contract.deposit_token(token, amount).is_err());
+
+        // Ledger withdrawals fail when the token refuses the transfer
+        assert!(contract.credit_ledger(token, sender, amount).is_ok());
+        let push = IERC20::transferCall { to: sender, amount };
+        vm.mock_call(token, push.abi_encode(), Err(vec![]));
+        assert!(contract.withdraw(token, amount).is_err());
     }
 
-    /// Get estimated gas for batch ETH transfer
-    pub fn estimate_batch_eth_gas(&self, recipient_count: U256) -> U256 {
-        // Base gas cost + per-recipient cost
-        // These are rough estimates and may vary
-        let base_gas = U256::from(21000); // Base transaction cost
-        let per_recipient_gas = U256::from(23000); // Gas per ETH transfer
-        
-        base_gas + (per_recipient_gas * recipient_count)
+    #[test]
+    fn test_compact_events() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(sender);
+
+        // Bits follow recipient order across byte boundaries
+        let mut statuses = vec![TRANSFER_OK; 9];
+        statuses[1] = FAILURE_TOKEN_CALL;
+        statuses[8] = FAILURE_INVALID_RECIPIENT;
+        assert_eq!(success_count(&statuses), 7);
+        assert_eq!(success_bitmap(&statuses), vec![0xfd, 0x00]);
+        assert!(success_bitmap(&[]).is_empty());
+
+        // Failure codes map to the verbose reasons
+        assert_eq!(failure_reason(FAILURE_INVALID_RECIPIENT), "Invalid recipient address");
+        assert_eq!(failure_reason(FAILURE_ETH_TRANSFER), "Transfer failed");
+        assert_eq!(failure_reason(FAILURE_TOKEN_RETURNED_FALSE), "Token transfer returned false");
+
+        // Compact batches record the same statistics as verbose ones
+        let paid = Address::from([0x10; 20]);
+        let call = IERC20::transferFromCall {
+            from: sender,
+            to: paid,
+            amount,
+        };
+        vm.mock_call(token, call.abi_encode(), Ok(true.abi_encode()));
+        let recipients = vec![paid, Address::ZERO];
+        let amounts = vec![amount; 2];
+        assert!(contract
+            .batch_send_token_with_mode(token, recipients.clone(), amounts.clone(), EVENTS_COMPACT)
+            .is_ok());
+        assert_eq!(contract.total_transactions(), U256::from(1));
+        assert_eq!(contract.total_recipients(), U256::from(1));
+
+        // Unknown modes are rejected
+        let result = contract.batch_send_token_with_mode(token, recipients.clone(), amounts, 2);
+        assert!(matches!(result, Err(Error::InvalidEventMode(_))));
+        let result = contract.batch_send_eth_with_mode(recipients, vec![amount; 2], 2);
+        assert!(matches!(result, Err(Error::InvalidEventMode(_))));
     }
 
-    /// Allow contract to receive ETH
-    #[payable]
-    pub fn receive_ether(&self) {
-        // This function allows the contract to receive ETH
+    #[test]
+    fn test_batch_results() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(sender);
+
+        let paid = Address::from([0x10; 20]);
+        let refused = Address::from([0x11; 20]);
+        for (recipient, response) in [(paid, true), (refused, false)] {
+            let call = IERC20::transferFromCall {
+                from: sender,
+                to: recipient,
+                amount,
+            };
+            vm.mock_call(token, call.abi_encode(), Ok(response.abi_encode()));
+        }
+
+        // Each recipient gets a status in input order and batch ids count up
+        let recipients = vec![paid, refused, Address::ZERO];
+        let amounts = vec![amount; 3];
+        let (batch_id, successes, statuses, refunded) = contract
+            .batch_send_token(token, recipients.clone(), amounts.clone())
+            .ok()
+            .unwrap();
+        assert_eq!(batch_id, U256::from(1));
+        assert_eq!(successes, U256::from(1));
+        assert_eq!(
+            statuses,
+            vec![TRANSFER_OK, FAILURE_TOKEN_RETURNED_FALSE, FAILURE_INVALID_RECIPIENT]
+        );
+        assert_eq!(refunded, U256::ZERO);
+
+        let (batch_id, ..) = contract
+            .batch_send_token_with_mode(token, recipients, amounts.clone(), EVENTS_COMPACT)
+            .ok()
+            .unwrap();
+        assert_eq!(batch_id, U256::from(2));
+
+        // Only failed amounts are refunded
+        let partial = [amount, U256::from(3), U256::from(4)];
+        assert_eq!(unpaid_amount(&statuses, &partial).ok(), Some(U256::from(7)));
+        assert_eq!(unpaid_amount(&[TRANSFER_OK; 3], &amounts).ok(), Some(U256::ZERO));
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use stylus_sdk::testing::*;
+    #[test]
+    fn test_batch_withdraw_to_l1() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        contract.constructor(vm.msg_sender());
 
-    #[no_mangle]
-    pub unsafe extern "C" fn emit_log(_pointer: *const u8, _len: usize, _: usize) {}
-    #[no_mangle]
-    pub unsafe extern "C" fn msg_sender(_sender: *mut u8) {}
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+        let amounts = vec![U256::from(100), U256::from(200)];
+        for (i, destination) in [alice, bob].into_iter().enumerate() {
+            let call = IArbSys::withdrawEthCall { destination };
+            let message_id = U256::from(40 + i).abi_encode();
+            vm.mock_call(ARB_SYS, call.abi_encode(), Ok(message_id));
+        }
+
+        // Invalid input reverts
+        vm.set_value(U256::from(300));
+        assert!(contract
+            .batch_withdraw_to_l1(vec![alice, Address::ZERO], amounts.clone())
+            .is_err());
+        assert!(contract.batch_withdraw_to_l1(vec![alice], amounts.clone()).is_err());
+        vm.set_value(U256::from(299));
+        let result = contract.batch_withdraw_to_l1(vec![alice, bob], amounts.clone());
+        assert!(matches!(result, Err(Error::InsufficientValue(_))));
+
+        // Each recipient gets its own message id
+        vm.set_value(U256::from(300));
+        let message_ids = contract
+            .batch_withdraw_to_l1(vec![alice, bob], amounts.clone())
+            .ok()
+            .unwrap();
+        assert_eq!(message_ids, vec![U256::from(40), U256::from(41)]);
+        assert_eq!(contract.total_transactions(), U256::from(1));
+        assert_eq!(contract.total_recipients(), U256::from(2));
+
+        // A rejected withdrawal reverts the whole batch
+        let call = IArbSys::withdrawEthCall { destination: bob };
+        vm.mock_call(ARB_SYS, call.abi_encode(), Err(vec![]));
+        let result = contract.batch_withdraw_to_l1(vec![alice, bob], amounts);
+        assert!(matches!(result, Err(Error::L1WithdrawalFailed(_))));
+    }
 
     #[test]
-    fn test_multisender() {
+    fn test_batch_keys() {
         let vm = TestVM::default();
         let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let recipient = Address::from([0x10; 20]);
+        let amount = U256::from(10);
+        contract.constructor(sender);
 
-        // Test initialization
-        let owner_addr = Address::from([1u8; 20]);
-        contract.constructor(owner_addr);
+        let call = IERC20::transferFromCall {
+            from: sender,
+            to: recipient,
+            amount,
+        };
+        vm.mock_call(token, call.abi_encode(), Ok(true.abi_encode()));
 
-        assert_eq!(contract.owner(), owner_addr);
+        // A key pays once per sender, whatever the event mode
+        let key = B256::from([9u8; 32]);
+        assert!(!contract.is_batch_executed(sender, key));
+        assert!(contract
+            .batch_send_token_with_key(token, vec![recipient], vec![amount], EVENTS_COMPACT, key)
+            .is_ok());
+        assert!(contract.is_batch_executed(sender, key));
+        assert!(!contract.is_batch_executed(recipient, key));
+
+        let result = contract.batch_send_token_with_key(
+            token,
+            vec![recipient],
+            vec![amount],
+            EVENTS_VERBOSE,
+            key,
+        );
+        assert!(matches!(result, Err(Error::BatchAlreadyExecuted(_))));
+        vm.set_value(amount);
+        let result =
+            contract.batch_send_eth_with_key(vec![recipient], vec![amount], EVENTS_VERBOSE, key);
+        assert!(matches!(result, Err(Error::BatchAlreadyExecuted(_))));
+        vm.set_value(U256::ZERO);
+        assert_eq!(contract.total_transactions(), U256::from(1));
+
+        // An invalid batch does not use up its key
+        let other = B256::from([8u8; 32]);
+        let result =
+            contract.batch_send_token_with_key(token, vec![], vec![], EVENTS_VERBOSE, other);
+        assert!(result.is_err());
+        assert!(!contract.is_batch_executed(sender, other));
+
+        // Other keys and the zero key are not blocked
+        assert!(contract
+            .batch_send_token_with_key(token, vec![recipient], vec![amount], EVENTS_VERBOSE, other)
+            .is_ok());
+        for _ in 0..2 {
+            assert!(contract
+                .batch_send_token_with_key(
+                    token,
+                    vec![recipient],
+                    vec![amount],
+                    EVENTS_VERBOSE,
+                    B256::ZERO,
+                )
+                .is_ok());
+        }
+        assert!(!contract.is_batch_executed(sender, B256::ZERO));
+        assert_eq!(contract.total_transactions(), U256::from(4));
+
+        // Group sends share the same keys
+        let group_id = contract.create_group("team".to_string()).ok().unwrap();
+        assert!(contract
+            .add_group_members(group_id, vec![recipient], vec![amount])
+            .is_ok());
+        let result =
+            contract.batch_send_token_to_group_with_key(token, group_id, U256::from(1), key);
+        assert!(matches!(result, Err(Error::BatchAlreadyExecuted(_))));
+        let group_key = B256::from([7u8; 32]);
+        assert!(contract
+            .batch_send_token_to_group_with_key(token, group_id, U256::from(1), group_key)
+            .is_ok());
+        vm.set_value(amount);
+        let result = contract.batch_send_eth_to_group_with_key(group_id, group_key);
+        assert!(matches!(result, Err(Error::BatchAlreadyExecuted(_))));
+
+        // So do L1 withdrawals
+        let l1_call = IArbSys::withdrawEthCall { destination: recipient };
+        vm.mock_call(ARB_SYS, l1_call.abi_encode(), Ok(U256::from(1).abi_encode()));
+        let l1_key = B256::from([6u8; 32]);
+        assert!(contract
+            .batch_withdraw_to_l1_with_key(vec![recipient], vec![amount], l1_key)
+            .is_ok());
+        let result = contract.batch_withdraw_to_l1_with_key(vec![recipient], vec![amount], l1_key);
+        assert!(matches!(result, Err(Error::BatchAlreadyExecuted(_))));
+        vm.set_value(U256::ZERO);
+
+        // A retried append does not list the recipients twice
+        let batch_id = U256::from(3);
+        let append_key = B256::from([5u8; 32]);
+        for _ in 0..2 {
+            let _ =
+                contract.append_batch_with_key(batch_id, vec![recipient], vec![amount], append_key);
+        }
+        let (_, _, item_count, total_amount, _, _, _) = contract.batch_progress(batch_id);
+        assert_eq!((item_count, total_amount), (U256::from(1), amount));
+        let result =
+            contract.append_batch_with_key(batch_id, vec![recipient], vec![amount], append_key);
+        assert!(matches!(result, Err(Error::BatchAlreadyExecuted(_))));
+    }
+
+    #[test]
+    fn test_sender_limits() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let recipient = Address::from([0x10; 20]);
+        contract.constructor(sender);
+        vm.set_block_timestamp(1_000);
+
+        for amount in [10u64, 100] {
+            let call = IERC20::transferFromCall {
+                from: sender,
+                to: recipient,
+                amount: U256::from(amount),
+            };
+            vm.mock_call(token, call.abi_encode(), Ok(true.abi_encode()));
+        }
+        let ten = vec![U256::from(10)];
+        let hundred = vec![U256::from(100)];
+
+        // First limits apply at once
+        assert!(contract
+            .set_sender_limits(token, U256::from(50), U256::from(50), U256::from(2))
+            .is_ok());
+        let limits = (U256::from(50), U256::from(50), U256::from(2));
+        assert_eq!(contract.sender_limits(sender, token), limits);
+        let unlimited = (U256::ZERO, U256::ZERO, U256::ZERO);
+        assert_eq!(contract.sender_limits(sender, NATIVE_ETH), unlimited);
+
+        // An extra zero is stopped
+        let result = contract.batch_send_token(token, vec![recipient], hundred.clone());
+        assert!(matches!(result, Err(Error::AmountAboveLimit(_))));
+        let amounts = vec![U256::from(10); 6];
+        let result = contract.batch_send_token(token, vec![recipient; 6], amounts);
+        assert!(matches!(result, Err(Error::BatchTotalAboveLimit(_))));
+
+        // The daily batch count resets the next day
+        assert!(contract.batch_send_token(token, vec![recipient], ten.clone()).is_ok());
+        assert!(contract.batch_send_token(token, vec![recipient], ten.clone()).is_ok());
+        let result = contract.batch_send_token(token, vec![recipient], ten.clone());
+        assert!(matches!(result, Err(Error::DailyBatchLimitReached(_))));
+        vm.set_block_timestamp(1_000 + LIMIT_RAISE_DELAY);
+        assert!(contract.batch_send_token(token, vec![recipient], ten.clone()).is_ok());
+
+        // Raising a limit waits for the delay
+        assert!(contract
+            .set_sender_limits(token, U256::from(500), U256::from(500), U256::from(2))
+            .is_ok());
+        assert_eq!(contract.sender_limits(sender, token).0, U256::from(50));
+        let (_, _, _, effective_at) = contract.pending_sender_limits(sender, token);
+        assert_eq!(effective_at, U256::from(1_000 + 2 * LIMIT_RAISE_DELAY));
+        let result = contract.apply_sender_limits(token);
+        assert!(matches!(result, Err(Error::LimitsNotYetEffective(_))));
+
+        vm.set_block_timestamp(1_000 + 2 * LIMIT_RAISE_DELAY);
+        assert!(contract.apply_sender_limits(token).is_ok());
+        assert_eq!(contract.sender_limits(sender, token).0, U256::from(500));
+        assert!(contract.batch_send_token(token, vec![recipient], hundred).is_ok());
+        let result = contract.apply_sender_limits(token);
+        assert!(matches!(result, Err(Error::NoPendingLimits(_))));
+
+        // Removing a limit counts as raising it, tightening cancels the queued raise
+        assert!(contract
+            .set_sender_limits(token, U256::ZERO, U256::from(500), U256::from(2))
+            .is_ok());
+        assert!(contract
+            .set_sender_limits(token, U256::from(20), U256::from(500), U256::from(2))
+            .is_ok());
+        assert_eq!(contract.sender_limits(sender, token).0, U256::from(20));
+        assert_eq!(contract.pending_sender_limits(sender, token).3, U256::ZERO);
+
+        // A mixed update tightens at once and only waits for the raise
+        assert!(contract
+            .set_sender_limits(token, U256::from(5), U256::from(1_000), U256::from(2))
+            .is_ok());
+        let limits = (U256::from(5), U256::from(500), U256::from(2));
+        assert_eq!(contract.sender_limits(sender, token), limits);
+        let pending = contract.pending_sender_limits(sender, token);
+        assert_eq!((pending.0, pending.1), (U256::from(5), U256::from(1_000)));
+        assert!(pending.3 > U256::ZERO);
+
+        // Ledger credits are limited too, but withdrawing one's own balance is not
+        let result = contract.batch_credit(token, vec![recipient], ten.clone());
+        assert!(matches!(result, Err(Error::AmountAboveLimit(_))));
+        assert!(contract.credit_ledger(token, sender, U256::from(10)).is_ok());
+        assert!(contract.reserve_funds(token, U256::from(10)).is_ok());
+        let push = IERC20::transferCall {
+            to: sender,
+            amount: U256::from(10),
+        };
+        vm.mock_call(token, push.abi_encode(), Ok(true.abi_encode()));
+        assert!(contract.withdraw(token, U256::from(10)).is_ok());
+        assert_eq!(contract.ledger_balance(token, sender), U256::ZERO);
+
+        // So are L1 withdrawals and stored batches
+        assert!(contract
+            .set_sender_limits(NATIVE_ETH, U256::from(5), U256::ZERO, U256::ZERO)
+            .is_ok());
+        vm.set_value(U256::from(10));
+        let result = contract.batch_withdraw_to_l1(vec![recipient], ten.clone());
+        assert!(matches!(result, Err(Error::AmountAboveLimit(_))));
+        assert!(contract.append_batch(U256::from(1), vec![recipient], ten).is_ok());
+        let result = contract.fund_batch(U256::from(1));
+        assert!(matches!(result, Err(Error::AmountAboveLimit(_))));
+    }
+
+    #[test]
+    fn test_partial_batches() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(sender);
+
+        // A reserve above the gas left stops before the first recipient
+        let recipients = vec![Address::from([0x10; 20]), Address::from([0x11; 20])];
+        let amounts = vec![amount, amount];
+        let (_, successes, statuses, refunded, next_index) = contract
+            .batch_send_token_partial(token, recipients.clone(), amounts.clone(), u64::MAX)
+            .ok()
+            .unwrap();
+        assert_eq!(successes, U256::ZERO);
+        assert!(statuses.is_empty());
+        assert_eq!(refunded, U256::ZERO);
+        assert_eq!(next_index, U256::ZERO);
+
+        // With gas to spare, recipient calls get the gas above the reserve, not all of it
+        let reserve = 150_000;
+        vm.set_gas_left(1_000_000);
+        assert_eq!(contract.call_gas(reserve), 850_000);
+        assert_eq!(contract.call_gas(0), u64::MAX);
+        vm.set_value(amount * U256::from(2));
+        let (_, successes, statuses, refunded, next_index) = contract
+            .batch_send_eth_partial(recipients.clone(), amounts.clone(), reserve)
+            .ok()
+            .unwrap();
+        assert_eq!(successes, U256::from(2));
+        assert_eq!(statuses, vec![TRANSFER_OK; 2]);
+        assert_eq!(refunded, U256::ZERO);
+        assert_eq!(next_index, U256::from(2));
+
+        // Token batches pull through the same capped calls and keep per-recipient codes
+        vm.set_value(U256::ZERO);
+        let declined = IERC20::transferFromCall { from: sender, to: recipients[1], amount };
+        vm.mock_call(token, declined.abi_encode(), Ok(false.abi_encode()));
+        let (_, successes, statuses, _, next_index) = contract
+            .batch_send_token_partial(token, recipients.clone(), amounts.clone(), reserve)
+            .ok()
+            .unwrap();
+        assert_eq!(successes, U256::from(1));
+        assert_eq!(statuses, vec![TRANSFER_OK, FAILURE_TOKEN_RETURNED_FALSE]);
+        assert_eq!(next_index, U256::from(2));
+        vm.set_gas_left(u64::MAX);
+
+        // Partial ETH batches validate the whole input up front
+        vm.set_value(amount);
+        let result = contract.batch_send_eth_partial(recipients.clone(), amounts, u64::MAX);
+        assert!(matches!(result, Err(Error::InsufficientValue(_))));
+        let result = contract.batch_send_token_partial(token, recipients, vec![amount], 0);
+        assert!(matches!(result, Err(Error::ArrayLengthMismatch(_))));
+
+        // Only processed recipients count towards the logged total
+        let amounts = [U256::from(1), U256::from(2), U256::from(4)];
+        let statuses = [TRANSFER_OK, FAILURE_ETH_TRANSFER];
+        assert_eq!(processed_amount(&statuses, &amounts).ok(), Some(U256::from(3)));
+        assert_eq!(processed_amount(&[], &amounts).ok(), Some(U256::ZERO));
+
+        // Sums that would wrap fail instead
+        let amounts = [U256::MAX, U256::from(1)];
+        let result = processed_amount(&[TRANSFER_OK; 2], &amounts);
+        assert!(matches!(result, Err(Error::ArithmeticOverflow(_))));
+        let result = unpaid_amount(&[FAILURE_ETH_TRANSFER; 2], &amounts);
+        assert!(matches!(result, Err(Error::ArithmeticOverflow(_))));
+    }
+
+    #[test]
+    fn test_invoices() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let payer = vm.msg_sender();
+        let alice = Address::from([2u8; 20]);
+        let bob = Address::from([3u8; 20]);
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(payer);
+
+        // Contractors bill the payer
+        vm.set_sender(alice);
+        let reference = "INV-001".to_string();
+        let first = contract
+            .create_invoice(payer, token, amount, U256::from(1_000), reference.clone())
+            .ok()
+            .unwrap();
+        assert!(contract
+            .create_invoice(Address::ZERO, token, amount, U256::ZERO, String::new())
+            .is_err());
+        assert!(contract
+            .create_invoice(payer, token, U256::ZERO, U256::ZERO, String::new())
+            .is_err());
+        vm.set_sender(bob);
+        let second = contract
+            .create_invoice(payer, token, amount, U256::ZERO, String::new())
+            .ok()
+            .unwrap();
+        let cancelled = contract
+            .create_invoice(payer, token, amount, U256::ZERO, String::new())
+            .ok()
+            .unwrap();
+        assert_eq!(contract.invoice_count(), U256::from(3));
+        let (issuer, invoice_payer, _, invoice_amount, _, invoice_reference, status) =
+            contract.invoice_info(first);
+        assert_eq!((issuer, invoice_payer, invoice_amount), (alice, payer, amount));
+        assert_eq!((invoice_reference, status), (reference, INVOICE_OPEN));
+
+        // Only the issuer cancels
+        assert!(matches!(
+            contract.cancel_invoice(first),
+            Err(Error::UnauthorizedInvoiceIssuer(_))
+        ));
+        assert!(contract.cancel_invoice(cancelled).is_ok());
+        assert_eq!(contract.invoice_info(cancelled).6, INVOICE_CANCELLED);
+
+        // Only the payer settles
+        let result = contract.pay_invoices(vec![first, second]);
+        assert!(matches!(result, Err(Error::UnauthorizedInvoicePayer(_))));
+
+        for issuer in [alice, bob] {
+            let call = IERC20::transferFromCall {
+                from: payer,
+                to: issuer,
+                amount,
+            };
+            vm.mock_call(token, call.abi_encode(), Ok(true.abi_encode()));
+        }
+        vm.set_sender(payer);
+        let result = contract.pay_invoices(vec![cancelled]);
+        assert!(matches!(result, Err(Error::InvoiceNotOpen(_))));
+        let result = contract.pay_invoices(vec![U256::from(9)]);
+        assert!(matches!(result, Err(Error::UnknownInvoice(_))));
+        assert_eq!(contract.pay_invoices(vec![first, second]).ok(), Some(U256::from(2)));
+        assert_eq!(contract.invoice_info(first).6, INVOICE_PAID);
+        assert_eq!(contract.invoice_info(second).6, INVOICE_PAID);
+        assert_eq!(contract.total_recipients(), U256::from(2));
+
+        // Paid invoices cannot be paid again
+        let result = contract.pay_invoices(vec![first]);
+        assert!(matches!(result, Err(Error::InvoiceNotOpen(_))));
+
+        // ETH invoices are paid from the sent value
+        vm.set_sender(alice);
+        let exact = contract
+            .create_invoice(payer, NATIVE_ETH, amount, U256::ZERO, String::new())
+            .ok()
+            .unwrap();
+        let with_excess = contract
+            .create_invoice(payer, NATIVE_ETH, amount, U256::ZERO, String::new())
+            .ok()
+            .unwrap();
+        let underpaid = contract
+            .create_invoice(payer, NATIVE_ETH, amount, U256::ZERO, String::new())
+            .ok()
+            .unwrap();
+        vm.set_sender(payer);
+        vm.set_value(amount);
+        vm.set_balance(vm.contract_address(), amount);
+        assert_eq!(contract.pay_invoices(vec![exact]).ok(), Some(U256::from(1)));
+        assert_eq!(contract.invoice_info(exact).6, INVOICE_PAID);
+
+        // Excess value goes back to the payer rather than into the ledger
+        vm.set_value(U256::from(25));
+        vm.set_balance(vm.contract_address(), U256::from(25));
+        assert_eq!(contract.pay_invoices(vec![with_excess]).ok(), Some(U256::from(1)));
+        assert_eq!(vm.balance(payer), U256::from(15));
+        assert_eq!(contract.invoice_info(with_excess).6, INVOICE_PAID);
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, payer), U256::ZERO);
+        assert_eq!(contract.reserved_eth.get(), U256::ZERO);
+
+        // Too little value for the ETH invoices fails
+        vm.set_value(amount - U256::from(1));
+        let result = contract.pay_invoices(vec![underpaid]);
+        assert!(matches!(
+            result,
+            Err(Error::InsufficientValue(InsufficientValue { required, provided }))
+                if required == amount && provided == amount - U256::from(1)
+        ));
+        assert_eq!(contract.total_recipients(), U256::from(4));
+    }
+
+    #[test]
+    fn test_batch_collect_token() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let collector = vm.msg_sender();
+        let treasury = Address::from([9u8; 20]);
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(collector);
+
+        let members: Vec<Address> = (0x10..0x14u8).map(|i| Address::from([i; 20])).collect();
+        for (i, &member) in members.iter().enumerate() {
+            let call = IERC20::transferFromCall {
+                from: member,
+                to: treasury,
+                amount,
+            };
+            let response = if i == 2 { Ok(false.abi_encode()) } else { Ok(true.abi_encode()) };
+            vm.mock_call(token, call.abi_encode(), response);
+        }
+
+        // Members 0 and 2 authorize a limited amount, member 1 no limit, member 3 nothing
+        for (member, allowance) in [(0, amount), (1, U256::MAX), (2, amount)] {
+            vm.set_sender(members[member]);
+            contract.approve_collector(collector, token, allowance);
+        }
+        vm.set_sender(collector);
+        assert_eq!(contract.collector_allowance(members[0], collector, token), amount);
+
+        let (_, successes, statuses, _) = contract
+            .batch_collect_token(token, members.clone(), vec![amount; 4], treasury)
+            .ok()
+            .unwrap();
+        assert_eq!(successes, U256::from(2));
+        let expected = [TRANSFER_OK, TRANSFER_OK, FAILURE_TOKEN_RETURNED_FALSE];
+        assert_eq!(statuses[..3], expected);
+        assert_eq!(statuses[3], FAILURE_COLLECTOR_ALLOWANCE);
+
+        // Limited allowances are spent, failed pulls give them back
+        assert_eq!(contract.collector_allowance(members[0], collector, token), U256::ZERO);
+        assert_eq!(contract.collector_allowance(members[1], collector, token), U256::MAX);
+        assert_eq!(contract.collector_allowance(members[2], collector, token), amount);
+
+        // Other callers cannot use the authorization
+        vm.set_sender(treasury);
+        let (_, successes, _, _) = contract
+            .batch_collect_token(token, vec![members[1]], vec![amount], treasury)
+            .ok()
+            .unwrap();
+        assert_eq!(successes, U256::ZERO);
+
+        // Invalid input reverts
+        let result = contract.batch_collect_token(token, members.clone(), vec![amount], treasury);
+        assert!(matches!(result, Err(Error::ArrayLengthMismatch(_))));
+        let amounts = vec![amount; 4];
+        let result = contract.batch_collect_token(token, members.clone(), amounts, Address::ZERO);
+        assert!(matches!(result, Err(Error::InvalidRecipient(_))));
+        let result = contract.batch_collect_token_with_permit(
+            token,
+            members.clone(),
+            vec![amount; 4],
+            treasury,
+            vec![U256::ZERO; 4],
+            vec![Bytes::from(vec![]); 3],
+            vec![Bytes::from(vec![]); 4],
+        );
+        assert!(matches!(result, Err(Error::ArrayLengthMismatch(_))));
+
+        // A member who only signed a permit and an authorization is collected
+        vm.set_sender(collector);
+        vm.set_block_timestamp(1_000);
+        let payer = members[3];
+        let deadline = U256::from(2_000);
+        let mut signature = vec![1u8; 32];
+        signature.extend_from_slice(&[2u8; 32]);
+        signature.push(27);
+        let (r, s) = (B256::from([1u8; 32]), B256::from([2u8; 32]));
+        let permit = IERC20Permit::permitCall {
+            owner: payer,
+            spender: vm.contract_address(),
+            value: amount,
+            deadline,
+            v: 27,
+            r,
+            s,
+        };
+        vm.mock_call(token, permit.abi_encode(), Ok(vec![]));
+        let authorization = (payer, collector, token, amount, deadline);
+        let digest = contract.collector_digest(authorization, U256::ZERO);
+        let recover = (digest, U256::from(27), r, s).abi_encode();
+        vm.mock_static_call(ECRECOVER, recover, Ok(payer.abi_encode()));
+
+        let collect = |contract: &mut MultiSender, signature: Vec<u8>| {
+            contract.batch_collect_token_with_permit(
+                token,
+                vec![payer],
+                vec![amount],
+                treasury,
+                vec![deadline],
+                vec![signature.clone().into()],
+                vec![signature.into()],
+            )
+        };
+        let (_, successes, statuses, _) = collect(&mut contract, signature.clone()).ok().unwrap();
+        assert_eq!((successes, statuses), (U256::from(1), vec![TRANSFER_OK]));
+        assert_eq!(contract.collector_nonce(payer), U256::from(1));
+        assert_eq!(contract.collector_allowance(payer, collector, token), U256::ZERO);
+
+        // Signatures are used once, must be well formed and expire
+        let result = collect(&mut contract, signature.clone());
+        assert!(matches!(result, Err(Error::InvalidCollectorSignature(_))));
+        let result = collect(&mut contract, signature[..64].to_vec());
+        assert!(matches!(result, Err(Error::InvalidCollectorSignature(_))));
+        vm.set_block_timestamp(2_001);
+        let result =
+            contract.approve_collector_by_sig(payer, collector, token, amount, deadline, 27, r, s);
+        assert!(matches!(result, Err(Error::CollectorAuthorizationExpired(_))));
+    }
+
+    #[test]
+    fn test_swap_and_distribute() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let owner = vm.msg_sender();
+        let router = Address::from([6u8; 20]);
+        let weth = Address::from([7u8; 20]);
+        let token_a = Address::from([4u8; 20]);
+        let token_b = Address::from([5u8; 20]);
+        let recipients = vec![Address::from([2u8; 20]), Address::from([3u8; 20])];
+        let weights = vec![U256::from(1), U256::from(3)];
+        let (min_out, deadline) = (U256::from(100), U256::MAX);
+        contract.constructor(owner);
+
+        // Swaps need a router, which only the owner sets
+        vm.set_value(U256::from(50));
+        let result = contract.swap_eth_and_distribute(
+            token_b,
+            recipients.clone(),
+            weights.clone(),
+            min_out,
+            deadline,
+        );
+        assert!(matches!(result, Err(Error::SwapRouterNotSet(_))));
+        vm.set_sender(Address::from([9u8; 20]));
+        assert!(contract.set_swap_router(router).is_err());
+        vm.set_sender(owner);
+        assert!(contract.set_swap_router(router).is_ok());
+        assert_eq!(contract.swap_router(), router);
+
+        // Invalid input reverts
+        let targets = recipients.clone();
+        let result = contract.swap_eth_and_distribute(token_b, targets, vec![], min_out, deadline);
+        assert!(matches!(result, Err(Error::ArrayLengthMismatch(_))));
+        let (targets, zero_weights) = (recipients.clone(), vec![U256::ZERO; 2]);
+        let result =
+            contract.swap_eth_and_distribute(token_b, targets, zero_weights, min_out, deadline);
+        assert!(matches!(result, Err(Error::InvalidAmount(_))));
+        let result = contract.swap_token_and_distribute(
+            token_b,
+            U256::from(50),
+            token_b,
+            recipients.clone(),
+            weights.clone(),
+            min_out,
+            deadline,
+        );
+        assert!(matches!(result, Err(Error::InvalidRecipient(_))));
+
+        // A router that delivers less than `min_out` reverts the whole swap. The `mock-router`
+        // crate pays at a configurable rate on a devnode; here its payout never arrives.
+        let this = vm.contract_address();
+        let balance_call = IERC20::balanceOfCall { account: this };
+        vm.mock_static_call(token_b, balance_call.abi_encode(), Ok(U256::ZERO.abi_encode()));
+        let weth_call = IUniswapV2Router::WETHCall {};
+        vm.mock_static_call(router, weth_call.abi_encode(), Ok(weth.abi_encode()));
+        let swap = IUniswapV2Router::swapExactETHForTokensCall {
+            amountOutMin: min_out,
+            path: vec![weth, token_b],
+            to: this,
+            deadline,
+        };
+        let amounts = vec![U256::from(50), min_out];
+        vm.mock_call(router, swap.abi_encode(), Ok(amounts.abi_encode()));
+        let result = contract.swap_eth_and_distribute(
+            token_b,
+            recipients.clone(),
+            weights.clone(),
+            min_out,
+            deadline,
+        );
+        assert!(matches!(result, Err(Error::InsufficientSwapOutput(_))));
+
+        // Router reverts surface as swap failures
+        vm.mock_call(router, swap.abi_encode(), Err(vec![]));
+        let result = contract.swap_eth_and_distribute(
+            token_b,
+            recipients.clone(),
+            weights.clone(),
+            min_out,
+            deadline,
+        );
+        assert!(matches!(result, Err(Error::SwapFailed(_))));
+
+        // Token input is pulled from the sender before swapping
+        vm.set_value(U256::ZERO);
+        let balance_call = IERC20::balanceOfCall { account: this };
+        vm.mock_static_call(token_a, balance_call.abi_encode(), Ok(U256::ZERO.abi_encode()));
+        let pull = IERC20::transferFromCall {
+            from: owner,
+            to: this,
+            amount: U256::from(50),
+        };
+        vm.mock_call(token_a, pull.abi_encode(), Ok(false.abi_encode()));
+        let result = contract.swap_token_and_distribute(
+            token_a,
+            U256::from(50),
+            token_b,
+            recipients,
+            weights,
+            min_out,
+            deadline,
+        );
+        assert!(matches!(result, Err(Error::TokenTransferFailed(_))));
         assert_eq!(contract.total_transactions(), U256::ZERO);
-        assert_eq!(contract.total_recipients(), U256::ZERO);
+    }
 
-        // Test gas estimation
-        let gas_estimate = contract.estimate_batch_eth_gas(U256::from(5));
-        assert!(gas_estimate > U256::ZERO);
+    #[test]
+    fn test_erc1363_distribution() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let owner = vm.msg_sender();
+        let holder = Address::from([9u8; 20]);
+        let token = Address::from([4u8; 20]);
+        let amount = U256::from(10);
+        contract.constructor(owner);
+
+        let recipients = vec![Address::from([2u8; 20]), Address::from([3u8; 20])];
+        for (i, &recipient) in recipients.iter().enumerate() {
+            let call = IERC20::transferCall { to: recipient, amount };
+            let response = if i == 1 { Ok(false.abi_encode()) } else { Ok(true.abi_encode()) };
+            vm.mock_call(token, call.abi_encode(), response);
+        }
+        let refund = IERC20::transferCall { to: holder, amount };
+        vm.mock_call(token, refund.abi_encode(), Ok(true.abi_encode()));
+        let data: Bytes = (recipients.clone(), vec![amount; 2]).abi_encode_params().into();
+        let total = amount * U256::from(2);
+        let balance = IERC20::balanceOfCall { account: vm.contract_address() }.abi_encode();
+        vm.mock_static_call(token, balance, Ok(total.abi_encode()));
+
+        // Only tokens accepted by the owner can call the hook
+        vm.set_sender(token);
+        let result = contract.on_transfer_received(holder, holder, total, data.clone());
+        assert!(matches!(result, Err(Error::UnsupportedToken(_))));
+        assert!(contract.set_erc1363_token(token, true).is_err());
+        vm.set_sender(owner);
+        assert!(contract.set_erc1363_token(token, true).is_ok());
+        assert!(contract.is_erc1363_token(token));
+
+        // The received tokens are paid out and the failed share goes back to the holder
+        vm.set_sender(token);
+        let result = contract.on_transfer_received(holder, holder, total, data.clone());
+        let selector = IERC1363Receiver::onTransferReceivedCall::SELECTOR;
+        assert_eq!(result.ok(), Some(FixedBytes::from(selector)));
+        assert_eq!(contract.total_recipients(), U256::from(1));
+        assert_eq!(contract.user_transaction_count(holder), U256::from(1));
+        assert_eq!(contract.ledger_balance(token, holder), U256::ZERO);
+
+        // A failed share that cannot go back stays claimable in the holder's ledger
+        vm.mock_call(token, refund.abi_encode(), Ok(false.abi_encode()));
+        let result = contract.on_transfer_received(holder, holder, total, data.clone());
+        assert!(result.is_ok());
+        assert_eq!(contract.ledger_balance(token, holder), amount);
+        assert_eq!(contract.reserved_tokens.get(token), amount);
+
+        // A reported value the unreserved balance does not cover is rejected
+        let result = contract.on_transfer_received(holder, holder, total, data.clone());
+        assert!(matches!(
+            result,
+            Err(Error::TransferAmountMismatch(e)) if e.received == amount
+        ));
+
+        // Malformed data and amounts that do not add up revert the transfer
+        let garbage = Bytes::from(vec![0x01, 0x02]);
+        let result = contract.on_transfer_received(holder, holder, amount, garbage);
+        assert!(matches!(result, Err(Error::InvalidTransferData(_))));
+        let result = contract.on_transfer_received(holder, holder, amount, data);
+        assert!(matches!(result, Err(Error::TransferAmountMismatch(_))));
+        let mismatched: Bytes = (recipients, vec![amount]).abi_encode_params().into();
+        let result = contract.on_transfer_received(holder, holder, amount, mismatched);
+        assert!(matches!(result, Err(Error::ArrayLengthMismatch(_))));
+        assert_eq!(contract.total_transactions(), U256::from(2));
+    }
+
+    #[test]
+    fn test_raffle() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        let other = Address::from([9u8; 20]);
+        contract.constructor(sender);
+
+        let participants: Vec<Address> = (0x10..0x15u8).map(|i| Address::from([i; 20])).collect();
+        let prizes = vec![U256::from(30), U256::from(20)];
+        let seed = B256::from([1u8; 32]);
+        let commitment = contract.raffle_commitment(sender, seed);
+        let block_number = IArbSys::arbBlockNumberCall {}.abi_encode();
+        vm.mock_static_call(ARB_SYS, block_number.clone(), Ok(U256::from(100).abi_encode()));
+
+        // Invalid raffles revert
+        vm.set_value(U256::from(50));
+        let result = contract.create_raffle(participants[..1].to_vec(), prizes.clone(), commitment);
+        assert!(matches!(result, Err(Error::TooManyWinners(_))));
+        let crowd = vec![participants[0]; MAX_RAFFLE_PARTICIPANTS + 1];
+        let result = contract.create_raffle(crowd, prizes.clone(), commitment);
+        assert!(matches!(result, Err(Error::TooManyParticipants(_))));
+        let result = contract.create_raffle(participants.clone(), prizes.clone(), B256::ZERO);
+        assert!(matches!(result, Err(Error::InvalidAmount(_))));
+        vm.set_value(U256::from(49));
+        let result = contract.create_raffle(participants.clone(), prizes.clone(), commitment);
+        assert!(matches!(result, Err(Error::InsufficientValue(_))));
+
+        // The sender can cancel until the draw block, and a commitment is only used once
+        vm.set_value(U256::from(60));
+        vm.set_balance(vm.contract_address(), U256::from(60));
+        let cancelled = contract
+            .create_raffle(participants.clone(), prizes.clone(), commitment)
+            .ok()
+            .unwrap();
+        vm.set_value(U256::ZERO);
+        assert_eq!(contract.reserved_eth.get(), U256::from(50));
+        vm.set_sender(other);
+        let result = contract.cancel_raffle(cancelled);
+        assert!(matches!(result, Err(Error::UnauthorizedRaffleSender(_))));
+        vm.set_sender(sender);
+        vm.set_balance(vm.contract_address(), U256::ZERO);
+        assert!(contract.cancel_raffle(cancelled).is_ok());
+        assert_eq!(contract.raffle_info(cancelled).5, RAFFLE_CANCELLED);
+
+        // A sender that cannot take the ETH back keeps it claimable in the ledger
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, sender), U256::from(50));
+        assert_eq!(contract.reserved_eth.get(), U256::from(50));
+        vm.set_balance(vm.contract_address(), U256::from(50));
+        assert!(contract.withdraw(NATIVE_ETH, U256::from(50)).is_ok());
+        assert_eq!(contract.reserved_eth.get(), U256::ZERO);
+        let result = contract.cancel_raffle(cancelled);
+        assert!(matches!(result, Err(Error::RaffleNotOpen(_))));
+        vm.set_value(U256::from(50));
+        let result = contract.create_raffle(participants.clone(), prizes.clone(), commitment);
+        assert!(matches!(result, Err(Error::RaffleCommitmentUsed(_))));
+
+        let seed = B256::from([3u8; 32]);
+        let commitment = contract.raffle_commitment(sender, seed);
+        vm.set_balance(vm.contract_address(), U256::from(50));
+        let raffle_id = contract
+            .create_raffle(participants.clone(), prizes.clone(), commitment)
+            .ok()
+            .unwrap();
+        assert_eq!(contract.raffle_count(), U256::from(2));
+        let (_, pool, count, _, draw_block, status) = contract.raffle_info(raffle_id);
+        assert_eq!((pool, count, draw_block), (U256::from(50), U256::from(5), U256::from(110)));
+        assert_eq!(status, RAFFLE_OPEN);
+        vm.set_value(U256::ZERO);
+
+        // The seed cannot be revealed before the draw block is final
+        let result = contract.reveal_raffle(raffle_id, seed);
+        assert!(matches!(result, Err(Error::RaffleNotDrawable(_))));
+
+        // Once it is, only the committed seed draws, and the sender can no longer cancel
+        let block_hash = B256::from([7u8; 32]);
+        let hash_call = IArbSys::arbBlockHashCall { arbBlockNum: draw_block };
+        vm.mock_static_call(ARB_SYS, hash_call.abi_encode(), Ok(block_hash.abi_encode()));
+        vm.mock_static_call(ARB_SYS, block_number.clone(), Ok(U256::from(111).abi_encode()));
+        let result = contract.reveal_raffle(raffle_id, B256::from([2u8; 32]));
+        assert!(matches!(result, Err(Error::InvalidRaffleSeed(_))));
+        let result = contract.cancel_raffle(raffle_id);
+        assert!(matches!(result, Err(Error::RaffleAwaitingReveal(_))));
+        let result = contract.settle_expired_raffle(raffle_id);
+        assert!(matches!(result, Err(Error::RaffleAwaitingReveal(_))));
+
+        let (_, winners, statuses, _) = contract.reveal_raffle(raffle_id, seed).ok().unwrap();
+        assert_eq!(winners, draw_winners(seed, block_hash, participants.clone(), 2));
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(contract.raffle_info(raffle_id).5, RAFFLE_DRAWN);
+        let result = contract.reveal_raffle(raffle_id, seed);
+        assert!(matches!(result, Err(Error::RaffleNotOpen(_))));
+
+        // Unrevealed raffles expire once the reveal window has passed, and anyone can split
+        // the pool between the participants; the sender gets nothing back
+        let seed = B256::from([4u8; 32]);
+        let commitment = contract.raffle_commitment(sender, seed);
+        vm.set_value(U256::from(52));
+        let raffle_id = contract
+            .create_raffle(participants.clone(), vec![U256::from(32), U256::from(20)], commitment)
+            .ok()
+            .unwrap();
+        vm.set_value(U256::ZERO);
+        let reserved = contract.reserved_eth.get();
+        let expired = U256::from(111 + RAFFLE_DRAW_DELAY + RAFFLE_REVEAL_WINDOW + 1);
+        vm.mock_static_call(ARB_SYS, block_number, Ok(expired.abi_encode()));
+        let result = contract.reveal_raffle(raffle_id, seed);
+        assert!(matches!(result, Err(Error::RaffleDrawExpired(_))));
+        let result = contract.cancel_raffle(raffle_id);
+        assert!(matches!(result, Err(Error::RaffleDrawExpired(_))));
+
+        vm.set_sender(other);
+        assert!(contract.settle_expired_raffle(raffle_id).is_ok());
+        assert_eq!(contract.raffle_info(raffle_id).5, RAFFLE_EXPIRED);
+        let shares: Vec<U256> = participants
+            .iter()
+            .map(|&p| contract.ledger_balance(NATIVE_ETH, p))
+            .collect();
+        assert_eq!(shares, [11, 11, 10, 10, 10].map(U256::from));
+        assert_eq!(contract.ledger_balance(NATIVE_ETH, sender), U256::ZERO);
+        assert_eq!(contract.reserved_eth.get(), reserved);
+        let result = contract.settle_expired_raffle(raffle_id);
+        assert!(matches!(result, Err(Error::RaffleNotOpen(_))));
+
+        // Draws are deterministic and never pick the same entry twice
+        let winners = draw_winners(seed, block_hash, participants.clone(), 5);
+        let mut sorted = winners.clone();
+        sorted.sort();
+        assert_eq!(sorted, participants);
+        assert_eq!(winners, draw_winners(seed, block_hash, participants, 5));
+    }
+
+    /// Amounts biased towards values whose sum wraps around
+    fn adversarial_amount() -> impl Strategy<Value = U256> {
+        prop_oneof![
+            (1u64..1_000).prop_map(U256::from),
+            (0u64..1_000).prop_map(|x| U256::MAX - U256::from(x)),
+            Just(U256::from(1) << 255),
+            any::<[u64; 4]>().prop_map(|limbs| U256::from_limbs(limbs).max(U256::from(1))),
+        ]
+    }
+
+    fn exact_sum(amounts: &[U256]) -> Option<U256> {
+        amounts
+            .iter()
+            .try_fold(U256::ZERO, |total, amount| total.checked_add(*amount))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_batch_totals_never_wrap(
+            amounts in proptest::collection::vec(adversarial_amount(), 1..6)
+        ) {
+            let vm = TestVM::default();
+            let mut contract = MultiSender::from(&vm);
+            contract.constructor(vm.msg_sender());
+            let recipients = vec![Address::from([2u8; 20]); amounts.len()];
+            let overflows = exact_sum(&amounts).is_none();
+
+            // Without value every valid batch fails the value check instead
+            let result = contract.batch_send_eth(recipients.clone(), amounts.clone());
+            prop_assert_eq!(matches!(result, Err(Error::ArithmeticOverflow(_))), overflows);
+            prop_assert_eq!(matches!(result, Err(Error::InsufficientValue(_))), !overflows);
+
+            // Without a ledger balance every valid batch fails the debit instead
+            let result = contract.batch_credit(NATIVE_ETH, recipients.clone(), amounts.clone());
+            prop_assert_eq!(matches!(result, Err(Error::ArithmeticOverflow(_))), overflows);
+
+            let result = contract.append_batch(U256::from(1), recipients, amounts.clone());
+            prop_assert_eq!(matches!(result, Err(Error::ArithmeticOverflow(_))), overflows);
+            if overflows {
+                prop_assert_eq!(contract.batch_progress(U256::from(1)).3, U256::ZERO);
+            }
+
+            // Forwarded multicall values are summed the same way
+            prop_assert!(contract.set_multicall_target(Address::from([3u8; 20]), true).is_ok());
+            let calls = amounts
+                .iter()
+                .map(|value| (Address::from([3u8; 20]), *value, Bytes::from(vec![]), true))
+                .collect();
+            let result = contract.multicall(calls);
+            prop_assert_eq!(matches!(result, Err(Error::ArithmeticOverflow(_))), overflows);
+        }
+
+        #[test]
+        fn prop_group_scale_never_wraps(
+            amount in adversarial_amount(),
+            scale in adversarial_amount()
+        ) {
+            let vm = TestVM::default();
+            let mut contract = MultiSender::from(&vm);
+            contract.constructor(vm.msg_sender());
+            let group_id = contract.create_group("team".to_string()).ok().unwrap();
+            let member = Address::from([2u8; 20]);
+            prop_assert!(contract
+                .add_group_members(group_id, vec![member], vec![amount])
+                .is_ok());
+
+            // Only overflowing scales are sent, valid ones would reach the token
+            if amount.checked_mul(scale).is_none() {
+                let token = Address::from([4u8; 20]);
+                let result = contract.batch_send_token_to_group(token, group_id, scale);
+                prop_assert!(matches!(result, Err(Error::ArithmeticOverflow(_))));
+            }
+        }
+    }
+
+    #[test]
+    fn test_counter_overflow() {
+        let vm = TestVM::default();
+        let mut contract = MultiSender::from(&vm);
+        let sender = vm.msg_sender();
+        contract.constructor(sender);
+        assert!(contract.credit_ledger(NATIVE_ETH, sender, U256::from(10)).is_ok());
+
+        // Statistics counters refuse to wrap around
+        contract.total_transactions.set(U256::MAX);
+        let recipients = vec![Address::from([2u8; 20])];
+        let result = contract.batch_credit(NATIVE_ETH, recipients, vec![U256::from(1)]);
+        assert!(matches!(result, Err(Error::ArithmeticOverflow(_))));
+
+        // Ledger credits refuse to wrap around
+        assert!(matches!(
+            contract.credit_ledger(NATIVE_ETH, sender, U256::MAX),
+            Err(Error::ArithmeticOverflow(_))
+        ));
     }
 }
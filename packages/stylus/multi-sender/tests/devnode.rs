@@ -0,0 +1,548 @@
+//! Integration tests against a local nitro-devnode (see `tests-common/devnode.rs`)
+
+#[path = "../../tests-common/devnode.rs"]
+mod devnode;
+
+use ethers::contract::parse_log;
+use ethers::prelude::*;
+use eyre::Result;
+
+abigen!(
+    MultiSender,
+    r#"[
+        function owner() external view returns (address)
+        function totalTransactions() external view returns (uint256)
+        function totalRecipients() external view returns (uint256)
+        function userTransactionCount(address user) external view returns (uint256)
+        function batchSendEth(address[] recipients, uint256[] amounts) external payable returns (uint256, uint256, uint8[], uint256)
+        function batchSendToken(address token, address[] recipients, uint256[] amounts) external returns (uint256, uint256, uint8[], uint256)
+        function batchSendEthWithMode(address[] recipients, uint256[] amounts, uint8 eventMode) external payable returns (uint256, uint256, uint8[], uint256)
+        function batchSendEthPartial(address[] recipients, uint256[] amounts, uint64 gasReserve) external payable returns (uint256, uint256, uint8[], uint256, uint256)
+        function setWeth(address weth) external
+        function batchWithdrawToL1(address[] l1Recipients, uint256[] amounts) external payable returns (uint256[])
+        function depositEth() external payable
+        function depositToken(address token, uint256 amount) external
+        function withdraw(address token, uint256 amount) external
+        function ledgerBalance(address token, address account) external view returns (uint256)
+        function batchCredit(address token, address[] recipients, uint256[] amounts) external
+        function recoverErc20(address token, address to, uint256 amount) external
+        function emergencyWithdraw() external
+        function transferOwnership(address newOwner) external
+        function setSwapRouter(address router) external
+        function swapEthAndDistribute(address tokenOut, address[] recipients, uint256[] weights, uint256 minOut, uint256 deadline) external payable returns (uint256, uint256, uint8[])
+        function swapTokenAndDistribute(address tokenIn, uint256 amountIn, address tokenOut, address[] recipients, uint256[] weights, uint256 minOut, uint256 deadline) external returns (uint256, uint256, uint8[])
+        event BatchEthTransfer(address indexed sender, uint256 totalAmount, uint256 recipientCount)
+        event TransferSuccess(address indexed recipient, uint256 amount)
+        event L1WithdrawalInitiated(address indexed l1Recipient, uint256 amount, uint256 indexed messageId)
+        event WrappedTransferSuccess(address indexed recipient, uint256 amount)
+        event BatchStoppedEarly(address indexed sender, address indexed token, uint256 nextIndex, uint256 recipientCount)
+        event BatchTransferSummary(address indexed sender, address indexed token, uint256 totalAmount, uint256 recipientCount, uint256 successCount, bytes successBitmap, bytes wrappedBitmap)
+        error ArrayLengthMismatch()
+        error InsufficientLedgerBalance(address token, uint256 available, uint256 required)
+        error InsufficientRecoverableBalance(address token, uint256 available, uint256 required)
+    ]"#
+);
+
+abigen!(
+    MockErc20,
+    r#"[
+        function mint(address to, uint256 amount) external
+        function approve(address spender, uint256 amount) external returns (bool)
+        function balanceOf(address account) external view returns (uint256)
+        function setMode(uint8 mode) external
+        function setFeeBps(uint16 feeBps) external
+        function setReentry(address target, bytes data) external
+    ]"#
+);
+
+abigen!(
+    MockRouter,
+    r#"[
+        function setRate(uint256 numerator, uint256 denominator) external
+    ]"#
+);
+
+abigen!(
+    MockWeth,
+    r#"[
+        function balanceOf(address account) external view returns (uint256)
+    ]"#
+);
+
+#[tokio::test]
+async fn test_multi_sender_on_devnode() -> Result<()> {
+    let _turn = devnode::exclusive().await;
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = MultiSender::new(address, client.clone());
+    assert_eq!(contract.owner().call().await?, owner);
+
+    // Batch send ETH with some excess value, which is refunded
+    let recipients: Vec<Address> = (0..3).map(|_| Address::random()).collect();
+    let amounts: Vec<U256> = (1..=3u64).map(|i| U256::from(i * 1_000)).collect();
+
+    // Preview the per-recipient results with `eth_call`
+    let preview = contract
+        .batch_send_eth(recipients.clone(), amounts.clone())
+        .value(10_000)
+        .call()
+        .await?;
+    assert_eq!(preview, (U256::one(), U256::from(3), vec![0, 0, 0], U256::from(4_000)));
+
+    let receipt = contract
+        .batch_send_eth(recipients.clone(), amounts.clone())
+        .value(10_000)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+
+    for (recipient, amount) in recipients.iter().zip(&amounts) {
+        assert_eq!(client.get_balance(*recipient, None).await?, *amount);
+    }
+    assert_eq!(client.get_balance(address, None).await?, U256::zero());
+
+    // Events
+    let successes: Vec<TransferSuccessFilter> = receipt
+        .logs
+        .iter()
+        .cloned()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(successes.len(), 3);
+    let batches: Vec<BatchEthTransferFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(batches, vec![BatchEthTransferFilter {
+        sender: owner,
+        total_amount: U256::from(6_000),
+        recipient_count: U256::from(3),
+    }]);
+
+    // Statistics
+    assert_eq!(contract.total_transactions().call().await?, U256::one());
+    assert_eq!(contract.total_recipients().call().await?, U256::from(3));
+    assert_eq!(contract.user_transaction_count(owner).call().await?, U256::one());
+
+    // Reverts carry decodable errors
+    let err = contract
+        .batch_send_token(Address::random(), recipients.clone(), vec![U256::one()])
+        .send()
+        .await
+        .expect_err("mismatched arrays");
+    assert!(matches!(
+        err.decode_contract_revert::<MultiSenderErrors>(),
+        Some(MultiSenderErrors::ArrayLengthMismatch(_))
+    ));
+
+    // Ledger deposits are reserved and survive an emergency withdraw
+    contract.deposit_eth().value(500).send().await?.await?;
+    contract.emergency_withdraw().send().await?.await?;
+    assert_eq!(client.get_balance(address, None).await?, U256::from(500));
+    let err = contract
+        .withdraw(Address::zero(), U256::from(501))
+        .send()
+        .await
+        .expect_err("overdrawn ledger");
+    let decoded = err.decode_contract_revert::<MultiSenderErrors>();
+    assert!(matches!(
+        decoded,
+        Some(MultiSenderErrors::InsufficientLedgerBalance(e)) if e.available == U256::from(500)
+    ));
+    contract.withdraw(Address::zero(), U256::from(500)).send().await?.await?;
+    assert_eq!(contract.ledger_balance(Address::zero(), owner).call().await?, U256::zero());
+
+    // L1 withdrawals go through the devnode's ArbSys precompile, one message per recipient
+    let l1_recipients: Vec<Address> = (0..2).map(|_| Address::random()).collect();
+    let l1_amounts = vec![U256::from(700), U256::from(800)];
+    let receipt = contract
+        .batch_withdraw_to_l1(l1_recipients.clone(), l1_amounts.clone())
+        .value(1_500)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+    let withdrawals: Vec<L1WithdrawalInitiatedFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    let amounts: Vec<U256> = withdrawals.iter().map(|w| w.amount).collect();
+    assert_eq!(amounts, l1_amounts);
+    assert_eq!(withdrawals[1].message_id, withdrawals[0].message_id + 1);
+    assert_eq!(client.get_balance(address, None).await?, U256::zero());
+
+    // Only the owner transfers ownership; other callers are ignored
+    let other = devnode::funded_account(&client, U256::exp10(17)).await?;
+    let as_other = MultiSender::new(address, other.clone());
+    as_other.transfer_ownership(other.address()).send().await?.await?;
+    assert_eq!(contract.owner().call().await?, owner);
+    contract.transfer_ownership(other.address()).send().await?.await?;
+    assert_eq!(contract.owner().call().await?, other.address());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weth_fallback_on_devnode() -> Result<()> {
+    let _turn = devnode::exclusive().await;
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = MultiSender::new(address, client.clone());
+    let weth_address = devnode::deploy_crate("mock-weth", &[])?;
+    let weth = MockWeth::new(weth_address, client.clone());
+    contract.set_weth(weth_address).send().await?.await?;
+
+    // A Stylus contract without a receive function rejects plain ETH
+    let rejecting = devnode::deploy_crate("mock-erc20", &[])?;
+    let payee = Address::random();
+    let recipients = vec![payee, rejecting];
+    let amounts = vec![U256::from(1_000), U256::from(2_000)];
+    let receipt = contract
+        .batch_send_eth(recipients.clone(), amounts.clone())
+        .value(3_000)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+
+    assert_eq!(client.get_balance(payee, None).await?, U256::from(1_000));
+    assert_eq!(client.get_balance(rejecting, None).await?, U256::zero());
+    assert_eq!(weth.balance_of(rejecting).call().await?, U256::from(2_000));
+    let wrapped: Vec<WrappedTransferSuccessFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(wrapped, vec![WrappedTransferSuccessFilter {
+        recipient: rejecting,
+        amount: U256::from(2_000),
+    }]);
+
+    // Compact batches keep the WETH signal in the summary
+    let receipt = contract
+        .batch_send_eth_with_mode(recipients, amounts, 1)
+        .value(3_000)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+    let summaries: Vec<BatchTransferSummaryFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].success_bitmap.to_vec(), vec![0b11]);
+    assert_eq!(summaries[0].wrapped_bitmap.to_vec(), vec![0b10]);
+
+    // Nothing is left behind in the contract
+    assert_eq!(client.get_balance(address, None).await?, U256::zero());
+    assert_eq!(weth.balance_of(address).call().await?, U256::zero());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_erc20_on_devnode() -> Result<()> {
+    let _turn = devnode::exclusive().await;
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = MultiSender::new(address, client.clone());
+    let token_address = devnode::deploy_crate("mock-erc20", &[])?;
+    let token = MockErc20::new(token_address, client.clone());
+    token.mint(owner, U256::from(100_000)).send().await?.await?;
+    token.approve(address, U256::MAX).send().await?.await?;
+
+    // Each transfer mode maps to its failure code, and failed pulls stay with the sender
+    let recipients: Vec<Address> = (0..2).map(|_| Address::random()).collect();
+    let amounts = vec![U256::from(1_000), U256::from(2_000)];
+    let cases = [(0u8, 0u8), (1, 3), (2, 5), (3, 0)];
+    for (mode, status) in cases {
+        token.set_mode(mode).send().await?.await?;
+        let call = contract.batch_send_token(token_address, recipients.clone(), amounts.clone());
+        let (_, successes, statuses, refunded) = call.call().await?;
+        assert_eq!(statuses, vec![status, status]);
+        assert_eq!(successes, U256::from(if status == 0 { 2 } else { 0 }));
+        assert_eq!(refunded, U256::zero());
+        call.send().await?.await?;
+    }
+
+    // Standard and no-return tokens each paid the batch once
+    assert_eq!(token.balance_of(recipients[0]).call().await?, U256::from(2_000));
+    assert_eq!(token.balance_of(recipients[1]).call().await?, U256::from(4_000));
+    assert_eq!(token.balance_of(owner).call().await?, U256::from(94_000));
+    assert_eq!(token.balance_of(address).call().await?, U256::zero());
+
+    // Fee-on-transfer deposits credit what actually arrived
+    token.set_mode(0).send().await?.await?;
+    token.set_fee_bps(100).send().await?.await?;
+    contract
+        .deposit_token(token_address, U256::from(10_000))
+        .send()
+        .await?
+        .await?;
+    assert_eq!(token.balance_of(address).call().await?, U256::from(9_900));
+    assert_eq!(contract.ledger_balance(token_address, owner).call().await?, U256::from(9_900));
+    assert!(contract.withdraw(token_address, U256::from(9_901)).send().await.is_err());
+
+    // Withdrawing the credit empties the contract
+    token.set_fee_bps(0).send().await?.await?;
+    contract.withdraw(token_address, U256::from(9_900)).send().await?.await?;
+    assert_eq!(token.balance_of(address).call().await?, U256::zero());
+    assert_eq!(token.balance_of(owner).call().await?, U256::from(93_900));
+
+    // A token that re-enters the batch from transferFrom is refused by the reentrancy guard,
+    // so every pull fails with the token call code and nothing moves
+    let batch = contract.batch_send_token(token_address, recipients.clone(), amounts.clone());
+    let reentry = batch.calldata().expect("batch calldata");
+    token.set_reentry(address, reentry).send().await?.await?;
+    let (_, successes, statuses, _) = batch.call().await?;
+    assert_eq!(statuses, vec![5, 5]);
+    assert_eq!(successes, U256::zero());
+    batch.send().await?.await?;
+    assert_eq!(token.balance_of(recipients[0]).call().await?, U256::from(2_000));
+    assert_eq!(token.balance_of(recipients[1]).call().await?, U256::from(4_000));
+    assert_eq!(token.balance_of(owner).call().await?, U256::from(93_900));
+    assert_eq!(token.balance_of(address).call().await?, U256::zero());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_ledger_on_devnode() -> Result<()> {
+    let _turn = devnode::exclusive().await;
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = MultiSender::new(address, client.clone());
+    let token_address = devnode::deploy_crate("mock-erc20", &[])?;
+    let token = MockErc20::new(token_address, client.clone());
+    token.mint(owner, U256::from(10_000)).send().await?.await?;
+    token.approve(address, U256::MAX).send().await?.await?;
+
+    // Ledger credits move token balances without transfers
+    contract
+        .deposit_token(token_address, U256::from(6_000))
+        .send()
+        .await?
+        .await?;
+    let alice = devnode::funded_account(&client, U256::exp10(17)).await?;
+    let bob = Address::random();
+    let credit = contract.batch_credit(
+        token_address,
+        vec![alice.address(), bob],
+        vec![U256::from(1_000), U256::from(2_000)],
+    );
+    credit.send().await?.await?;
+    assert_eq!(contract.ledger_balance(token_address, owner).call().await?, U256::from(3_000));
+    assert_eq!(token.balance_of(address).call().await?, U256::from(6_000));
+
+    // Recipients withdraw their credit on their own
+    let as_alice = MultiSender::new(address, alice.clone());
+    as_alice.withdraw(token_address, U256::from(1_000)).send().await?.await?;
+    assert_eq!(token.balance_of(alice.address()).call().await?, U256::from(1_000));
+    let err = as_alice
+        .withdraw(token_address, U256::one())
+        .send()
+        .await
+        .expect_err("empty ledger");
+    assert!(matches!(
+        err.decode_contract_revert::<MultiSenderErrors>(),
+        Some(MultiSenderErrors::InsufficientLedgerBalance(_))
+    ));
+
+    // Tokens sent by mistake are recoverable, ledger balances are not
+    token.mint(address, U256::from(500)).send().await?.await?;
+    let err = contract
+        .recover_erc_20(token_address, owner, U256::from(501))
+        .send()
+        .await
+        .expect_err("reserved tokens");
+    let decoded = err.decode_contract_revert::<MultiSenderErrors>();
+    assert!(matches!(
+        decoded,
+        Some(MultiSenderErrors::InsufficientRecoverableBalance(e)) if e.available == U256::from(500)
+    ));
+    contract
+        .recover_erc_20(token_address, owner, U256::from(500))
+        .send()
+        .await?
+        .await?;
+    assert_eq!(token.balance_of(address).call().await?, U256::from(5_000));
+
+    // Without a WETH fallback a recipient rejecting ETH is refunded to the sender
+    let recipients = vec![Address::random(), token_address];
+    let amounts = vec![U256::from(1_000), U256::from(2_000)];
+    let call = contract.batch_send_eth(recipients, amounts).value(3_000);
+    let (_, successes, statuses, refunded) = call.call().await?;
+    assert_eq!((successes, statuses, refunded), (U256::one(), vec![0, 2], U256::from(2_000)));
+    call.send().await?.await?;
+    assert_eq!(client.get_balance(token_address, None).await?, U256::zero());
+    assert_eq!(client.get_balance(address, None).await?, U256::zero());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partial_batch_on_devnode() -> Result<()> {
+    let _turn = devnode::exclusive().await;
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = MultiSender::new(address, client.clone());
+
+    // Measure the gas of one recipient to give the batch room for about half of them
+    let count = 20;
+    let recipients: Vec<Address> = (0..count).map(|_| Address::random()).collect();
+    let amounts = vec![U256::from(1_000); count];
+    let single = contract
+        .batch_send_eth(recipients[..1].to_vec(), amounts[..1].to_vec())
+        .value(1_000)
+        .estimate_gas()
+        .await?;
+    let full = contract
+        .batch_send_eth(recipients.clone(), amounts.clone())
+        .value(20_000)
+        .estimate_gas()
+        .await?;
+    let per_recipient = (full - single) / (count - 1);
+    let gas = single + per_recipient * (count / 2) + U256::from(150_000);
+
+    // The batch stops before running out of gas and refunds what it did not send
+    let balance_before = client.get_balance(owner, None).await?;
+    let receipt = contract
+        .batch_send_eth_partial(recipients.clone(), amounts.clone(), 0)
+        .value(20_000)
+        .gas(gas)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+    assert_eq!(receipt.status, Some(U64::one()));
+    let stopped: Vec<BatchStoppedEarlyFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(stopped.len(), 1);
+    let next_index = stopped[0].next_index.as_usize();
+    assert!(next_index > 0 && next_index < count);
+    assert_eq!(stopped[0].recipient_count, U256::from(count));
+
+    for (i, recipient) in recipients.iter().enumerate() {
+        let expected = if i < next_index { U256::from(1_000) } else { U256::zero() };
+        assert_eq!(client.get_balance(*recipient, None).await?, expected);
+    }
+    assert_eq!(client.get_balance(address, None).await?, U256::zero());
+    let fee = receipt.gas_used.unwrap_or_default()
+        * receipt.effective_gas_price.unwrap_or_default();
+    let sent = U256::from(1_000 * next_index);
+    assert_eq!(client.get_balance(owner, None).await?, balance_before - sent - fee);
+
+    // Resuming from `nextIndex` pays the rest
+    let (rest, rest_amounts) = (recipients[next_index..].to_vec(), amounts[next_index..].to_vec());
+    contract
+        .batch_send_eth_partial(rest, rest_amounts, 0)
+        .value(U256::from(1_000 * (count - next_index)))
+        .send()
+        .await?
+        .await?;
+    for recipient in &recipients {
+        assert_eq!(client.get_balance(*recipient, None).await?, U256::from(1_000));
+    }
+    assert_eq!(contract.total_transactions().call().await?, U256::from(2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_swap_and_distribute_on_devnode() -> Result<()> {
+    let _turn = devnode::exclusive().await;
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = MultiSender::new(address, client.clone());
+    let weth = devnode::deploy_crate("mock-weth", &[])?;
+    let router_address = devnode::deploy_crate("mock-router", &[format!("{weth:?}")])?;
+    let router = MockRouter::new(router_address, client.clone());
+    let token_in_address = devnode::deploy_crate("mock-erc20", &[])?;
+    let token_out_address = devnode::deploy_crate("mock-erc20", &[])?;
+    let token_in = MockErc20::new(token_in_address, client.clone());
+    let token_out = MockErc20::new(token_out_address, client.clone());
+    token_out.mint(router_address, U256::from(100_000)).send().await?.await?;
+    contract.set_swap_router(router_address).send().await?.await?;
+    router.set_rate(U256::from(2), U256::one()).send().await?.await?;
+
+    // ETH in at 2:1, split 1:2 with the rounding dust back to the sender
+    let recipients = vec![Address::random(), Address::random()];
+    let weights = vec![U256::one(), U256::from(2)];
+    let deadline = U256::MAX;
+    let call = contract
+        .swap_eth_and_distribute(
+            token_out_address,
+            recipients.clone(),
+            weights.clone(),
+            U256::from(1_000),
+            deadline,
+        )
+        .value(500);
+    let (_, amount_out, statuses) = call.call().await?;
+    assert_eq!((amount_out, statuses), (U256::from(1_000), vec![0, 0]));
+    call.send().await?.await?;
+    assert_eq!(token_out.balance_of(recipients[0]).call().await?, U256::from(333));
+    assert_eq!(token_out.balance_of(recipients[1]).call().await?, U256::from(666));
+    assert_eq!(token_out.balance_of(owner).call().await?, U256::one());
+    assert_eq!(token_out.balance_of(address).call().await?, U256::zero());
+    assert_eq!(client.get_balance(router_address, None).await?, U256::from(500));
+
+    // Tokens in are pulled from the sender and swapped at the same rate
+    token_in.mint(owner, U256::from(1_000)).send().await?.await?;
+    token_in.approve(address, U256::from(1_000)).send().await?.await?;
+    contract
+        .swap_token_and_distribute(
+            token_in_address,
+            U256::from(1_000),
+            token_out_address,
+            recipients.clone(),
+            weights,
+            U256::from(2_000),
+            deadline,
+        )
+        .send()
+        .await?
+        .await?;
+    assert_eq!(token_out.balance_of(recipients[0]).call().await?, U256::from(333 + 666));
+    assert_eq!(token_out.balance_of(recipients[1]).call().await?, U256::from(666 + 1_333));
+    assert_eq!(token_out.balance_of(owner).call().await?, U256::from(2));
+    assert_eq!(token_in.balance_of(router_address).call().await?, U256::from(1_000));
+    assert_eq!(token_in.balance_of(address).call().await?, U256::zero());
+    assert_eq!(contract.total_transactions().call().await?, U256::from(2));
+
+    // Too little output reverts the whole swap
+    let weights = vec![U256::one(); 2];
+    let min_out = U256::from(1_001);
+    let call = contract
+        .swap_eth_and_distribute(token_out_address, recipients, weights, min_out, deadline)
+        .value(500);
+    assert!(call.send().await.is_err());
+
+    Ok(())
+}
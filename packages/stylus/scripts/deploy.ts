@@ -51,6 +51,45 @@ export default async function deployScript(deployOptions: DeployOptions) {
   //   ...deployOptions,
   // });
 
+  // EXAMPLE: Deploy YourContract behind an upgradeable ERC-1967 proxy, uncomment to try
+  // (also import getContractDataFromDeployments from "./utils/" and encodeFunctionData, parseAbi from "viem")
+  // await deployStylusContract({
+  //   contract: "your-contract",
+  //   name: "your-contract-implementation",
+  //   constructorArgs: [config.deployerAddress!],
+  //   ...deployOptions,
+  // });
+  // const implementation = getContractDataFromDeployments(
+  //   config.deploymentDir,
+  //   "your-contract-implementation",
+  //   config.chain.id.toString(),
+  // );
+  // await deployStylusContract({
+  //   contract: "erc1967-proxy",
+  //   name: "your-contract-proxy",
+  //   constructorArgs: [
+  //     implementation!.address,
+  //     encodeFunctionData({
+  //       abi: parseAbi(["function initialize(address initialOwner)"]),
+  //       args: [config.deployerAddress!],
+  //     }),
+  //   ],
+  //   ...deployOptions,
+  // });
+
+  // EXAMPLE: Deploy a factory that creates per-team MultiSender instances, uncomment to try
+  // (also import getContractDataFromDeployments from "./utils/")
+  // const multiSender = getContractDataFromDeployments(
+  //   config.deploymentDir,
+  //   "multi-sender",
+  //   config.chain.id.toString(),
+  // );
+  // await deployStylusContract({
+  //   contract: "multi-sender-factory",
+  //   constructorArgs: [multiSender!.address],
+  //   ...deployOptions,
+  // });
+
   // EXAMPLE: Deploy your contract with a custom name, uncomment to try
   // await deployStylusContract({
   //   contract: "your-contract",
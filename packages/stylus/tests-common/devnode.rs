@@ -0,0 +1,126 @@
+//!
+//! Helpers shared by the nitro-devnode integration tests of each contract crate.
+//!
+//! Start a node with `nitro-devnode/run-dev-node.sh`, then run `cargo test` in a crate.
+//! `RPC_URL` and `PRIVATE_KEY` default to the devnode endpoint and its funded dev account.
+//! Tests skip themselves when no node answers, so plain `cargo test` keeps working offline.
+//!
+
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use ethers::prelude::*;
+use eyre::{eyre, Result};
+
+/// Endpoint exposed by `run-dev-node.sh`
+pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8547";
+
+/// Pre-funded account of the nitro-devnode
+pub const DEFAULT_PRIVATE_KEY: &str =
+    "0xb6b15c8cb491557369f3c7d2c287b053eb229daa9c22138887752191c9520659";
+
+pub type DevClient = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+pub fn rpc_url() -> String {
+    dotenv::dotenv().ok();
+    std::env::var("RPC_URL").unwrap_or_else(|_| DEFAULT_RPC_URL.to_string())
+}
+
+pub fn private_key() -> String {
+    dotenv::dotenv().ok();
+    std::env::var("PRIVATE_KEY").unwrap_or_else(|_| DEFAULT_PRIVATE_KEY.to_string())
+}
+
+/// Tests of one binary run in parallel but share the dev account and its nonce, so each test
+/// holds this guard while it talks to the node
+pub async fn exclusive() -> tokio::sync::MutexGuard<'static, ()> {
+    static LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(())).lock().await
+}
+
+/// Connect the dev account to the node, or return `None` when no node is reachable
+pub async fn connect() -> Option<Arc<DevClient>> {
+    let url = rpc_url();
+    let provider = Provider::<Http>::try_from(url.as_str()).ok()?;
+    let chain_id = match tokio::time::timeout(Duration::from_secs(2), provider.get_chainid()).await
+    {
+        Ok(Ok(chain_id)) => chain_id.as_u64(),
+        _ => {
+            eprintln!("skipping: no node reachable at {url}");
+            return None;
+        }
+    };
+
+    let wallet = private_key().parse::<LocalWallet>().ok()?.with_chain_id(chain_id);
+    Some(Arc::new(SignerMiddleware::new(provider, wallet)))
+}
+
+/// Create a new random account funded with `amount` wei by the dev account
+pub async fn funded_account(client: &Arc<DevClient>, amount: U256) -> Result<Arc<DevClient>> {
+    let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng())
+        .with_chain_id(client.signer().chain_id());
+    let tx = TransactionRequest::pay(wallet.address(), amount);
+    client.send_transaction(tx, None).await?.await?;
+    Ok(Arc::new(SignerMiddleware::new(client.inner().clone(), wallet)))
+}
+
+/// Deploy the crate under test with `cargo stylus deploy` and return its address
+pub fn deploy(constructor_args: &[String]) -> Result<Address> {
+    deploy_dir(Path::new(env!("CARGO_MANIFEST_DIR")), constructor_args)
+}
+
+/// Deploy a sibling crate of `packages/stylus`, such as `mock-erc20`, and return its address
+pub fn deploy_crate(name: &str, constructor_args: &[String]) -> Result<Address> {
+    deploy_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(name), constructor_args)
+}
+
+fn deploy_dir(dir: &Path, constructor_args: &[String]) -> Result<Address> {
+    let mut command = Command::new("cargo");
+    command
+        .current_dir(dir)
+        .args(["stylus", "deploy", "--no-verify"])
+        .arg(format!("--endpoint={}", rpc_url()))
+        .arg(format!("--private-key={}", private_key()));
+    if !constructor_args.is_empty() {
+        command.arg("--constructor-args").args(constructor_args);
+    }
+
+    let output = command.output()?;
+    let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+    if !output.status.success() {
+        return Err(eyre!(
+            "cargo stylus deploy failed:\n{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    stdout
+        .lines()
+        .filter(|line| line.contains("deployed code at address:"))
+        .find_map(|line| line.split_whitespace().find_map(|word| word.parse::<Address>().ok()))
+        .ok_or_else(|| eyre!("no deployed address in cargo stylus output:\n{stdout}"))
+}
+
+/// Remove the color codes cargo stylus adds to its output
+fn strip_ansi(output: &str) -> String {
+    let mut result = String::with_capacity(output.len());
+    let mut chars = output.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip to the end of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
@@ -0,0 +1,118 @@
+//!
+//! ERC-1967 slot and UUPS upgrade logic shared by `erc1967-proxy` and the contracts behind it.
+//!
+//! Include it with `#[path = "../../uups-common/uups.rs"] mod uups;`. Each contract keeps its own
+//! authorization check in `upgrade_to_and_call` and converts `UupsError` into its `Error`.
+//!
+
+#![allow(dead_code)]
+
+use alloc::vec::Vec;
+
+use stylus_sdk::{
+    alloy_primitives::{b256, Address, B256, U256},
+    alloy_sol_types::{sol, SolCall, SolValue},
+    prelude::calls::context::Call,
+    prelude::*,
+    stylus_core::log,
+};
+
+/// ERC-1967 implementation slot, `keccak256("eip1967.proxy.implementation") - 1`
+pub const IMPLEMENTATION_SLOT: B256 =
+    b256!("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+
+// Define the upgrade event, errors and the UUPS interface
+sol! {
+    event Upgraded(address indexed implementation);
+
+    #[derive(Debug)]
+    error ERC1967InvalidImplementation(address implementation);
+    #[derive(Debug)]
+    error UpgradeCallFailed(bytes returnData);
+    #[derive(Debug)]
+    error UUPSUnauthorizedCallContext();
+
+    interface IERC1822Proxiable {
+        function proxiableUUID() external view returns (bytes32);
+    }
+}
+
+/// Errors of the shared upgrade logic, converted into each contract's `Error`
+pub enum UupsError {
+    InvalidImplementation(ERC1967InvalidImplementation),
+    UpgradeCallFailed(UpgradeCallFailed),
+    UnauthorizedCallContext(UUPSUnauthorizedCallContext),
+}
+
+/// The ERC-1967 implementation slot as a storage key
+pub fn implementation_slot() -> U256 {
+    U256::from_be_bytes(IMPLEMENTATION_SLOT.0)
+}
+
+/// Read the implementation from the storage the code runs in
+pub fn implementation<S: HostAccess>(storage: &S) -> Address {
+    Address::from_word(storage.vm().storage_load_bytes32(implementation_slot()))
+}
+
+/// Fail unless the code runs in a proxy's storage, which always holds an implementation.
+/// Calls made directly to an implementation see an empty slot.
+pub fn only_proxy<S: HostAccess>(storage: &S) -> Result<(), UupsError> {
+    if implementation(storage) == Address::ZERO {
+        return Err(UupsError::UnauthorizedCallContext(UUPSUnauthorizedCallContext {}));
+    }
+    Ok(())
+}
+
+/// Fail when the code runs in a proxy's storage, so a proxy never passes for an implementation
+pub fn not_delegated<S: HostAccess>(storage: &S) -> Result<(), UupsError> {
+    if implementation(storage) != Address::ZERO {
+        return Err(UupsError::UnauthorizedCallContext(UUPSUnauthorizedCallContext {}));
+    }
+    Ok(())
+}
+
+/// Point the proxy at `new_implementation`, then run `data` against it when not empty.
+/// Callers check that the sender may upgrade before calling this.
+pub fn upgrade_to_and_call<S: TopLevelStorage + HostAccess>(
+    storage: &mut S,
+    new_implementation: Address,
+    data: &[u8],
+) -> Result<(), UupsError> {
+    only_proxy(storage)?;
+
+    // Refuse implementations that could not be upgraded again
+    let calldata = IERC1822Proxiable::proxiableUUIDCall {}.abi_encode();
+    let uuid = storage
+        .vm()
+        .static_call(&Call::new(), new_implementation, &calldata)
+        .ok()
+        .and_then(|result| B256::abi_decode(&result, true).ok());
+    if uuid != Some(IMPLEMENTATION_SLOT) {
+        return Err(UupsError::InvalidImplementation(ERC1967InvalidImplementation {
+            implementation: new_implementation,
+        }));
+    }
+
+    unsafe {
+        storage
+            .vm()
+            .storage_cache_bytes32(implementation_slot(), new_implementation.into_word());
+    }
+    storage.vm().flush_cache(false);
+
+    log(storage.vm(), Upgraded {
+        implementation: new_implementation,
+    });
+
+    if !data.is_empty() {
+        let config = Call::new();
+        let result = unsafe { storage.vm().delegate_call(&config, new_implementation, data) };
+        if let Err(err) = result {
+            return Err(UupsError::UpgradeCallFailed(UpgradeCallFailed {
+                returnData: Vec::<u8>::from(err).into(),
+            }));
+        }
+    }
+
+    Ok(())
+}
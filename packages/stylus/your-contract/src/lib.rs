@@ -3,8 +3,11 @@
 //!
 //! A smart contract that allows changing a state variable of the contract and tracking the changes
 //! It also allows the owner to withdraw the Ether in the contract
+//! and to recover ERC20, ERC721 and ERC1155 tokens sent to it by mistake
+//! Plain ETH transfers emit a Deposit event and can be rejected by the owner
 //!
 //! This is the Stylus Rust equivalent of the Solidity YourContract.
+//! It can also run behind the `erc1967-proxy` crate and be upgraded in place (UUPS).
 //!
 
 // Allow `cargo stylus export-abi` to generate a main function.
@@ -19,12 +22,20 @@ use alloc::vec::Vec;
 
 /// Import items from the SDK. The prelude contains common traits and macros.
 use stylus_sdk::{
-    alloy_primitives::{Address, U256},
-    alloy_sol_types::sol,
+    abi::Bytes,
+    alloy_primitives::{Address, B256, U256},
+    alloy_sol_types::{sol, SolCall, SolValue},
+    prelude::calls::context::Call,
     prelude::*,
     stylus_core::log,
+    ArbResult,
 };
 
+#[path = "../../uups-common/uups.rs"]
+mod uups;
+
+use uups::{ERC1967InvalidImplementation, UUPSUnauthorizedCallContext, UpgradeCallFailed};
+
 /// Import OpenZeppelin Ownable functionality
 use openzeppelin_stylus::access::ownable::{self, IOwnable, Ownable};
 
@@ -33,6 +44,14 @@ use openzeppelin_stylus::access::ownable::{self, IOwnable, Ownable};
 pub enum Error {
     UnauthorizedAccount(ownable::OwnableUnauthorizedAccount),
     InvalidOwner(ownable::OwnableInvalidOwner),
+    AlreadyInitialized(AlreadyInitialized),
+    InvalidImplementation(ERC1967InvalidImplementation),
+    UpgradeCallFailed(UpgradeCallFailed),
+    UnauthorizedCallContext(UUPSUnauthorizedCallContext),
+    InvalidRecipient(InvalidRecipient),
+    RecoveryFailed(RecoveryFailed),
+    UnsolicitedEthRejected(UnsolicitedEthRejected),
+    UnknownFunction(UnknownFunction),
 }
 
 impl From<ownable::Error> for Error {
@@ -44,13 +63,65 @@ impl From<ownable::Error> for Error {
     }
 }
 
+impl From<uups::UupsError> for Error {
+    fn from(value: uups::UupsError) -> Self {
+        match value {
+            uups::UupsError::InvalidImplementation(e) => Error::InvalidImplementation(e),
+            uups::UupsError::UpgradeCallFailed(e) => Error::UpgradeCallFailed(e),
+            uups::UupsError::UnauthorizedCallContext(e) => Error::UnauthorizedCallContext(e),
+        }
+    }
+}
+
 // Define the GreetingChange event
 sol! {
     event GreetingChange(address indexed greetingSetter, string newGreeting, bool premium, uint256 value);
+    event ERC20Recovered(address indexed token, address indexed to, uint256 amount);
+    event ERC721Recovered(address indexed token, address indexed to, uint256 tokenId);
+    event ERC1155Recovered(address indexed token, address indexed to, uint256 id, uint256 amount);
+    event Deposit(address indexed sender, uint256 amount);
+    event UnsolicitedEthPolicyUpdated(bool rejected);
+}
+
+// Define errors for initialization
+sol! {
+    #[derive(Debug)]
+    error AlreadyInitialized();
+}
+
+// Define errors for plain ETH transfers and unknown calls
+sol! {
+    #[derive(Debug)]
+    error UnsolicitedEthRejected(address sender, uint256 amount);
+    #[derive(Debug)]
+    error UnknownFunction(bytes4 selector);
+}
+
+// Define errors and interfaces for recovering tokens sent by mistake
+sol! {
+    #[derive(Debug)]
+    error InvalidRecipient(address recipient);
+    #[derive(Debug)]
+    error RecoveryFailed(address token);
+
+    interface IERC20 {
+        function transfer(address to, uint256 amount) external returns (bool);
+    }
+
+    interface IERC721 {
+        function safeTransferFrom(address from, address to, uint256 tokenId) external;
+    }
+
+    interface IERC1155 {
+        function safeTransferFrom(address from, address to, uint256 id, uint256 value, bytes data) external;
+    }
 }
 
 // Define persistent storage using the Solidity ABI.
 // `YourContract` will be the entrypoint.
+//
+// The contract can run behind an ERC-1967 proxy, so the layout is append-only:
+// new fields go at the end and existing fields are never removed or reordered.
 sol_storage! {
     #[entrypoint]
     pub struct YourContract {
@@ -59,6 +130,8 @@ sol_storage! {
         bool premium;
         uint256 total_counter;
         mapping(address => uint256) user_greeting_counter;
+        bool initialized;
+        bool reject_unsolicited_eth;
     }
 }
 
@@ -73,6 +146,21 @@ impl YourContract {
         self.greeting.set_str("Building Unstoppable Apps!!!");
         self.premium.set(false);
         self.total_counter.set(U256::ZERO);
+        // Also locks `initialize` on implementations deployed for a proxy
+        self.initialized.set(true);
+        Ok(())
+    }
+
+    /// Initializer used instead of the constructor when deployed behind a proxy
+    pub fn initialize(&mut self, initial_owner: Address) -> Result<(), Error> {
+        // Contracts deployed before `initialized` existed already have an owner
+        if self.initialized.get() || self.ownable.owner() != Address::ZERO {
+            return Err(Error::AlreadyInitialized(AlreadyInitialized {}));
+        }
+
+        self.ownable.constructor(initial_owner)?;
+        self.greeting.set_str("Building Unstoppable Apps!!!");
+        self.initialized.set(true);
         Ok(())
     }
 
@@ -144,11 +232,180 @@ impl YourContract {
         Ok(())
     }
 
-    /// Allow contract to receive ETH (equivalent to receive() function)
+    /// Recover ERC20 tokens sent to the contract by mistake
+    /// The function can only be called by the owner of the contract
+    pub fn recover_erc20(
+        &mut self,
+        token: Address,
+        to: Address,
+        amount: U256,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+        let call = IERC20::transferCall { to, amount };
+        self.recover(token, to, &call.abi_encode())?;
+
+        log(self.vm(), ERC20Recovered { token, to, amount });
+
+        Ok(())
+    }
+
+    /// Recover an ERC721 token sent to the contract by mistake
+    /// The function can only be called by the owner of the contract
+    pub fn recover_erc721(
+        &mut self,
+        token: Address,
+        to: Address,
+        token_id: U256,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+        let call = IERC721::safeTransferFromCall {
+            from: self.vm().contract_address(),
+            to,
+            tokenId: token_id,
+        };
+        self.recover(token, to, &call.abi_encode())?;
+
+        log(
+            self.vm(),
+            ERC721Recovered {
+                token,
+                to,
+                tokenId: token_id,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Recover ERC1155 tokens sent to the contract by mistake
+    /// The function can only be called by the owner of the contract
+    pub fn recover_erc1155(
+        &mut self,
+        token: Address,
+        to: Address,
+        id: U256,
+        amount: U256,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+        let call = IERC1155::safeTransferFromCall {
+            from: self.vm().contract_address(),
+            to,
+            id,
+            value: amount,
+            data: Default::default(),
+        };
+        self.recover(token, to, &call.abi_encode())?;
+
+        log(
+            self.vm(),
+            ERC1155Recovered {
+                token,
+                to,
+                id,
+                amount,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Get the UUPS identifier, which is the ERC-1967 implementation slot
+    /// Fails through a proxy so the proxy is never taken for an implementation
+    #[selector(name = "proxiableUUID")]
+    pub fn proxiable_uuid(&self) -> Result<B256, Error> {
+        uups::not_delegated(self)?;
+        Ok(uups::IMPLEMENTATION_SLOT)
+    }
+
+    /// Upgrade the proxy to a new implementation and optionally call it
+    /// The function can only be called by the owner of the contract, through a proxy
+    #[payable]
+    pub fn upgrade_to_and_call(
+        &mut self,
+        new_implementation: Address,
+        data: Bytes,
+    ) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+        uups::upgrade_to_and_call(self, new_implementation, &data)?;
+        Ok(())
+    }
+
+    /// Gets whether plain ETH transfers to the contract are rejected
+    pub fn reject_unsolicited_eth(&self) -> bool {
+        self.reject_unsolicited_eth.get()
+    }
+
+    /// Reject or accept plain ETH transfers to the contract
+    /// The function can only be called by the owner of the contract
+    pub fn set_reject_unsolicited_eth(&mut self, rejected: bool) -> Result<(), Error> {
+        self.ownable.only_owner()?;
+        self.reject_unsolicited_eth.set(rejected);
+
+        log(self.vm(), UnsolicitedEthPolicyUpdated { rejected });
+
+        Ok(())
+    }
+
+    /// Allow contract to receive ETH, kept for callers of the old deposit method
+    #[payable]
+    pub fn receive_ether(&mut self) -> Result<(), Error> {
+        self.accept_eth()
+    }
+
+    /// Handle plain ETH transfers (equivalent to receive() function)
+    #[receive]
+    #[payable]
+    pub fn receive(&mut self) -> Result<(), Vec<u8>> {
+        Ok(self.accept_eth()?)
+    }
+
+    /// Reject calls to unknown functions, with or without ETH, so a mistyped payable call
+    /// reverts instead of being kept as a deposit. Plain transfers go to `receive`.
+    #[fallback]
     #[payable]
-    pub fn receive_ether(&self) {
-        // This function allows the contract to receive ETH
-        // The #[payable] attribute allows it to accept value
+    pub fn fallback(&mut self, calldata: &[u8]) -> ArbResult {
+        let mut selector = [0u8; 4];
+        let len = calldata.len().min(4);
+        selector[..len].copy_from_slice(&calldata[..len]);
+        Err(Error::UnknownFunction(UnknownFunction {
+            selector: selector.into(),
+        })
+        .into())
+    }
+}
+
+impl YourContract {
+    /// Accept plain ETH and emit `Deposit`, unless the owner rejects it
+    fn accept_eth(&mut self) -> Result<(), Error> {
+        let sender = self.vm().msg_sender();
+        let amount = self.vm().msg_value();
+        if self.reject_unsolicited_eth.get() {
+            return Err(Error::UnsolicitedEthRejected(UnsolicitedEthRejected { sender, amount }));
+        }
+
+        log(self.vm(), Deposit { sender, amount });
+
+        Ok(())
+    }
+
+    /// Send a recovery transfer call to `token`, which must be a contract
+    fn recover(&mut self, token: Address, to: Address, calldata: &[u8]) -> Result<(), Error> {
+        if to == Address::ZERO {
+            return Err(Error::InvalidRecipient(InvalidRecipient { recipient: to }));
+        }
+
+        // A call to an address without code succeeds with no data, so it would log a
+        // recovery that never happened
+        if self.vm().code_size(token) == 0 {
+            return Err(Error::RecoveryFailed(RecoveryFailed { token }));
+        }
+
+        let config = Call::new();
+        match self.vm().call(&config, token, calldata) {
+            Ok(result) if result.is_empty() => Ok(()),
+            Ok(result) if bool::abi_decode(&result, true).unwrap_or(false) => Ok(()),
+            _ => Err(Error::RecoveryFailed(RecoveryFailed { token })),
+        }
     }
 }
 
@@ -173,12 +430,29 @@ impl IOwnable for YourContract {
 #[cfg(test)]
 mod test {
     use super::*;
+    use super::Error;
     use stylus_sdk::testing::*;
 
+    use core::cell::Cell;
+
+    thread_local! {
+        /// Caller returned by the `msg_sender` hostio, which `Ownable::only_owner` reads
+        static HOSTIO_SENDER: Cell<[u8; 20]> = const { Cell::new([0u8; 20]) };
+    }
+
     #[no_mangle]
     pub unsafe extern "C" fn emit_log(_pointer: *const u8, _len: usize, _: usize) {}
     #[no_mangle]
-    pub unsafe extern "C" fn msg_sender(_sender: *mut u8) {}
+    pub unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        let address = HOSTIO_SENDER.with(Cell::get);
+        core::ptr::copy_nonoverlapping(address.as_ptr(), sender, address.len());
+    }
+
+    /// Set the caller seen by both the TestVM and the `msg_sender` hostio
+    fn set_sender(vm: &TestVM, sender: Address) {
+        vm.set_sender(sender);
+        HOSTIO_SENDER.with(|cell| cell.set(sender.into_array()));
+    }
 
     #[test]
     fn test_your_contract() {
@@ -191,13 +465,13 @@ mod test {
 
         assert_eq!(contract.owner(), owner_addr);
         assert_eq!(contract.greeting(), "Building Unstoppable Apps!!!");
-        assert_eq!(contract.premium(), false);
+        assert!(!contract.premium());
         assert_eq!(contract.total_counter(), U256::ZERO);
 
         // Test setting greeting without payment
         contract.set_greeting("Hello World".to_string());
         assert_eq!(contract.greeting(), "Hello World");
-        assert_eq!(contract.premium(), false);
+        assert!(!contract.premium());
         assert_eq!(contract.total_counter(), U256::from(1));
 
         // Test user greeting counter
@@ -208,8 +482,218 @@ mod test {
         vm.set_value(U256::from(100));
         contract.set_greeting("Premium Hello".to_string());
         assert_eq!(contract.greeting(), "Premium Hello");
-        assert_eq!(contract.premium(), true);
+        assert!(contract.premium());
         assert_eq!(contract.total_counter(), U256::from(2));
         assert_eq!(contract.user_greeting_counter(sender), U256::from(2));
     }
+
+    sol_storage! {
+        /// `YourContract` with a field appended, as the next implementation would declare it.
+        pub struct YourContractV2 {
+            Ownable ownable;
+            string greeting;
+            bool premium;
+            uint256 total_counter;
+            mapping(address => uint256) user_greeting_counter;
+            bool initialized;
+            bool reject_unsolicited_eth;
+            uint256 greeting_fee;
+        }
+    }
+
+    #[test]
+    fn test_upgrade() {
+        use uups::{IERC1822Proxiable, IMPLEMENTATION_SLOT};
+        let vm = TestVM::default();
+        let mut contract = YourContract::from(&vm);
+        let owner = Address::from([1u8; 20]);
+        let new_implementation = Address::from([6u8; 20]);
+        let uuid_call = IERC1822Proxiable::proxiableUUIDCall {}.abi_encode();
+        vm.mock_static_call(new_implementation, uuid_call, Ok(IMPLEMENTATION_SLOT.to_vec()));
+
+        // A directly called implementation can be queried for its UUID but never upgraded
+        assert!(contract.initialize(owner).is_ok());
+        assert_eq!(contract.proxiable_uuid().ok(), Some(IMPLEMENTATION_SLOT));
+        set_sender(&vm, owner);
+        assert!(matches!(
+            contract.upgrade_to_and_call(new_implementation, Bytes::from(vec![])),
+            Err(Error::UnauthorizedCallContext(_))
+        ));
+
+        // Fresh storage behind a proxy, which holds the current implementation
+        let vm = TestVM::default();
+        set_sender(&vm, vm.msg_sender());
+        let mut contract = YourContract::from(&vm);
+        let current_implementation = Address::from([5u8; 20]).into_word();
+        unsafe { vm.storage_cache_bytes32(uups::implementation_slot(), current_implementation) };
+        assert!(contract.proxiable_uuid().is_err());
+
+        // Proxies are set up through the initializer, exactly once
+        assert!(contract.initialize(owner).is_ok());
+        assert!(contract.initialize(Address::from([2u8; 20])).is_err());
+        assert_eq!(contract.owner(), owner);
+        assert_eq!(contract.greeting(), "Building Unstoppable Apps!!!");
+
+        contract.set_greeting("Before upgrade".to_string());
+        let setter = vm.msg_sender();
+        assert!(contract.set_reject_unsolicited_eth(true).is_err());
+
+        // Only the owner upgrades, and only to a UUPS-compatible implementation
+        let uuid_call = IERC1822Proxiable::proxiableUUIDCall {}.abi_encode();
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_err());
+        vm.mock_static_call(new_implementation, uuid_call, Ok(IMPLEMENTATION_SLOT.to_vec()));
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_err());
+
+        set_sender(&vm, owner);
+        assert!(contract.set_reject_unsolicited_eth(true).is_ok());
+        assert!(contract
+            .upgrade_to_and_call(new_implementation, Bytes::from(vec![]))
+            .is_ok());
+        assert_eq!(
+            Address::from_word(vm.storage_load_bytes32(uups::implementation_slot())),
+            new_implementation
+        );
+
+        // The appended layout reads the existing state and starts the new field empty
+        let upgraded = YourContractV2::from(&vm);
+        assert_eq!(upgraded.ownable.owner(), owner);
+        assert_eq!(upgraded.greeting.get_string(), "Before upgrade");
+        assert!(!upgraded.premium.get());
+        assert_eq!(upgraded.total_counter.get(), U256::from(1));
+        assert_eq!(upgraded.user_greeting_counter.get(setter), U256::from(1));
+        assert!(upgraded.initialized.get());
+        assert!(upgraded.reject_unsolicited_eth.get());
+        assert_eq!(upgraded.greeting_fee.get(), U256::ZERO);
+    }
+
+    #[test]
+    fn test_recover_tokens() {
+        use stylus_sdk::alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let mut contract = YourContract::from(&vm);
+        let owner = vm.msg_sender();
+        let token = Address::from([4u8; 20]);
+        let alice = Address::from([2u8; 20]);
+        let amount = U256::from(5);
+        set_sender(&vm, owner);
+        let _ = contract.constructor(owner);
+
+        // Recovered tokens need a recipient and a token contract
+        assert!(contract
+            .recover_erc20(token, Address::ZERO, amount)
+            .is_err());
+        assert!(matches!(
+            contract.recover_erc20(token, alice, amount),
+            Err(Error::RecoveryFailed(_))
+        ));
+        vm.set_code(token, vec![0xfe]);
+
+        // The owner recovers tokens and the recovery is logged
+        let transfer = IERC20::transferCall { to: alice, amount };
+        vm.mock_call(token, transfer.abi_encode(), Ok(true.abi_encode()));
+        assert!(contract.recover_erc20(token, alice, amount).is_ok());
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = ERC20Recovered::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!((event.token, event.to, event.amount), (token, alice, amount));
+
+        // NFTs are sent with safeTransferFrom, which returns no data
+        let from = vm.contract_address();
+        let id = U256::from(1);
+        let erc721 = IERC721::safeTransferFromCall { from, to: alice, tokenId: id };
+        vm.mock_call(token, erc721.abi_encode(), Ok(vec![]));
+        assert!(contract.recover_erc721(token, alice, id).is_ok());
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = ERC721Recovered::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!((event.token, event.to, event.tokenId), (token, alice, id));
+
+        let erc1155 = IERC1155::safeTransferFromCall {
+            from,
+            to: alice,
+            id,
+            value: amount,
+            data: Default::default(),
+        };
+        vm.mock_call(token, erc1155.abi_encode(), Ok(vec![]));
+        assert!(contract.recover_erc1155(token, alice, id, amount).is_ok());
+        let (topics, data) = vm.get_emitted_logs().pop().unwrap();
+        let event = ERC1155Recovered::decode_raw_log(topics, &data, true).unwrap();
+        assert_eq!(
+            (event.token, event.to, event.id, event.amount),
+            (token, alice, id, amount)
+        );
+
+        // Reverting transfers fail the recovery
+        let unowned = IERC721::safeTransferFromCall { from, to: alice, tokenId: U256::from(2) };
+        vm.mock_call(token, unowned.abi_encode(), Err(vec![]));
+        assert!(matches!(
+            contract.recover_erc721(token, alice, U256::from(2)),
+            Err(Error::RecoveryFailed(_))
+        ));
+
+        // Tokens refusing the transfer fail the recovery
+        vm.mock_call(token, transfer.abi_encode(), Ok(false.abi_encode()));
+        assert!(matches!(
+            contract.recover_erc20(token, alice, amount),
+            Err(Error::RecoveryFailed(_))
+        ));
+        vm.mock_call(token, transfer.abi_encode(), Ok(true.abi_encode()));
+
+        // Only the owner recovers tokens
+        set_sender(&vm, alice);
+        assert!(matches!(
+            contract.recover_erc20(token, alice, amount),
+            Err(Error::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.recover_erc721(token, alice, U256::from(1)),
+            Err(Error::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.recover_erc1155(token, alice, U256::from(1), amount),
+            Err(Error::UnauthorizedAccount(_))
+        ));
+    }
+
+    #[test]
+    fn test_receive_eth() {
+        let vm = TestVM::default();
+        let mut contract = YourContract::from(&vm);
+        let owner = vm.msg_sender();
+        let alice = Address::from([2u8; 20]);
+        set_sender(&vm, owner);
+        let _ = contract.constructor(owner);
+
+        // Plain transfers are accepted by default
+        set_sender(&vm, alice);
+        vm.set_value(U256::from(10));
+        assert!(contract.receive().is_ok());
+
+        // Unknown calls revert, with or without ETH
+        let selector = [0x12, 0x34, 0x56, 0x78];
+        let unknown: Vec<u8> = Error::UnknownFunction(UnknownFunction {
+            selector: selector.into(),
+        })
+        .into();
+        assert_eq!(contract.fallback(&selector).err(), Some(unknown.clone()));
+        vm.set_value(U256::ZERO);
+        assert_eq!(contract.fallback(&selector).err(), Some(unknown));
+
+        // Only the owner can reject plain transfers
+        assert!(contract.set_reject_unsolicited_eth(true).is_err());
+        set_sender(&vm, owner);
+        assert!(contract.set_reject_unsolicited_eth(true).is_ok());
+        assert!(contract.reject_unsolicited_eth());
+
+        vm.set_value(U256::from(10));
+        assert!(contract.receive().is_err());
+        assert!(contract.receive_ether().is_err());
+
+        // Premium greetings still accept ETH
+        contract.set_greeting("Premium Hello".to_string());
+        assert!(contract.premium());
+    }
 }
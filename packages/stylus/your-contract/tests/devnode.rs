@@ -0,0 +1,98 @@
+//! Integration tests against a local nitro-devnode (see `tests-common/devnode.rs`)
+
+#[path = "../../tests-common/devnode.rs"]
+mod devnode;
+
+use ethers::contract::parse_log;
+use ethers::prelude::*;
+use eyre::Result;
+
+abigen!(
+    YourContract,
+    r#"[
+        function greeting() external view returns (string)
+        function premium() external view returns (bool)
+        function totalCounter() external view returns (uint256)
+        function userGreetingCounter(address user) external view returns (uint256)
+        function setGreeting(string newGreeting) external payable
+        function withdraw() external
+        function owner() external view returns (address)
+        function transferOwnership(address newOwner) external
+        function rejectUnsolicitedEth() external view returns (bool)
+        function setRejectUnsolicitedEth(bool rejected) external
+        event GreetingChange(address indexed greetingSetter, string newGreeting, bool premium, uint256 value)
+        event Deposit(address indexed sender, uint256 amount)
+        error OwnableUnauthorizedAccount(address account)
+        error UnsolicitedEthRejected(address sender, uint256 amount)
+    ]"#
+);
+
+#[tokio::test]
+async fn test_your_contract_on_devnode() -> Result<()> {
+    let Some(client) = devnode::connect().await else {
+        return Ok(());
+    };
+    let owner = client.address();
+    let address = devnode::deploy(&[format!("{owner:?}")])?;
+    let contract = YourContract::new(address, client.clone());
+    assert_eq!(contract.owner().call().await?, owner);
+    assert_eq!(contract.greeting().call().await?, "Building Unstoppable Apps!!!");
+
+    // Premium greetings keep the sent ETH and emit GreetingChange
+    let receipt = contract
+        .set_greeting("Hello devnode".to_string())
+        .value(100)
+        .send()
+        .await?
+        .await?
+        .expect("receipt");
+    let events: Vec<GreetingChangeFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].greeting_setter, owner);
+    assert!(events[0].premium);
+    assert_eq!(events[0].value, U256::from(100));
+    assert_eq!(contract.total_counter().call().await?, U256::one());
+    assert_eq!(client.get_balance(address, None).await?, U256::from(100));
+
+    // Plain ETH transfers emit Deposit
+    let tx = TransactionRequest::pay(address, 50);
+    let receipt = client.send_transaction(tx, None).await?.await?.expect("receipt");
+    let deposits: Vec<DepositFilter> = receipt
+        .logs
+        .into_iter()
+        .filter_map(|log| parse_log(log).ok())
+        .collect();
+    assert_eq!(deposits, vec![DepositFilter {
+        sender: owner,
+        amount: U256::from(50),
+    }]);
+
+    // Non-owners are rejected with a decodable error
+    let other = devnode::funded_account(&client, U256::exp10(17)).await?;
+    let as_other = YourContract::new(address, other.clone());
+    let err = as_other.withdraw().send().await.expect_err("non-owner withdraw");
+    let decoded = err.decode_contract_revert::<YourContractErrors>();
+    assert!(matches!(
+        decoded,
+        Some(YourContractErrors::OwnableUnauthorizedAccount(e)) if e.account == other.address()
+    ));
+
+    // The owner can reject unsolicited ETH
+    contract.set_reject_unsolicited_eth(true).send().await?.await?;
+    let tx = TransactionRequest::pay(address, 1);
+    assert!(other.send_transaction(tx, None).await.is_err());
+    contract.set_reject_unsolicited_eth(false).send().await?.await?;
+
+    // Ownership moves and the owner withdraws everything
+    contract.transfer_ownership(other.address()).send().await?.await?;
+    assert_eq!(contract.owner().call().await?, other.address());
+    assert!(contract.withdraw().send().await.is_err());
+    as_other.withdraw().send().await?.await?;
+    assert_eq!(client.get_balance(address, None).await?, U256::zero());
+
+    Ok(())
+}